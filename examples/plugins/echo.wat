@@ -0,0 +1,27 @@
+;; Minimal devflow WASM plugin: replies with the request it received.
+;;
+;; Build and run (requires devflow built with `--features wasm`):
+;;   wat2wasm examples/plugins/echo.wat -o echo.wasm
+;;   devflow plugin ./echo.wasm --payload '{"hello":"wasm"}'
+(module
+  (import "devflow" "request_len" (func $request_len (result i32)))
+  (import "devflow" "request_read" (func $request_read (param i32)))
+  (import "devflow" "response_write" (func $response_write (param i32 i32)))
+
+  (memory (export "memory") 1)
+
+  ;; Response prefix; the request JSON is appended after it as `data`.
+  (data (i32.const 0) "{\22ok\22:true,\22message\22:\22echo\22,\22data\22:")
+  (global $prefix_len i32 (i32.const 35))
+
+  (func (export "run")
+    (local $len i32)
+    (local.set $len (call $request_len))
+    (call $request_read (global.get $prefix_len))
+    ;; Close the response object: prefix + request + "}".
+    (i32.store8
+      (i32.add (global.get $prefix_len) (local.get $len))
+      (i32.const 125))
+    (call $response_write
+      (i32.const 0)
+      (i32.add (i32.add (global.get $prefix_len) (local.get $len)) (i32.const 1)))))
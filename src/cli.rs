@@ -1,24 +1,178 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use clap_complete::Shell;
+use std::{net::IpAddr, path::PathBuf};
+
+use crate::{
+    commands::{dash::DEFAULT_INTERVAL_MS, logs::parse_duration, watch::DEFAULT_DEBOUNCE_MS},
+    plugin::DEFAULT_PLUGIN_TIMEOUT_SECS,
+    utils::{color::ColorChoice, config::ConfigFormat, language::Language, ports::PortRange},
+};
 
 #[derive(Debug, Parser)]
 #[command(name = "devflow", version, about = "Developer workflow automation")]
 pub struct Cli {
+    /// Merge the named profile from the config over the base settings
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Run as if devflow was started in PATH instead of the current directory
+    #[arg(short = 'C', long, global = true, value_name = "PATH")]
+    pub root: Option<PathBuf>,
+    /// Read the config from FILE instead of the project root's
+    /// .devflow.yaml; the format comes from the extension
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+    /// Print machine-readable JSON instead of human-readable text
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Also log devflow's own activity to FILE (default: devflow.log), rotated
+    /// daily
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        env = "DEVFLOW_LOG",
+        num_args = 0..=1,
+        require_equals = true
+    )]
+    pub log_file: Option<Option<PathBuf>>,
+    /// Show more of devflow's diagnostics: -v info, -vv debug, -vvv trace
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+    /// Show only errors from devflow's diagnostics
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// When to use color: auto (terminals without NO_COLOR), always or never
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t)]
+    pub color: ColorChoice,
+    /// Same as --color=never
+    #[arg(long, global = true, conflicts_with = "color")]
+    pub no_color: bool,
     #[command(subcommand)]
     pub command: Command,
 }
 
+impl Cli {
+    /// `--color`, with `--no-color` meaning `never`.
+    pub fn color_choice(&self) -> ColorChoice {
+        if self.no_color {
+            ColorChoice::Never
+        } else {
+            self.color
+        }
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
-    Up,
+    Up(UpArgs),
     Port(PortArgs),
-    Watch,
+    Watch(WatchArgs),
     Env(EnvArgs),
-    Logs,
-    Deps,
+    Logs(LogsArgs),
+    Deps(DepsArgs),
     Snap(SnapArgs),
-    Dash,
-    Init,
+    Dash(DashArgs),
+    Init(InitArgs),
     Plugin(PluginArgs),
+    /// Remove devflow's generated files under .devflow/
+    Clean(CleanArgs),
+    /// Start one configured service and stream its output
+    Run(RunArgs),
+    /// Print the JSON Schema of .devflow.yaml for editor validation
+    Schema,
+    /// Copy stdin to stdout with secrets redacted
+    Redact(RedactArgs),
+    /// Print a shell completion script to stdout
+    Completions(CompletionsArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct UpArgs {
+    /// Start services in the background and return immediately
+    #[arg(short, long)]
+    pub detach: bool,
+    /// Fail when the toolchain is missing or doesn't match the project's version file
+    #[arg(long)]
+    pub strict: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct WatchArgs {
+    /// Send a desktop notification (or ring the terminal bell) when tests fail
+    #[arg(long)]
+    pub notify: bool,
+    /// Wait until the filesystem has been quiet for MS milliseconds before running tests
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_DEBOUNCE_MS)]
+    pub debounce: u64,
+    /// Don't skip paths excluded by .gitignore files
+    #[arg(long)]
+    pub no_gitignore: bool,
+    /// Clear the screen before each test run
+    #[arg(long)]
+    pub clear: bool,
+    /// Keep CMD running and restart it on every change instead of running tests
+    #[arg(long, value_name = "CMD")]
+    pub restart: Option<String>,
+    /// Watch only DIR, relative to the project root, instead of the whole
+    /// project (repeatable)
+    #[arg(long = "path", value_name = "DIR")]
+    pub paths: Vec<PathBuf>,
+    /// Run the tests once, for FILEs if given, and exit with their exit code
+    #[arg(long, conflicts_with = "restart")]
+    pub once: bool,
+    /// With `--once`, the changed files to pick tests for, relative to the
+    /// project root (default: the full suite)
+    #[arg(value_name = "FILE", requires = "once")]
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct LogsArgs {
+    /// Keep running and print new lines as they are appended, reporting new error groups
+    #[arg(short, long)]
+    pub follow: bool,
+    /// Read PATH instead of the configured `log_file` or devflow.log (repeatable)
+    #[arg(long = "file", value_name = "PATH")]
+    pub files: Vec<PathBuf>,
+    /// How to read log lines: JSON objects, plain text, or whichever each line looks like
+    #[arg(long, value_enum, default_value = "auto")]
+    pub format: LogFormat,
+    /// Group lines at LEVEL or above instead of only errors
+    #[arg(long, value_enum, value_name = "LEVEL", default_value = "error")]
+    pub level: LogLevel,
+    /// Skip lines timestamped more than DURATION ago (e.g. 30m, 1h, 2d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub since: Option<chrono::TimeDelta>,
+    /// Show the last 4 characters of secrets in printed lines instead of hiding them entirely
+    #[arg(long)]
+    pub partial_redact: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    Auto,
+    Json,
+    Text,
+}
+
+#[derive(Debug, Args)]
+pub struct DepsArgs {
+    /// Check for newer versions of dependencies over the network
+    #[arg(long)]
+    pub online: bool,
+    /// RustSec advisory database to check `Cargo.lock` against, instead of
+    /// the copy in `.devflow/advisory-db`
+    #[arg(long, value_name = "PATH")]
+    pub advisory_db: Option<PathBuf>,
 }
 
 #[derive(Debug, Args)]
@@ -29,12 +183,49 @@ pub struct PortArgs {
     pub watch: bool,
     #[arg(short, long)]
     pub port: Option<u16>,
+    /// Inspect UDP sockets instead of TCP listeners
+    #[arg(long)]
+    pub udp: bool,
+    /// Report every occupied port in an inclusive START-END range
+    #[arg(long, value_name = "START-END", conflicts_with_all = ["port", "watch", "free"])]
+    pub range: Option<PortRange>,
+    /// With --watch, monitor only the `desired_ports` from .devflow.yaml
+    #[arg(long, requires = "watch")]
+    pub config_ports_only: bool,
+    /// Terminate the process that owns the port
+    #[arg(long)]
+    pub kill: bool,
+    /// Escalate to SIGKILL / `taskkill /F` if the process does not exit
+    #[arg(long, requires = "kill")]
+    pub force: bool,
+    /// Skip the confirmation prompt when killing
+    #[arg(short, long, requires = "kill")]
+    pub yes: bool,
+    /// With --free, the address to test (repeatable; default: 127.0.0.1 and ::1)
+    #[arg(long = "bind", value_name = "ADDR", requires = "free")]
+    pub bind: Vec<IpAddr>,
 }
 
 #[derive(Debug, Args)]
 pub struct EnvArgs {
     #[arg(value_enum)]
     pub mode: EnvMode,
+    /// With `fix`, print the keys that would be added without writing .env
+    #[arg(long)]
+    pub dry_run: bool,
+    /// With `diff`, the template to compare against (default: .env.example)
+    #[arg(long, value_name = "PATH")]
+    pub against: Option<PathBuf>,
+    /// With `export`, the shell syntax to print
+    #[arg(long, value_enum, default_value_t = ShellFormat::Posix)]
+    pub format: ShellFormat,
+    /// With `encrypt`/`decrypt`, read the key from this file instead of
+    /// DEVFLOW_ENV_KEY
+    #[arg(long, value_name = "PATH")]
+    pub key_file: Option<PathBuf>,
+    /// With `decrypt`, overwrite a .env that differs from the decrypted one
+    #[arg(long)]
+    pub force: bool,
 }
 
 #[derive(Debug, Clone, clap::ValueEnum)]
@@ -42,23 +233,170 @@ pub enum EnvMode {
     Doctor,
     Fix,
     Diff,
+    Export,
+    Encrypt,
+    Decrypt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ShellFormat {
+    /// `export KEY='value'` for sh, bash and zsh
+    Posix,
+    /// `set -gx KEY 'value'`
+    Fish,
+    /// `$env:KEY = 'value'`
+    Powershell,
 }
 
 #[derive(Debug, Args)]
 pub struct SnapArgs {
     #[arg(value_enum)]
     pub mode: SnapMode,
+    /// With `save`, the snapshot's name (default: a timestamp); with
+    /// `restore`, the snapshot to read (default: the latest)
+    #[arg(long, value_name = "NAME")]
+    pub name: Option<String>,
+    /// With `diff`, the snapshots to compare; a single one is compared with
+    /// the current state
+    #[arg(value_name = "SNAPSHOT", num_args = 0..=2)]
+    pub snapshots: Vec<String>,
+    /// With `save`, record every running process, not just the project's
+    #[arg(long)]
+    pub all: bool,
+    /// With `save`, keep secret-looking env values instead of redacting them
+    #[arg(long)]
+    pub include_secrets: bool,
+    /// With `save`, keep the last 4 characters of redacted secrets (e.g. ****3f9a)
+    #[arg(long, conflicts_with = "include_secrets")]
+    pub partial_redact: bool,
+    /// With `restore`, only list what would be started (the default)
+    #[arg(long, conflicts_with = "apply")]
+    pub dry_run: bool,
+    /// With `restore`, start the snapshotted processes that aren't running;
+    /// with --env, add the snapshot's env vars to .env
+    #[arg(long)]
+    pub apply: bool,
+    /// With `restore`, print the snapshot's env vars as shell exports
+    /// instead of restoring processes
+    #[arg(long)]
+    pub env: bool,
+    /// With `restore --env`, the shell syntax to print
+    #[arg(long, value_enum, default_value_t = ShellFormat::Posix, requires = "env")]
+    pub format: ShellFormat,
 }
 
 #[derive(Debug, Clone, clap::ValueEnum)]
 pub enum SnapMode {
     Save,
     Restore,
+    List,
+    Diff,
+}
+
+#[derive(Debug, Args)]
+pub struct DashArgs {
+    /// Refresh every MS milliseconds (100 to 10000; change live with + and -)
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_INTERVAL_MS)]
+    pub interval: u64,
+    /// Show the DISKS panel from the start (toggle it with d)
+    #[arg(long)]
+    pub disks: bool,
+    /// Print one reading as JSON and exit instead of starting the dashboard
+    #[arg(long)]
+    pub once: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /// File format of the generated config
+    #[arg(long, value_enum, default_value = "yaml")]
+    pub format: ConfigFormat,
+    /// Language whose services, test command and ignores to use (default:
+    /// detected from the project)
+    #[arg(long, value_enum, value_name = "LANG")]
+    pub template: Option<Language>,
+    /// Overwrite an existing devflow config
+    #[arg(long)]
+    pub force: bool,
+    /// Ask for services, start commands, ports and env keys, starting from
+    /// the template (skipped when stdin isn't a terminal)
+    #[arg(short, long)]
+    pub interactive: bool,
 }
 
 #[derive(Debug, Args)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct PluginArgs {
-    pub name: String,
+    #[command(subcommand)]
+    pub action: Option<PluginAction>,
+    /// Plugin to run (`devflow-plugin-<name>` on PATH or in ./plugins)
+    #[arg(required = true)]
+    pub name: Option<String>,
     #[arg(short, long)]
     pub payload: Option<String>,
+    /// Read the JSON payload from PATH, or from stdin with `-`
+    #[arg(long, value_name = "PATH", conflicts_with = "payload")]
+    pub payload_file: Option<PathBuf>,
+    /// Send the payload as a string, `{"raw": "..."}`, instead of parsing it as JSON
+    #[arg(long)]
+    pub raw: bool,
+    /// Let a WASM plugin read files under DIR (repeatable)
+    #[arg(long, value_name = "DIR")]
+    pub allow_read: Vec<PathBuf>,
+    /// Set KEY in the plugin's environment, taking its value from devflow's
+    /// environment or the project's env files (repeatable)
+    #[arg(long, value_name = "KEY")]
+    pub pass_env: Vec<String>,
+    /// Kill an executable plugin that runs longer than SECS
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_PLUGIN_TIMEOUT_SECS)]
+    pub timeout: u64,
+    /// Read newline-delimited JSON responses and print each as it arrives
+    #[arg(long)]
+    pub stream: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PluginAction {
+    /// List plugins found on PATH and in ./plugins
+    List {
+        /// Ask each plugin for its `--manifest` and show it
+        #[arg(long)]
+        manifest: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct RedactArgs {
+    /// Keep the last 4 characters of each secret (e.g. token=****3f9a)
+    #[arg(long)]
+    pub partial: bool,
+    /// Also redact matches of REGEX, or its `secret` group (repeatable)
+    #[arg(long = "pattern", value_name = "REGEX")]
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct CleanArgs {
+    /// Only list what would be removed (the default)
+    #[arg(long, conflicts_with = "yes")]
+    pub dry_run: bool,
+    /// Actually delete the listed files
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Also empty the log file `devflow logs` reads (devflow.log or `log_file`)
+    #[arg(long)]
+    pub logs: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RunArgs {
+    /// Name of the service in the config's `services`
+    pub name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate completions for
+    #[arg(value_enum)]
+    pub shell: Shell,
 }
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::{dash, deps, logs, print_json};
+use crate::{
+    cli::CleanArgs,
+    utils::{registry, services, snapshot},
+};
+
+/// Everything devflow writes under `.devflow/`, with what it is. The config
+/// lives next to `.devflow/`, never in it, so it can't be listed here.
+const ARTIFACTS: [(&str, &str); 7] = [
+    (snapshot::SNAPSHOT_DIR, "process snapshots"),
+    (
+        snapshot::LEGACY_SNAPSHOT_PATH,
+        "snapshot from older devflow",
+    ),
+    (logs::STATE_PATH, "logs error-group history"),
+    (dash::METRICS_DIR, "saved dash metrics"),
+    (services::SERVICE_LOG_DIR, "background service logs"),
+    (registry::CACHE_PATH, "package registry cache"),
+    (deps::ADVISORY_DB_DIR, "RustSec advisory database"),
+];
+
+#[derive(Debug, Serialize)]
+struct CleanReport {
+    dry_run: bool,
+    items: Vec<CleanItem>,
+    /// Bytes freed, or that would be freed on a dry run.
+    freed_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct CleanItem {
+    path: String,
+    what: &'static str,
+    action: CleanAction,
+    bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CleanAction {
+    Remove,
+    Truncate,
+}
+
+/// Lists devflow's generated files and, with `--yes`, removes them. With
+/// `--logs`, the log file `devflow logs` reads is emptied too.
+pub async fn run(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    args: CleanArgs,
+    json: bool,
+) -> Result<()> {
+    let mut items = Vec::new();
+    for (path, what) in ARTIFACTS {
+        let full = root.join(path);
+        if full.symlink_metadata().is_ok() {
+            items.push((
+                full.clone(),
+                CleanItem {
+                    path: path.to_string(),
+                    what,
+                    action: CleanAction::Remove,
+                    bytes: disk_usage(&full),
+                },
+            ));
+        }
+    }
+    if args.logs {
+        let log = logs::default_log_file(root, config, profile)?;
+        if let Ok(meta) = fs::metadata(root.join(&log)) {
+            if meta.len() > 0 {
+                items.push((
+                    root.join(&log),
+                    CleanItem {
+                        path: log.display().to_string(),
+                        what: "project log",
+                        action: CleanAction::Truncate,
+                        bytes: meta.len(),
+                    },
+                ));
+            }
+        }
+    }
+
+    let dry_run = !args.yes;
+    if !dry_run {
+        for (full, item) in &items {
+            delete(full, item.action).with_context(|| format!("failed to clean {}", item.path))?;
+        }
+        // Only goes if it is now empty, i.e. held nothing but our artifacts.
+        let _ = fs::remove_dir(root.join(".devflow"));
+    }
+
+    let report = CleanReport {
+        dry_run,
+        freed_bytes: items.iter().map(|(_, item)| item.bytes).sum(),
+        items: items.into_iter().map(|(_, item)| item).collect(),
+    };
+    if json {
+        return print_json(&report);
+    }
+
+    if report.items.is_empty() {
+        println!("nothing to clean");
+        return Ok(());
+    }
+    for item in &report.items {
+        let action = match (item.action, report.dry_run) {
+            (CleanAction::Remove, true) => "would remove",
+            (CleanAction::Remove, false) => "removed",
+            (CleanAction::Truncate, true) => "would truncate",
+            (CleanAction::Truncate, false) => "truncated",
+        };
+        println!(
+            "{} {} ({}, {})",
+            action,
+            item.path,
+            item.what,
+            dash::format_bytes(item.bytes)
+        );
+    }
+    if report.dry_run {
+        println!(
+            "{} would be freed; run `devflow clean --yes` to delete",
+            dash::format_bytes(report.freed_bytes)
+        );
+    } else {
+        println!("freed {}", dash::format_bytes(report.freed_bytes));
+    }
+    Ok(())
+}
+
+fn delete(path: &Path, action: CleanAction) -> std::io::Result<()> {
+    match action {
+        CleanAction::Truncate => fs::File::options().write(true).open(path)?.set_len(0),
+        CleanAction::Remove if path.symlink_metadata()?.is_dir() => fs::remove_dir_all(path),
+        CleanAction::Remove => fs::remove_file(path),
+    }
+}
+
+/// Total size of the files at or under `path`, not following symlinks.
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(meta) = path.symlink_metadata() else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    let mut pending: Vec<PathBuf> = vec![path.to_path_buf()];
+    let mut total = 0;
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total += meta.len();
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_usage_counts_nested_files() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let dir = td.path().join("a");
+        fs::create_dir_all(dir.join("b")).expect("mkdir");
+        fs::write(dir.join("one"), [0u8; 10]).expect("write");
+        fs::write(dir.join("b/two"), [0u8; 32]).expect("write");
+        assert_eq!(disk_usage(&dir), 42);
+        assert_eq!(disk_usage(&dir.join("one")), 10);
+        assert_eq!(disk_usage(&td.path().join("missing")), 0);
+    }
+
+    #[test]
+    fn artifacts_stay_inside_the_state_dir() {
+        for (path, _) in ARTIFACTS {
+            assert!(path.starts_with(".devflow/"), "{path}");
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use anyhow::Result;
+use clap::CommandFactory;
+use std::io;
+
+use crate::cli::{Cli, CompletionsArgs};
+
+pub async fn run(args: CompletionsArgs) -> Result<()> {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(args.shell, &mut cmd, name, &mut io::stdout());
+    Ok(())
+}
@@ -1,19 +1,64 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local, Utc};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    cursor::Show,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Alignment, Constraint, Direction, Layout},
+    buffer::Buffer,
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, Cell, Gauge, Paragraph, Row, Sparkline, Table},
+    widgets::{
+        Block, BorderType, Borders, Cell, Gauge, Paragraph, Row, Sparkline, Table, TableState,
+    },
     Terminal,
 };
-use std::{io, time::Duration};
-use sysinfo::{CpuRefreshKind, MemoryRefreshKind, ProcessesToUpdate, RefreshKind, System};
+use serde::Serialize;
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    panic,
+    path::Path,
+    time::{Duration, Instant},
+};
+use sysinfo::{
+    CpuRefreshKind, Disks, MemoryRefreshKind, Networks, Pid, ProcessRefreshKind, ProcessesToUpdate,
+    RefreshKind, System, UpdateKind,
+};
+
+use super::print_json;
+use crate::{
+    cli::DashArgs,
+    utils::{
+        color::ColorChoice,
+        gpu::{self, GpuReading},
+        ports::terminate_process,
+    },
+};
+
+/// Default for `--interval`: how often system stats are refreshed. Input is
+/// handled as it arrives.
+pub const DEFAULT_INTERVAL_MS: u64 = 400;
+/// Below this the sysinfo refresh itself becomes a noticeable CPU load.
+const MIN_INTERVAL_MS: u64 = 100;
+const MAX_INTERVAL_MS: u64 = 10_000;
+/// How far `+` and `-` move the interval.
+const INTERVAL_STEP_MS: u64 = 100;
+/// Where `s` saves the current metrics, relative to the project root.
+pub const METRICS_DIR: &str = ".devflow/dash";
+/// How long a message such as "saved ..." stays in the footer.
+const FLASH_FOR: Duration = Duration::from_secs(3);
+/// How many rows TOP PROCESSES keeps, after filtering and sorting.
+const TOP_PROCESSES: usize = 50;
+/// How many GPUs get a row of gauges; more would crowd out the processes.
+const MAX_GPUS_SHOWN: usize = 4;
 
 const LOGO: &str = r"  ██████╗ ███████╗██╗   ██╗███████╗██╗      ██████╗ ██╗    ██╗
   ██╔══██╗██╔════╝██║   ██║██╔════╝██║     ██╔═══██╗██║    ██║
@@ -34,6 +79,15 @@ fn gauge_color(pct: f64) -> Color {
     }
 }
 
+/// Resets every cell to the terminal's own colors, keeping bold, reverse
+/// and the other modifiers. Gauges and sparklines are drawn with block
+/// characters, so they stay readable.
+fn strip_colors(buf: &mut Buffer) {
+    for cell in &mut buf.content {
+        cell.set_fg(Color::Reset).set_bg(Color::Reset);
+    }
+}
+
 fn format_uptime(secs: u64) -> String {
     let d = secs / 86400;
     let h = (secs % 86400) / 3600;
@@ -48,29 +102,413 @@ fn format_uptime(secs: u64) -> String {
     }
 }
 
-fn format_bytes(bytes: u64) -> String {
+pub fn format_bytes(bytes: u64) -> String {
     let gb = bytes as f64 / (1024.0 * 1024.0 * 1024.0);
+    let mb = bytes as f64 / (1024.0 * 1024.0);
+    let kb = bytes as f64 / 1024.0;
     if gb >= 1.0 {
         format!("{:.2} GB", gb)
-    } else {
-        let mb = bytes as f64 / (1024.0 * 1024.0);
+    } else if mb >= 1.0 {
         format!("{:.0} MB", mb)
+    } else if kb >= 1.0 {
+        format!("{:.0} KB", kb)
+    } else {
+        format!("{} B", bytes)
     }
 }
 
-pub async fn run() -> Result<()> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+fn format_rate(bytes_per_sec: u64) -> String {
+    format!("{}/s", format_bytes(bytes_per_sec))
+}
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+/// Bytes per second for `bytes` moved in `elapsed`.
+fn per_second(bytes: u64, elapsed: Duration) -> u64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        (bytes as f64 / secs) as u64
+    } else {
+        0
+    }
+}
+
+/// One mounted filesystem in the DISKS panel.
+#[derive(Debug, Clone, PartialEq)]
+struct DiskRow {
+    mount: String,
+    used: u64,
+    total: u64,
+    /// Bytes per second read and written since the previous refresh.
+    read_rate: u64,
+    write_rate: u64,
+}
+
+impl DiskRow {
+    fn used_pct(&self) -> f64 {
+        if self.total > 0 {
+            self.used as f64 / self.total as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Every filesystem in `disks` with a size, fullest first, with I/O rates
+/// over the `elapsed` since the previous refresh of `disks`.
+fn disk_rows(disks: &Disks, elapsed: Duration) -> Vec<DiskRow> {
+    let mut rows = disks
+        .list()
+        .iter()
+        .filter(|disk| disk.total_space() > 0)
+        .map(|disk| {
+            let usage = disk.usage();
+            DiskRow {
+                mount: disk.mount_point().display().to_string(),
+                used: disk.total_space().saturating_sub(disk.available_space()),
+                total: disk.total_space(),
+                read_rate: per_second(usage.read_bytes, elapsed),
+                write_rate: per_second(usage.written_bytes, elapsed),
+            }
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| {
+        b.used_pct()
+            .total_cmp(&a.used_pct())
+            .then(a.mount.cmp(&b.mount))
+    });
+    rows
+}
+
+/// Received and transmitted bytes per second of each interface, busiest
+/// first, over the `elapsed` since the previous refresh of `networks`.
+fn network_rates(networks: &Networks, elapsed: Duration) -> Vec<(String, u64, u64)> {
+    let mut rates = networks
+        .list()
+        .iter()
+        .map(|(name, data)| {
+            (
+                name.clone(),
+                per_second(data.received(), elapsed),
+                per_second(data.transmitted(), elapsed),
+            )
+        })
+        .collect::<Vec<_>>();
+    rates.sort_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then(a.0.cmp(&b.0)));
+    rates
+}
+
+/// One row of the TOP PROCESSES table.
+#[derive(Debug, Clone, Serialize)]
+struct ProcRow {
+    pid: u32,
+    name: String,
+    cpu_percent: f32,
+    memory_bytes: u64,
+}
+
+/// The column TOP PROCESSES is sorted by: CPU and memory descending, pid
+/// and name ascending.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SortKey {
+    #[default]
+    Cpu,
+    Memory,
+    Pid,
+    Name,
+}
+
+impl SortKey {
+    fn from_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('c') => Some(SortKey::Cpu),
+            KeyCode::Char('m') => Some(SortKey::Memory),
+            KeyCode::Char('p') => Some(SortKey::Pid),
+            KeyCode::Char('n') => Some(SortKey::Name),
+            _ => None,
+        }
+    }
+}
+
+/// What the dashboard keeps across refreshes.
+#[derive(Debug, Default)]
+struct DashState {
+    /// Milliseconds between refreshes, within the `MIN`/`MAX_INTERVAL_MS` range.
+    interval_ms: u64,
+    sort: SortKey,
+    /// Only processes whose name or command line contains this, ignoring
+    /// case, are listed.
+    filter: String,
+    /// Whether keys are being typed into the filter after `/`.
+    editing_filter: bool,
+    /// Whether the DISKS panel is shown, from `--disks` and toggled by `d`.
+    show_disks: bool,
+    /// Whether refreshing is suspended (space), freezing the display.
+    paused: bool,
+}
+
+/// One reading of the system: what the panels draw, what `s` saves to
+/// [`METRICS_DIR`] and what `--once` prints.
+#[derive(Debug, Serialize)]
+struct Metrics {
+    /// When the `System` it was read from was last refreshed.
+    sampled_at: DateTime<Utc>,
+    cpu_percent: f32,
+    cores_percent: Vec<f32>,
+    memory: MemoryMetrics,
+    uptime_secs: u64,
+    /// All processes, before filtering.
+    process_count: usize,
+    /// The TOP PROCESSES rows: filtered, sorted and truncated.
+    processes: Vec<ProcRow>,
+    /// Filled in by the caller; left out when there are none.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    gpus: Vec<GpuReading>,
+}
 
-    let mut sys = System::new_with_specifics(
+#[derive(Debug, Serialize)]
+struct MemoryMetrics {
+    used_bytes: u64,
+    total_bytes: u64,
+    swap_used_bytes: u64,
+    swap_total_bytes: u64,
+}
+
+fn new_system() -> System {
+    System::new_with_specifics(
         RefreshKind::nothing()
             .with_memory(MemoryRefreshKind::everything())
             .with_cpu(CpuRefreshKind::everything()),
+    )
+}
+
+/// Refreshes everything [`collect_metrics`] reads.
+fn refresh_system(sys: &mut System) {
+    sys.refresh_memory();
+    sys.refresh_cpu_all();
+    // Command lines are only read for new processes; the filter matches on
+    // them.
+    sys.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing()
+            .with_memory()
+            .with_cpu()
+            .with_cmd(UpdateKind::OnlyIfNotSet),
+    );
+}
+
+/// Reads the metrics out of `sys`, keeping the top processes that pass
+/// `filter` in `sort` order.
+fn collect_metrics(
+    sys: &System,
+    sampled_at: DateTime<Utc>,
+    filter: &str,
+    sort: SortKey,
+) -> Metrics {
+    let filter = filter.to_lowercase();
+    let mut processes: Vec<ProcRow> = sys
+        .processes()
+        .values()
+        .filter_map(|p| {
+            let name = p.name().to_string_lossy().to_string();
+            let cmd = p
+                .cmd()
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" ");
+            matches_filter(&filter, &name, &cmd).then(|| ProcRow {
+                pid: p.pid().as_u32(),
+                name,
+                cpu_percent: p.cpu_usage(),
+                memory_bytes: p.memory(),
+            })
+        })
+        .collect();
+    sort_processes(&mut processes, sort);
+    processes.truncate(TOP_PROCESSES);
+
+    Metrics {
+        sampled_at,
+        cpu_percent: sys.global_cpu_usage(),
+        cores_percent: sys.cpus().iter().map(|c| c.cpu_usage()).collect(),
+        memory: MemoryMetrics {
+            used_bytes: sys.used_memory(),
+            total_bytes: sys.total_memory(),
+            swap_used_bytes: sys.used_swap(),
+            swap_total_bytes: sys.total_swap(),
+        },
+        uptime_secs: System::uptime(),
+        process_count: sys.processes().len(),
+        processes,
+        gpus: Vec::new(),
+    }
+}
+
+/// Height of the GPU row: a line of gauges per GPU, nothing without GPUs.
+fn gpu_panel_height(gpus: usize) -> u16 {
+    3 * gpus.min(MAX_GPUS_SHOWN) as u16
+}
+
+/// `--once`: prints a single reading as JSON, for CI logs and terminals
+/// that can't run the dashboard.
+async fn print_once() -> Result<()> {
+    let mut sys = new_system();
+    refresh_system(&mut sys);
+    // CPU usage is measured between two refreshes.
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+    refresh_system(&mut sys);
+    print_json(&Metrics {
+        gpus: gpu::read_all(&gpu::detect()),
+        ..collect_metrics(&sys, Utc::now(), "", SortKey::default())
+    })
+}
+
+/// Writes `metrics` to a new timestamped file in [`METRICS_DIR`], returning
+/// its path relative to `root`.
+fn save_metrics(root: &Path, metrics: &Metrics) -> Result<String> {
+    let name = Local::now().format("%Y%m%d-%H%M%S%.3f");
+    let path = format!("{METRICS_DIR}/{name}.json");
+    fs::create_dir_all(root.join(METRICS_DIR))?;
+    fs::write(root.join(&path), serde_json::to_string_pretty(metrics)?)?;
+    Ok(path)
+}
+
+fn clamp_interval(ms: u64) -> u64 {
+    ms.clamp(MIN_INTERVAL_MS, MAX_INTERVAL_MS)
+}
+
+/// Whether a process passes `filter`, which must be lowercase.
+fn matches_filter(filter: &str, name: &str, cmd: &str) -> bool {
+    filter.is_empty() || name.to_lowercase().contains(filter) || cmd.to_lowercase().contains(filter)
+}
+
+/// Orders `procs` by `key`, ties broken by pid so rows don't jump around.
+fn sort_processes(procs: &mut [ProcRow], key: SortKey) {
+    procs.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Cpu => b.cpu_percent.total_cmp(&a.cpu_percent),
+            SortKey::Memory => b.memory_bytes.cmp(&a.memory_bytes),
+            SortKey::Pid => std::cmp::Ordering::Equal,
+            SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        };
+        order.then(a.pid.cmp(&b.pid))
+    });
+}
+
+/// Moves the selection `delta` rows, staying within `len` rows.
+fn move_selection(state: &mut TableState, len: usize, delta: isize) {
+    if len == 0 {
+        state.select(None);
+        return;
+    }
+    let current = state.selected().unwrap_or(0) as isize;
+    state.select(Some((current + delta).clamp(0, len as isize - 1) as usize));
+}
+
+/// The table row under the terminal cell at `column`, `row`, for a bordered
+/// table with a one-line header drawn in `area` and scrolled by `offset`.
+fn row_at(area: Rect, offset: usize, len: usize, column: u16, row: u16) -> Option<usize> {
+    let first = area.y + 2;
+    let last = area.bottom().saturating_sub(1);
+    if column <= area.x || column >= area.right().saturating_sub(1) || row < first || row >= last {
+        return None;
+    }
+    let index = offset + usize::from(row - first);
+    (index < len).then_some(index)
+}
+
+/// SIGTERM (or its platform equivalent) to `pid`, refusing the ones that
+/// would take the system or the dashboard down.
+fn kill_process(pid: u32) -> Result<()> {
+    if pid == 1 {
+        bail!("refusing to kill pid 1");
+    }
+    if pid == std::process::id() {
+        bail!("refusing to kill devflow itself");
+    }
+    terminate_process(pid, false)
+}
+
+/// Puts the terminal back the way the shell expects it. Each step is
+/// attempted even if an earlier one fails, and running it twice is harmless.
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        Show
     );
+}
+
+/// Owns the raw-mode, alternate-screen terminal for the lifetime of the
+/// dashboard and restores it when dropped, so an error returned with `?` or a
+/// panic can't leave the user's shell unusable.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        // From here on, dropping the guard undoes whatever did get set up.
+        let guard = TerminalGuard;
+        // The default hook prints the panic message before unwinding reaches
+        // `drop`; restore first so the message lands on the normal screen.
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            previous(info);
+        }));
+        execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+        // Back to the default hook now the terminal is no longer ours. The
+        // hook can't be changed while unwinding, and is no longer needed then.
+        if !std::thread::panicking() {
+            let _ = panic::take_hook();
+        }
+    }
+}
+
+/// Runs the dashboard. Without `color` (`--color=never`, or `NO_COLOR` in
+/// auto mode) it is drawn monochrome: bold and reverse video only.
+pub async fn run(root: &Path, args: DashArgs, color: ColorChoice) -> Result<()> {
+    if args.once {
+        return print_once().await;
+    }
+    // Raw mode and the alternate screen would fail with a cryptic error or
+    // fill a pipe with escape codes.
+    if !io::stdout().is_terminal() {
+        bail!("dash needs a terminal, but stdout is not one; use `devflow dash --once` to print a JSON reading instead");
+    }
+
+    let color = color.enabled(&io::stdout());
+    let _guard = TerminalGuard::enter()?;
+
+    let backend = CrosstermBackend::new(io::stdout());
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut sys = new_system();
+    let mut sampled_at = Utc::now();
+
+    let mut networks = Networks::new_with_refreshed_list();
+    let mut networks_sampled_at = Instant::now();
+    let mut net_rates: Vec<(String, u64, u64)> = Vec::new();
+    // Total bytes per second received and transmitted, per refresh.
+    let mut net_history: Vec<u64> = Vec::new();
+
+    // Only listed once the DISKS panel is first shown.
+    let mut disks: Option<Disks> = None;
+    let mut disks_sampled_at = Instant::now();
+    let mut disk_data: Vec<DiskRow> = Vec::new();
+
+    // Found once; empty without the `gpu` feature or a supported GPU.
+    let gpu_sources = gpu::detect();
+    let mut gpu_data: Vec<GpuReading> = Vec::new();
 
     let mut cpu_history: Vec<u64> = Vec::new();
     let history_max: usize = 200;
@@ -83,38 +521,96 @@ pub async fn run() -> Result<()> {
 
     let cyan = Style::default().fg(Color::Cyan);
 
+    let mut state = DashState {
+        interval_ms: clamp_interval(args.interval),
+        show_disks: args.disks,
+        ..DashState::default()
+    };
+    let mut table_state = TableState::default();
+    // The selected row follows its process as the table re-sorts.
+    let mut selected_pid: Option<u32> = None;
+    // Where the process table was last drawn, for mapping mouse clicks.
+    let mut table_area = Rect::default();
+    // A process `k` was pressed on, waiting for `y` to confirm.
+    let mut confirm_kill: Option<(u32, String)> = None;
+    // A process sent SIGTERM, checked on the next refresh.
+    let mut killed: Option<u32> = None;
+    let mut status: Option<(String, Color)> = None;
+    // A short-lived footer message and when it was shown.
+    let mut flash: Option<(String, Color, Instant)> = None;
+    let mut last_refresh: Option<Instant> = None;
+
     loop {
-        sys.refresh_memory();
-        sys.refresh_cpu_all();
-        sys.refresh_processes(ProcessesToUpdate::All, true);
-
-        let cpu = sys.global_cpu_usage();
-        let mem_used = sys.used_memory();
-        let mem_total = sys.total_memory();
-        let swap_used = sys.used_swap();
-        let swap_total = sys.total_swap();
-        let uptime = System::uptime();
-
-        // Collect owned process data to avoid borrow issues in the draw closure
-        let mut proc_data: Vec<(String, String, f32, u64)> = sys
-            .processes()
-            .values()
-            .map(|p| {
-                (
-                    format!("{}", p.pid()),
-                    p.name().to_string_lossy().to_string(),
-                    p.cpu_usage(),
-                    p.memory(),
-                )
-            })
-            .collect();
-        proc_data.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
-        proc_data.truncate(50);
+        let interval = Duration::from_millis(state.interval_ms);
+        if !state.paused && last_refresh.is_none_or(|at| at.elapsed() >= interval) {
+            refresh_system(&mut sys);
+            gpu_data = gpu::read_all(&gpu_sources);
+            sampled_at = Utc::now();
+            last_refresh = Some(Instant::now());
+
+            cpu_history.push(sys.global_cpu_usage() as u64);
+            if cpu_history.len() > history_max {
+                cpu_history.remove(0);
+            }
+
+            networks.refresh(true);
+            net_rates = network_rates(&networks, networks_sampled_at.elapsed());
+            networks_sampled_at = Instant::now();
+            net_history.push(net_rates.iter().map(|(_, rx, tx)| rx + tx).sum());
+            if net_history.len() > history_max {
+                net_history.remove(0);
+            }
+
+            if state.show_disks {
+                match &mut disks {
+                    Some(disks) => {
+                        disks.refresh(true);
+                        disk_data = disk_rows(disks, disks_sampled_at.elapsed());
+                    }
+                    None => {
+                        let listed = Disks::new_with_refreshed_list();
+                        // No I/O counted yet, so no rates until the next refresh.
+                        disk_data = disk_rows(&listed, Duration::ZERO);
+                        disks = Some(listed);
+                    }
+                }
+                disks_sampled_at = Instant::now();
+            }
+            tick += 1;
 
-        let proc_count = sys.processes().len();
+            if let Some(pid) = killed.take() {
+                status = Some(if sys.process(Pid::from_u32(pid)).is_some() {
+                    (format!("pid {pid} is still running"), Color::Yellow)
+                } else {
+                    (format!("pid {pid} exited"), Color::Green)
+                });
+            }
+        }
+
+        // Owned data, to avoid borrow issues in the draw closure
+        let metrics = Metrics {
+            gpus: gpu_data.clone(),
+            ..collect_metrics(&sys, sampled_at, &state.filter, state.sort)
+        };
+        let cpu = metrics.cpu_percent;
+        let mem_used = metrics.memory.used_bytes;
+        let mem_total = metrics.memory.total_bytes;
+        let swap_used = metrics.memory.swap_used_bytes;
+        let swap_total = metrics.memory.swap_total_bytes;
+        let uptime = metrics.uptime_secs;
+        let proc_data = &metrics.processes;
+
+        let followed = selected_pid.and_then(|pid| proc_data.iter().position(|p| p.pid == pid));
+        match followed {
+            Some(index) => table_state.select(Some(index)),
+            None => move_selection(&mut table_state, proc_data.len(), 0),
+        }
+        selected_pid = table_state.selected().map(|index| proc_data[index].pid);
+
+        let proc_count = metrics.process_count;
 
         // Per-core CPU data
-        let core_data: Vec<f32> = sys.cpus().iter().map(|c| c.cpu_usage()).collect();
+        let core_data = &metrics.cores_percent;
         let core_count = core_data.len();
 
         // Memory percentages
@@ -129,13 +625,6 @@ pub async fn run() -> Result<()> {
             0.0
         };
 
-        // CPU history for sparkline
-        cpu_history.push(cpu as u64);
-        if cpu_history.len() > history_max {
-            cpu_history.remove(0);
-        }
-
-        tick += 1;
         let spinner = SPIN[tick % SPIN.len()];
 
         terminal.draw(|f| {
@@ -144,11 +633,12 @@ pub async fn run() -> Result<()> {
             let main_layout = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
-                    Constraint::Length(10), // banner
-                    Constraint::Length(3),  // gauges row
-                    Constraint::Min(8),     // middle section
-                    Constraint::Length(5),  // sparkline
-                    Constraint::Length(3),  // footer
+                    Constraint::Length(10),                                   // banner
+                    Constraint::Length(3),                                    // gauges row
+                    Constraint::Length(gpu_panel_height(metrics.gpus.len())), // GPUs, if any
+                    Constraint::Min(8),                                       // middle section
+                    Constraint::Length(5),                                    // sparkline
+                    Constraint::Length(3),                                    // footer
                 ])
                 .split(area);
 
@@ -316,13 +806,64 @@ pub async fn run() -> Result<()> {
             );
             f.render_widget(procs_widget, gauge_layout[3]);
 
+            // ══════════════════════════════════════════════════════
+            //  GPUS — utilization / memory per GPU (zero height if none)
+            // ══════════════════════════════════════════════════════
+            let shown_gpus = &metrics.gpus[..metrics.gpus.len().min(MAX_GPUS_SHOWN)];
+            let gpu_layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Length(3); shown_gpus.len()])
+                .split(main_layout[2]);
+            for (i, (reading, row)) in shown_gpus.iter().zip(gpu_layout.iter()).enumerate() {
+                let halves = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .split(*row);
+                let util = reading.utilization_percent as f64;
+                let mem_pct = reading.memory_pct();
+                let gpu_gauges = [
+                    (
+                        format!(" \u{25a3} GPU{i} {} ", reading.name),
+                        util,
+                        format!("{util:.0}%"),
+                    ),
+                    (
+                        format!(" \u{25c8} GPU{i} MEM "),
+                        mem_pct,
+                        format!(
+                            "{} / {}",
+                            format_bytes(reading.memory_used_bytes),
+                            format_bytes(reading.memory_total_bytes)
+                        ),
+                    ),
+                ];
+                for ((title, pct, label), area) in gpu_gauges.into_iter().zip(halves.iter()) {
+                    let gauge = Gauge::default()
+                        .block(
+                            Block::default()
+                                .borders(Borders::ALL)
+                                .border_style(cyan)
+                                .title(Span::styled(title, Style::default().fg(Color::Cyan))),
+                        )
+                        .gauge_style(Style::default().fg(gauge_color(pct)).bg(Color::DarkGray))
+                        .ratio((pct / 100.0).clamp(0.0, 1.0))
+                        .label(Span::styled(
+                            label,
+                            Style::default()
+                                .fg(Color::White)
+                                .add_modifier(Modifier::BOLD),
+                        ));
+                    f.render_widget(gauge, *area);
+                }
+            }
+
             // ══════════════════════════════════════════════════════
             //  MIDDLE — CPU Cores (left) + Top Processes (right)
             // ══════════════════════════════════════════════════════
             let mid_layout = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
-                .split(main_layout[2]);
+                .split(main_layout[3]);
 
             // ─── Left: CPU Core Bars ───
             let bar_area_w = mid_layout[0].width.saturating_sub(2) as usize;
@@ -358,49 +899,117 @@ pub async fn run() -> Result<()> {
                             .add_modifier(Modifier::BOLD),
                     )),
             );
-            f.render_widget(cores_widget, mid_layout[0]);
+            let left_layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(if state.show_disks {
+                    [Constraint::Percentage(50), Constraint::Percentage(50)]
+                } else {
+                    [Constraint::Percentage(100), Constraint::Length(0)]
+                })
+                .split(mid_layout[0]);
+            f.render_widget(cores_widget, left_layout[0]);
+
+            // ─── Left, below the cores: Disks ───
+            if state.show_disks {
+                let disk_lines: Vec<Line> = disk_data
+                    .iter()
+                    .flat_map(|disk| {
+                        let pct = disk.used_pct();
+                        let filled = ((pct / 100.0) * bar_w as f64) as usize;
+                        let empty = bar_w.saturating_sub(filled);
+                        let color = gauge_color(pct);
+                        [
+                            Line::from(vec![
+                                Span::styled(
+                                    format!(" {} ", disk.mount),
+                                    Style::default().fg(Color::White),
+                                ),
+                                Span::styled(
+                                    format!(
+                                        "{} / {}",
+                                        format_bytes(disk.used),
+                                        format_bytes(disk.total)
+                                    ),
+                                    Style::default().fg(Color::DarkGray),
+                                ),
+                                Span::styled(
+                                    format!("  R {}", format_rate(disk.read_rate)),
+                                    Style::default().fg(Color::Green),
+                                ),
+                                Span::styled(
+                                    format!("  W {}", format_rate(disk.write_rate)),
+                                    Style::default().fg(Color::Magenta),
+                                ),
+                            ]),
+                            Line::from(vec![
+                                Span::raw("     "),
+                                Span::styled("\u{2588}".repeat(filled), Style::default().fg(color)),
+                                Span::styled(
+                                    "\u{2591}".repeat(empty),
+                                    Style::default().fg(Color::DarkGray),
+                                ),
+                                Span::styled(format!(" {:>5.1}%", pct), Style::default().fg(color)),
+                            ]),
+                        ]
+                    })
+                    .collect();
+                let disks_widget = Paragraph::new(disk_lines).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(cyan)
+                        .title(Span::styled(
+                            " \u{25b6} DISKS ",
+                            Style::default()
+                                .fg(Color::Cyan)
+                                .add_modifier(Modifier::BOLD),
+                        )),
+                );
+                f.render_widget(disks_widget, left_layout[1]);
+            }
 
             // ─── Right: Top Processes Table ───
-            let header = Row::new(vec![
-                Cell::from("  PID").style(
-                    Style::default()
-                        .fg(Color::Cyan)
-                        .add_modifier(Modifier::BOLD),
-                ),
-                Cell::from("PROCESS").style(
-                    Style::default()
-                        .fg(Color::Cyan)
-                        .add_modifier(Modifier::BOLD),
-                ),
-                Cell::from("CPU%").style(
-                    Style::default()
-                        .fg(Color::Cyan)
-                        .add_modifier(Modifier::BOLD),
-                ),
-                Cell::from("MEM").style(
-                    Style::default()
-                        .fg(Color::Cyan)
-                        .add_modifier(Modifier::BOLD),
-                ),
-            ])
+            let header = Row::new(
+                [
+                    ("  PID", SortKey::Pid),
+                    ("PROCESS", SortKey::Name),
+                    ("CPU%", SortKey::Cpu),
+                    ("MEM", SortKey::Memory),
+                ]
+                .map(|(title, key)| {
+                    let style = Style::default().add_modifier(Modifier::BOLD);
+                    if key == state.sort {
+                        let arrow = if matches!(key, SortKey::Cpu | SortKey::Memory) {
+                            "\u{25bc}"
+                        } else {
+                            "\u{25b2}"
+                        };
+                        Cell::from(format!("{title}{arrow}"))
+                            .style(style.fg(Color::Yellow).add_modifier(Modifier::UNDERLINED))
+                    } else {
+                        Cell::from(title).style(style.fg(Color::Cyan))
+                    }
+                }),
+            )
             .style(Style::default().bg(Color::DarkGray));
 
             let proc_rows: Vec<Row> = proc_data
                 .iter()
-                .map(|(pid, name, cpu_u, mem)| {
-                    let color = if *cpu_u > 50.0 {
+                .map(|p| {
+                    let color = if p.cpu_percent > 50.0 {
                         Color::Red
-                    } else if *cpu_u > 20.0 {
+                    } else if p.cpu_percent > 20.0 {
                         Color::Yellow
                     } else {
                         Color::White
                     };
                     Row::new(vec![
-                        Cell::from(format!("  {}", pid))
+                        Cell::from(format!("  {}", p.pid))
+                            .style(Style::default().fg(Color::DarkGray)),
+                        Cell::from(p.name.clone()).style(Style::default().fg(Color::White)),
+                        Cell::from(format!("{:.1}", p.cpu_percent))
+                            .style(Style::default().fg(color)),
+                        Cell::from(format_bytes(p.memory_bytes))
                             .style(Style::default().fg(Color::DarkGray)),
-                        Cell::from(name.clone()).style(Style::default().fg(Color::White)),
-                        Cell::from(format!("{:.1}", cpu_u)).style(Style::default().fg(color)),
-                        Cell::from(format_bytes(*mem)).style(Style::default().fg(Color::DarkGray)),
                     ])
                 })
                 .collect();
@@ -412,58 +1021,144 @@ pub async fn run() -> Result<()> {
                 Constraint::Length(12),
             ];
 
-            let proc_table = Table::new(proc_rows, widths).header(header).block(
+            let proc_table = Table::new(proc_rows, widths)
+                .header(header)
+                .highlight_style(if color {
+                    Style::default()
+                        .bg(Color::Blue)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+                })
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(cyan)
+                        .title(Span::styled(
+                            " \u{25b6} TOP PROCESSES ",
+                            Style::default()
+                                .fg(Color::Cyan)
+                                .add_modifier(Modifier::BOLD),
+                        )),
+                );
+            table_area = mid_layout[1];
+            f.render_stateful_widget(proc_table, mid_layout[1], &mut table_state);
+
+            // ══════════════════════════════════════════════════════
+            //  SPARKLINE — CPU usage history
+            // ══════════════════════════════════════════════════════
+            let spark = Sparkline::default()
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(cyan)
+                        .title(Span::styled(
+                            " \u{25b6} CPU HISTORY ",
+                            Style::default()
+                                .fg(Color::Cyan)
+                                .add_modifier(Modifier::BOLD),
+                        )),
+                )
+                .data(&cpu_history)
+                .max(100)
+                .style(Style::default().fg(Color::Cyan));
+            let history_layout = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Percentage(40),
+                    Constraint::Percentage(30),
+                    Constraint::Percentage(30),
+                ])
+                .split(main_layout[4]);
+            f.render_widget(spark, history_layout[0]);
+
+            // ─── Network: per-interface rates and total throughput ───
+            let net_lines: Vec<Line> = net_rates
+                .iter()
+                .map(|(name, rx, tx)| {
+                    Line::from(vec![
+                        Span::styled(format!(" {:<10}", name), Style::default().fg(Color::White)),
+                        Span::styled(
+                            format!(" \u{2193}{:>10}", format_rate(*rx)),
+                            Style::default().fg(Color::Green),
+                        ),
+                        Span::styled(
+                            format!(" \u{2191}{:>10}", format_rate(*tx)),
+                            Style::default().fg(Color::Magenta),
+                        ),
+                    ])
+                })
+                .collect();
+            let net_widget = Paragraph::new(net_lines).block(
                 Block::default()
                     .borders(Borders::ALL)
                     .border_style(cyan)
                     .title(Span::styled(
-                        " \u{25b6} TOP PROCESSES ",
+                        " \u{25b6} NETWORK ",
                         Style::default()
                             .fg(Color::Cyan)
                             .add_modifier(Modifier::BOLD),
                     )),
             );
-            f.render_widget(proc_table, mid_layout[1]);
+            f.render_widget(net_widget, history_layout[1]);
 
-            // ══════════════════════════════════════════════════════
-            //  SPARKLINE — CPU usage history
-            // ══════════════════════════════════════════════════════
-            let spark = Sparkline::default()
+            let net_total = net_history.last().copied().unwrap_or(0);
+            let net_spark = Sparkline::default()
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
                         .border_style(cyan)
                         .title(Span::styled(
-                            " \u{25b6} CPU HISTORY ",
+                            format!(" \u{25b6} NET {} ", format_rate(net_total)),
                             Style::default()
                                 .fg(Color::Cyan)
                                 .add_modifier(Modifier::BOLD),
                         )),
                 )
-                .data(&cpu_history)
-                .max(100)
-                .style(Style::default().fg(Color::Cyan));
-            f.render_widget(spark, main_layout[3]);
+                .data(&net_history)
+                .style(Style::default().fg(Color::Green));
+            f.render_widget(net_spark, history_layout[2]);
 
             // ══════════════════════════════════════════════════════
             //  FOOTER — keybindings + monitoring status
             // ══════════════════════════════════════════════════════
-            let footer = Paragraph::new(Line::from(vec![
+            let key_style = Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD);
+            let mut footer_spans = vec![
                 Span::styled(format!(" {} ", spinner), Style::default().fg(Color::Cyan)),
-                Span::styled(
-                    "MONITORING",
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(Modifier::BOLD),
-                ),
+                if state.paused {
+                    Span::styled(
+                        "PAUSED",
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    Span::styled(
+                        "MONITORING",
+                        Style::default()
+                            .fg(Color::Green)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                },
                 Span::styled("  \u{2502}  ", Style::default().fg(Color::DarkGray)),
-                Span::styled(
-                    "[q]",
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
-                ),
+                Span::styled("[q]", key_style),
                 Span::styled(" Quit  ", Style::default().fg(Color::White)),
+                Span::styled("[\u{2191}\u{2193}]", key_style),
+                Span::styled(" Select  ", Style::default().fg(Color::White)),
+                Span::styled("[k]", key_style),
+                Span::styled(" Kill  ", Style::default().fg(Color::White)),
+                Span::styled("[c/m/p/n]", key_style),
+                Span::styled(" Sort  ", Style::default().fg(Color::White)),
+                Span::styled("[/]", key_style),
+                Span::styled(" Filter  ", Style::default().fg(Color::White)),
+                Span::styled("[d]", key_style),
+                Span::styled(" Disks  ", Style::default().fg(Color::White)),
+                Span::styled("[space]", key_style),
+                Span::styled(" Pause  ", Style::default().fg(Color::White)),
+                Span::styled("[s]", key_style),
+                Span::styled(" Save  ", Style::default().fg(Color::White)),
                 Span::styled("\u{2502}  ", Style::default().fg(Color::DarkGray)),
                 Span::styled(
                     format!("{} cores", core_count),
@@ -475,29 +1170,356 @@ pub async fn run() -> Result<()> {
                     Style::default().fg(Color::White),
                 ),
                 Span::styled("  \u{2502}  ", Style::default().fg(Color::DarkGray)),
-                Span::styled("\u{27f3} 400ms", Style::default().fg(Color::DarkGray)),
-            ]))
-            .alignment(Alignment::Center)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .border_type(BorderType::Double)
-                    .border_style(cyan),
-            );
-            f.render_widget(footer, main_layout[4]);
+                Span::styled("[+/-]", key_style),
+                Span::styled(
+                    format!(" \u{27f3} {}ms", state.interval_ms),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ];
+            if !state.filter.is_empty() {
+                footer_spans.push(Span::styled(
+                    "  \u{2502}  ",
+                    Style::default().fg(Color::DarkGray),
+                ));
+                footer_spans.push(Span::styled(
+                    format!("filter: {} ", state.filter),
+                    Style::default().fg(Color::Magenta),
+                ));
+                footer_spans.push(Span::styled("[Esc]", key_style));
+                footer_spans.push(Span::styled(" Clear", Style::default().fg(Color::White)));
+            }
+            let flashed = flash
+                .as_ref()
+                .filter(|(_, _, at)| at.elapsed() < FLASH_FOR)
+                .map(|(message, color, _)| (message, color));
+            if let Some((message, color)) = flashed.or(status.as_ref().map(|(m, c)| (m, c))) {
+                footer_spans.push(Span::styled(
+                    "  \u{2502}  ",
+                    Style::default().fg(Color::DarkGray),
+                ));
+                footer_spans.push(Span::styled(message.clone(), Style::default().fg(*color)));
+            }
+            let footer_line = match &confirm_kill {
+                Some((pid, name)) => Line::from(vec![
+                    Span::styled(
+                        format!(" Send SIGTERM to {name} (pid {pid})? "),
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled("[y/N]", key_style),
+                ]),
+                None if state.editing_filter => Line::from(vec![
+                    Span::styled(
+                        format!(" Filter: {}\u{2588}  ", state.filter),
+                        Style::default()
+                            .fg(Color::Magenta)
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled("[Enter]", key_style),
+                    Span::styled(" Keep  ", Style::default().fg(Color::White)),
+                    Span::styled("[Esc]", key_style),
+                    Span::styled(" Clear", Style::default().fg(Color::White)),
+                ]),
+                None => Line::from(footer_spans),
+            };
+            let footer = Paragraph::new(footer_line)
+                .alignment(Alignment::Center)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Double)
+                        .border_style(cyan),
+                );
+            f.render_widget(footer, main_layout[5]);
+            if !color {
+                strip_colors(f.buffer_mut());
+            }
         })?;
 
-        if event::poll(Duration::from_millis(400))? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('q') {
-                    break;
+        // While paused the loop only wakes up to redraw, e.g. to drop an
+        // expired flash message.
+        let next_refresh = match last_refresh {
+            _ if state.paused => interval,
+            Some(at) => interval.saturating_sub(at.elapsed()),
+            None => Duration::ZERO,
+        };
+        if !event::poll(next_refresh)? {
+            continue;
+        }
+        match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                if let Some((pid, _)) = confirm_kill.take() {
+                    status = Some(if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                        match kill_process(pid) {
+                            Ok(()) => {
+                                killed = Some(pid);
+                                (format!("sent SIGTERM to pid {pid}"), Color::White)
+                            }
+                            Err(err) => (err.to_string(), Color::Red),
+                        }
+                    } else {
+                        ("kill cancelled".to_string(), Color::DarkGray)
+                    });
+                    continue;
+                }
+                if state.editing_filter {
+                    match key.code {
+                        KeyCode::Char(c) => state.filter.push(c),
+                        KeyCode::Backspace => {
+                            state.filter.pop();
+                        }
+                        KeyCode::Enter => state.editing_filter = false,
+                        KeyCode::Esc => {
+                            state.filter.clear();
+                            state.editing_filter = false;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') => break,
+                    KeyCode::Char('/') => state.editing_filter = true,
+                    KeyCode::Char(' ') => state.paused = !state.paused,
+                    KeyCode::Char('s') => {
+                        flash = Some(match save_metrics(root, &metrics) {
+                            Ok(path) => (format!("saved {path}"), Color::Green, Instant::now()),
+                            Err(err) => (format!("save failed: {err}"), Color::Red, Instant::now()),
+                        });
+                    }
+                    KeyCode::Char('d') => {
+                        state.show_disks = !state.show_disks;
+                        // Fill the panel now rather than on the next tick.
+                        last_refresh = None;
+                    }
+                    KeyCode::Char('+' | '=') => {
+                        state.interval_ms = clamp_interval(state.interval_ms + INTERVAL_STEP_MS);
+                    }
+                    KeyCode::Char('-') => {
+                        state.interval_ms =
+                            clamp_interval(state.interval_ms.saturating_sub(INTERVAL_STEP_MS));
+                    }
+                    KeyCode::Esc => state.filter.clear(),
+                    KeyCode::Up => move_selection(&mut table_state, proc_data.len(), -1),
+                    KeyCode::Down => move_selection(&mut table_state, proc_data.len(), 1),
+                    KeyCode::Char('k') => {
+                        confirm_kill = table_state
+                            .selected()
+                            .map(|index| (proc_data[index].pid, proc_data[index].name.clone()));
+                    }
+                    code => {
+                        if let Some(sort) = SortKey::from_key(code) {
+                            state.sort = sort;
+                        }
+                    }
                 }
             }
+            Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                let clicked = row_at(
+                    table_area,
+                    table_state.offset(),
+                    proc_data.len(),
+                    mouse.column,
+                    mouse.row,
+                );
+                if clicked.is_some() {
+                    table_state.select(clicked);
+                }
+            }
+            _ => {}
         }
+        selected_pid = table_state.selected().map(|index| proc_data[index].pid);
     }
 
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monochrome_keeps_text_and_modifiers() {
+        let mut buf = Buffer::empty(Rect::new(0, 0, 10, 1));
+        buf.set_string(
+            0,
+            0,
+            "CPU",
+            Style::default()
+                .fg(Color::Cyan)
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+        );
+        strip_colors(&mut buf);
+        let cell = &buf[(0, 0)];
+        assert_eq!(cell.symbol(), "C");
+        assert_eq!((cell.fg, cell.bg), (Color::Reset, Color::Reset));
+        assert!(cell.modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn selection_moves_within_the_table() {
+        let mut state = TableState::default();
+        move_selection(&mut state, 3, 0);
+        assert_eq!(state.selected(), Some(0));
+        move_selection(&mut state, 3, -1);
+        assert_eq!(state.selected(), Some(0));
+        move_selection(&mut state, 3, 5);
+        assert_eq!(state.selected(), Some(2));
+        // The table shrank under the selection.
+        move_selection(&mut state, 2, 0);
+        assert_eq!(state.selected(), Some(1));
+        move_selection(&mut state, 0, 1);
+        assert_eq!(state.selected(), None);
+    }
+
+    #[test]
+    fn processes_sort_by_the_chosen_column() {
+        let row = |pid, name: &str, cpu_percent, memory_bytes| ProcRow {
+            pid,
+            name: name.into(),
+            cpu_percent,
+            memory_bytes,
+        };
+        let mut procs = vec![
+            row(30, "node", 5.0, 300),
+            row(10, "Cargo", 50.0, 100),
+            row(20, "bash", 5.0, 900),
+        ];
+        let pids = |procs: &[ProcRow]| procs.iter().map(|p| p.pid).collect::<Vec<_>>();
+        sort_processes(&mut procs, SortKey::Cpu);
+        assert_eq!(pids(&procs), [10, 20, 30]);
+        sort_processes(&mut procs, SortKey::Memory);
+        assert_eq!(pids(&procs), [20, 30, 10]);
+        sort_processes(&mut procs, SortKey::Pid);
+        assert_eq!(pids(&procs), [10, 20, 30]);
+        sort_processes(&mut procs, SortKey::Name);
+        assert_eq!(pids(&procs), [20, 10, 30]);
+    }
+
+    #[test]
+    fn filter_matches_name_or_command_ignoring_case() {
+        assert!(matches_filter("", "bash", ""));
+        assert!(matches_filter("node", "Node", "/usr/bin/node"));
+        assert!(matches_filter(
+            "server.js",
+            "node",
+            "node /app/Server.js --port 3000"
+        ));
+        assert!(!matches_filter("python", "node", "node server.js"));
+    }
+
+    #[test]
+    fn intervals_are_clamped() {
+        assert_eq!(clamp_interval(0), MIN_INTERVAL_MS);
+        assert_eq!(clamp_interval(DEFAULT_INTERVAL_MS), DEFAULT_INTERVAL_MS);
+        assert_eq!(clamp_interval(60_000), MAX_INTERVAL_MS);
+    }
+
+    #[test]
+    fn sizes_and_rates_use_readable_units() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(64 * 1024), "64 KB");
+        assert_eq!(format_bytes(300 * 1024 * 1024), "300 MB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024 / 2), "1.50 GB");
+        assert_eq!(format_rate(2048), "2 KB/s");
+        assert_eq!(per_second(1000, Duration::from_millis(500)), 2000);
+        assert_eq!(per_second(1000, Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn disk_usage_is_a_percentage_of_the_size() {
+        let disk = |used, total| DiskRow {
+            mount: "/".into(),
+            used,
+            total,
+            read_rate: 0,
+            write_rate: 0,
+        };
+        assert_eq!(disk(25, 100).used_pct(), 25.0);
+        assert_eq!(disk(0, 0).used_pct(), 0.0);
+    }
+
+    #[test]
+    fn clicks_map_to_rows_below_the_header() {
+        let area = Rect::new(10, 5, 40, 10);
+        // Border at y=5, header at y=6, rows from y=7 to the border at y=14.
+        assert_eq!(row_at(area, 0, 50, 20, 6), None);
+        assert_eq!(row_at(area, 0, 50, 20, 7), Some(0));
+        assert_eq!(row_at(area, 0, 50, 20, 13), Some(6));
+        assert_eq!(row_at(area, 0, 50, 20, 14), None);
+        assert_eq!(row_at(area, 4, 50, 20, 8), Some(5));
+        assert_eq!(row_at(area, 0, 2, 20, 9), None);
+        assert_eq!(row_at(area, 0, 50, 10, 7), None);
+    }
+
+    #[test]
+    fn metrics_are_saved_as_json_under_the_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let metrics = Metrics {
+            sampled_at: Utc::now(),
+            cpu_percent: 12.5,
+            cores_percent: vec![10.0, 15.0],
+            memory: MemoryMetrics {
+                used_bytes: 1024,
+                total_bytes: 4096,
+                swap_used_bytes: 0,
+                swap_total_bytes: 0,
+            },
+            uptime_secs: 60,
+            process_count: 1,
+            processes: vec![ProcRow {
+                pid: 42,
+                name: "node".into(),
+                cpu_percent: 3.0,
+                memory_bytes: 512,
+            }],
+            gpus: Vec::new(),
+        };
+
+        let path = save_metrics(dir.path(), &metrics).unwrap();
+        assert!(path.starts_with(".devflow/dash/") && path.ends_with(".json"));
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.path().join(&path)).unwrap()).unwrap();
+        assert_eq!(saved["cores_percent"], serde_json::json!([10.0, 15.0]));
+        assert_eq!(saved["memory"]["total_bytes"], 4096);
+        assert_eq!(saved["processes"][0]["name"], "node");
+        assert!(saved.get("gpus").is_none());
+    }
+
+    #[test]
+    fn metrics_are_collected_from_the_system() {
+        let mut sys = new_system();
+        refresh_system(&mut sys);
+        let own_pid = std::process::id();
+
+        let metrics = collect_metrics(&sys, Utc::now(), "", SortKey::Pid);
+        assert_eq!(metrics.cores_percent.len(), sys.cpus().len());
+        assert!(metrics.memory.total_bytes > 0);
+        assert!(metrics.process_count >= metrics.processes.len());
+        assert!(metrics.processes.len() <= TOP_PROCESSES);
+        assert!(metrics.processes.windows(2).all(|w| w[0].pid < w[1].pid));
+
+        let own_name = sys
+            .process(Pid::from_u32(own_pid))
+            .unwrap()
+            .name()
+            .to_string_lossy();
+        let filtered = collect_metrics(&sys, Utc::now(), &own_name.to_uppercase(), SortKey::Cpu);
+        assert!(filtered.processes.iter().any(|p| p.pid == own_pid));
+        // Built at runtime so no command line (say, a shell running this
+        // test) can contain it.
+        let missing = format!("no-process-{own_pid}-{}", Utc::now().timestamp_micros());
+        let none = collect_metrics(&sys, Utc::now(), &missing, SortKey::Cpu);
+        assert!(none.processes.is_empty());
+        assert_eq!(none.process_count, sys.processes().len());
+    }
+
+    #[test]
+    fn the_gpu_row_is_only_as_tall_as_the_gpus_shown() {
+        assert_eq!(gpu_panel_height(0), 0);
+        assert_eq!(gpu_panel_height(2), 6);
+        assert_eq!(gpu_panel_height(8), gpu_panel_height(MAX_GPUS_SHOWN));
+    }
+}
@@ -1,49 +1,1886 @@
-use anyhow::Result;
-use std::{env, fs};
+use anyhow::{Context, Result};
+use globset::Glob;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::Duration,
+};
 
-use crate::utils::language::{detect_project_language, Language};
+use super::print_json;
+use crate::{
+    cli::DepsArgs,
+    utils::{
+        language::{
+            detect_node_package_manager, detect_project_language, Language, NodePackageManager,
+        },
+        registry::{self, Registry},
+    },
+};
 
-pub async fn run() -> Result<()> {
-    let root = env::current_dir()?;
-    match detect_project_language(&root) {
-        Language::Python => python_report(&root),
-        Language::Node => node_report(&root),
-        Language::Rust => rust_report(&root),
-        Language::Go | Language::Unknown => {
-            println!("deps analysis not yet available for this project type")
+/// Dependency overview for the detected project type.
+#[derive(Debug, Serialize)]
+struct DepsReport {
+    /// `None` when the project type has no deps analysis yet.
+    ecosystem: Option<&'static str>,
+    /// Manifest and lock files checked, in display order.
+    files: Vec<FileCheck>,
+    declared_packages: Option<usize>,
+    /// Direct dependencies in manifest order; empty where manifests aren't
+    /// parsed yet.
+    dependencies: Vec<Dependency>,
+    /// Third-party packages pinned by the lock file, transitive ones included.
+    locked_packages: Option<usize>,
+    /// Tool that manages the dependencies, where it can be told from the
+    /// lock files or manifest.
+    package_manager: Option<&'static str>,
+    /// Packages declared in more than one manifest file.
+    overlaps: Vec<Overlap>,
+    /// `go.mod` details besides its requirements (Go only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    go_module: Option<GoModule>,
+    /// Dependencies with a newer release, from `--online` checks.
+    outdated: Vec<Outdated>,
+    /// Locked crates with a RustSec advisory (Rust only).
+    advisories: Vec<Advisory>,
+    /// Follow-up hints keyed by topic, in display order.
+    notes: Vec<Note>,
+}
+
+#[derive(Debug, Serialize)]
+struct Dependency {
+    name: String,
+    /// Version requirement as declared, or where the package comes from
+    /// (`path:...`, `git:...`) when it has none.
+    requirement: String,
+    kind: DepKind,
+    /// Workspace member or manifest file that declares it, when there are
+    /// several.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    declared_in: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DepKind {
+    Normal,
+    Dev,
+    Build,
+    /// Python extras (`[project.optional-dependencies]`).
+    Optional,
+    /// Go modules required only by other modules (`// indirect`).
+    Indirect,
+}
+
+impl DepKind {
+    const ALL: [DepKind; 5] = [
+        DepKind::Normal,
+        DepKind::Dev,
+        DepKind::Build,
+        DepKind::Optional,
+        DepKind::Indirect,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            DepKind::Normal => "normal",
+            DepKind::Dev => "dev",
+            DepKind::Build => "build",
+            DepKind::Optional => "optional",
+            DepKind::Indirect => "indirect",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Overlap {
+    name: String,
+    /// Whether the files ask for different requirements rather than
+    /// repeating the same one.
+    conflicting: bool,
+    /// `file: requirement` for each declaration.
+    declarations: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FileCheck {
+    name: &'static str,
+    present: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct GoModule {
+    path: String,
+    go_version: Option<String>,
+    /// `replace` directives as written, e.g. `example.com/a => ../a`.
+    replace: Vec<String>,
+    /// `exclude` directives as written, e.g. `example.com/b v1.2.0`.
+    exclude: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Outdated {
+    name: String,
+    current: String,
+    /// `None` when the registry couldn't be asked.
+    latest: Option<String>,
+    /// `major`, `minor` or `patch`, by semver.
+    bump: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Advisory {
+    /// e.g. `RUSTSEC-2021-0003`.
+    id: String,
+    name: String,
+    version: String,
+    title: String,
+    /// Version requirements that contain the fix; empty if there is none.
+    patched: Vec<String>,
+    /// `unmaintained`, `unsound` or `notice` for advisories that aren't
+    /// vulnerabilities; these don't fail the command.
+    informational: Option<String>,
+}
+
+impl Advisory {
+    fn is_vulnerability(&self) -> bool {
+        self.informational.is_none()
+    }
+}
+
+enum AdvisoryScan {
+    Checked(Vec<Advisory>),
+    /// Why nothing was checked.
+    Skipped(String),
+}
+
+#[derive(Debug, Serialize)]
+struct Note {
+    topic: &'static str,
+    hint: String,
+}
+
+pub async fn run(root: &Path, args: DepsArgs, json: bool) -> Result<()> {
+    let (mut report, registry) = match detect_project_language(root) {
+        Language::Python => (Some(python_report(root)?), Some(Registry::PyPi)),
+        Language::Node => (Some(node_report(root)?), Some(Registry::Npm)),
+        Language::Rust => (
+            Some(rust_report(root, &args).await?),
+            Some(Registry::CratesIo),
+        ),
+        // `go_report` asks the Go toolchain itself.
+        Language::Go => (Some(go_report(root, args.online).await?), None),
+        Language::Java => (Some(java_report(root)?), None),
+        Language::Ruby => (Some(ruby_report(root)?), None),
+        Language::Php => (Some(php_report(root)?), None),
+        Language::Unknown => (None, None),
+    };
+    if let (Some(report), Some(registry)) = (&mut report, registry) {
+        if args.online {
+            check_outdated(root, report, registry).await?;
+        } else {
+            report.notes.push(Note {
+                topic: "outdated packages",
+                hint: "run devflow deps --online to check".into(),
+            });
+        }
+    }
+
+    match (&report, json) {
+        (Some(report), true) => print_json(report)?,
+        (Some(report), false) => print_report(report),
+        (None, true) => print_json(&DepsReport {
+            ecosystem: None,
+            files: Vec::new(),
+            declared_packages: None,
+            dependencies: Vec::new(),
+            locked_packages: None,
+            package_manager: None,
+            overlaps: Vec::new(),
+            go_module: None,
+            outdated: Vec::new(),
+            advisories: Vec::new(),
+            notes: Vec::new(),
+        })?,
+        (None, false) => println!("deps analysis not yet available for this project type"),
+    }
+
+    let vulnerable = report.as_ref().map_or(0, |report| {
+        report
+            .advisories
+            .iter()
+            .filter(|a| a.is_vulnerability())
+            .count()
+    });
+    if vulnerable > 0 {
+        anyhow::bail!("{vulnerable} vulnerable crate version(s) in Cargo.lock");
+    }
+    Ok(())
+}
+
+fn print_report(report: &DepsReport) {
+    println!("{} deps", report.ecosystem.unwrap_or("unknown"));
+    for file in &report.files {
+        println!("{}: {}", file.name, file.present);
+    }
+    if let Some(module) = &report.go_module {
+        match &module.go_version {
+            Some(version) => println!("module: {} (go {})", module.path, version),
+            None => println!("module: {}", module.path),
+        }
+        for replace in &module.replace {
+            println!("replace: {}", replace);
+        }
+        for exclude in &module.exclude {
+            println!("exclude: {}", exclude);
+        }
+    }
+    if let Some(count) = report.declared_packages {
+        println!("declared packages: {}", count);
+    }
+    if !report.dependencies.is_empty() {
+        let counts = DepKind::ALL
+            .iter()
+            .filter_map(|kind| {
+                let n = report
+                    .dependencies
+                    .iter()
+                    .filter(|d| d.kind == *kind)
+                    .count();
+                // Kinds only some ecosystems have are left out when empty.
+                let common = matches!(kind, DepKind::Normal | DepKind::Dev | DepKind::Build);
+                (n > 0 || common).then(|| format!("{} {}", n, kind.name()))
+            })
+            .collect::<Vec<_>>();
+        println!("direct dependencies: {}", counts.join(", "));
+        for dep in &report.dependencies {
+            let mut tags = Vec::new();
+            if dep.kind != DepKind::Normal {
+                tags.push(dep.kind.name());
+            }
+            if let Some(member) = &dep.declared_in {
+                tags.push(member);
+            }
+            if tags.is_empty() {
+                println!("  {} {}", dep.name, dep.requirement);
+            } else {
+                println!("  {} {} ({})", dep.name, dep.requirement, tags.join(", "));
+            }
+        }
+    }
+    if let Some(count) = report.locked_packages {
+        println!("locked packages: {}", count);
+    }
+    if let Some(manager) = report.package_manager {
+        println!("package manager: {}", manager);
+    }
+    for overlap in &report.overlaps {
+        let label = if overlap.conflicting {
+            "conflict"
+        } else {
+            "duplicate"
+        };
+        println!(
+            "{}: {} ({})",
+            label,
+            overlap.name,
+            overlap.declarations.join(", ")
+        );
+    }
+    for update in &report.outdated {
+        let latest = update.latest.as_deref().unwrap_or("unknown");
+        match update.bump {
+            Some(bump) => println!(
+                "outdated: {} {} -> {} ({})",
+                update.name, update.current, latest, bump
+            ),
+            None => println!("outdated: {} {} -> {}", update.name, update.current, latest),
+        }
+    }
+    for advisory in &report.advisories {
+        let fixed = if advisory.patched.is_empty() {
+            "no fixed version".to_string()
+        } else {
+            format!("fixed in {}", advisory.patched.join(", "))
+        };
+        println!(
+            "{}: {} {} {} ({}), {}",
+            advisory.informational.as_deref().unwrap_or("vulnerable"),
+            advisory.name,
+            advisory.version,
+            advisory.id,
+            advisory.title,
+            fixed
+        );
+    }
+    for note in &report.notes {
+        println!("{}: {}", note.topic, note.hint);
+    }
+}
+
+fn python_report(root: &Path) -> Result<DepsReport> {
+    let mut dependencies = Vec::new();
+    let mut visited = BTreeSet::new();
+    for (file, kind) in [
+        ("requirements.txt", DepKind::Normal),
+        ("requirements-dev.txt", DepKind::Dev),
+    ] {
+        let path = root.join(file);
+        if path.exists() {
+            read_requirements(root, &path, kind, &mut visited, &mut dependencies)?;
+        }
+    }
+    let pyproject_path = root.join("pyproject.toml");
+    let pyproject = if pyproject_path.exists() {
+        let pyproject = read_toml(&pyproject_path)?;
+        dependencies.extend(pyproject_dependencies(&pyproject));
+        Some(pyproject)
+    } else {
+        None
+    };
+
+    let overlaps = python_overlaps(&dependencies);
+    let files = dependencies
+        .iter()
+        .filter_map(|d| d.declared_in.as_deref())
+        .collect::<BTreeSet<_>>();
+    if files.len() < 2 {
+        for dep in &mut dependencies {
+            dep.declared_in = None;
+        }
+    }
+
+    let lock_files = ["poetry.lock", "pdm.lock", "uv.lock"];
+    let lock = lock_files.iter().find(|lock| root.join(lock).exists());
+    let locked_packages = match lock {
+        Some(lock) => {
+            let lock = read_toml(&root.join(lock))?;
+            Some(
+                lock.get("package")
+                    .and_then(|p| p.as_array())
+                    .map_or(0, Vec::len),
+            )
+        }
+        None => None,
+    };
+    let tool = |name: &str| {
+        pyproject
+            .as_ref()
+            .and_then(|p| p.get("tool")?.get(name))
+            .is_some()
+    };
+    let package_manager = match lock {
+        Some(&"poetry.lock") => Some("poetry"),
+        Some(&"pdm.lock") => Some("pdm"),
+        Some(_) => Some("uv"),
+        None if tool("poetry") => Some("poetry"),
+        None if tool("pdm") => Some("pdm"),
+        None if tool("uv") => Some("uv"),
+        None if !dependencies.is_empty() => Some("pip"),
+        None => None,
+    };
+
+    let mut checks = vec![
+        FileCheck {
+            name: "requirements",
+            present: root.join("requirements.txt").exists(),
+        },
+        FileCheck {
+            name: "pyproject.toml",
+            present: pyproject.is_some(),
+        },
+    ];
+    checks.extend(lock_files.iter().map(|lock| FileCheck {
+        name: lock,
+        present: root.join(lock).exists(),
+    }));
+
+    Ok(DepsReport {
+        ecosystem: Some("python"),
+        files: checks,
+        declared_packages: Some(distinct_normal(&dependencies)),
+        dependencies,
+        locked_packages,
+        package_manager,
+        overlaps,
+        go_module: None,
+        outdated: Vec::new(),
+        advisories: Vec::new(),
+        notes: vec![Note {
+            topic: "license_risk_summary",
+            hint: "unknown (offline mode)".into(),
+        }],
+    })
+}
+
+/// Reads a pip requirements file, following `-r`/`--requirement` includes
+/// relative to the including file. Each file is read once.
+fn read_requirements(
+    root: &Path,
+    path: &Path,
+    kind: DepKind,
+    visited: &mut BTreeSet<PathBuf>,
+    deps: &mut Vec<Dependency>,
+) -> Result<()> {
+    if !visited.insert(path.to_path_buf()) {
+        return Ok(());
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let label = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string();
+    for line in content.replace("\\\n", " ").lines() {
+        let line = strip_comment(line);
+        if let Some(include) = line
+            .strip_prefix("--requirement")
+            .or_else(|| line.strip_prefix("-r"))
+        {
+            let include = include.trim_start_matches([' ', '=']).trim();
+            let base = path.parent().unwrap_or(root);
+            read_requirements(root, &base.join(include), kind, visited, deps)?;
+            continue;
+        }
+        // Other options (-c, -e, --index-url, ...) don't declare a package.
+        if line.is_empty() || line.starts_with('-') {
+            continue;
+        }
+        if let Some((name, requirement)) = parse_requirement(line) {
+            deps.push(Dependency {
+                name,
+                requirement,
+                kind,
+                declared_in: Some(label.clone()),
+            });
         }
     }
     Ok(())
 }
 
-fn python_report(root: &std::path::Path) {
-    let req = root.join("requirements.txt");
-    let lock = root.join("poetry.lock");
-    println!("python deps");
-    println!("requirements: {}", req.exists());
-    println!("poetry.lock: {}", lock.exists());
-    println!("license_risk_summary: unknown (offline mode)");
+/// Drops a `#` comment, which needs whitespace before it unless it starts
+/// the line (a `#` inside a URL fragment is kept).
+fn strip_comment(line: &str) -> &str {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return "";
+    }
+    match line.find(" #").or_else(|| line.find("\t#")) {
+        Some(at) => line[..at].trim(),
+        None => line,
+    }
+}
+
+/// Splits a PEP 508 requirement such as `requests[socks]>=2.31,<3 ;
+/// python_version < "3.12"` into the name and its version specifier, `*` when
+/// unconstrained or `url:...` for a direct reference.
+fn parse_requirement(line: &str) -> Option<(String, String)> {
+    let line = line.split(';').next()?.trim();
+    let end = line
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(line.len());
+    let name = &line[..end];
+    if name.is_empty() {
+        return None;
+    }
+    let mut rest = line[end..].trim_start();
+    if rest.starts_with('[') {
+        rest = rest
+            .split_once(']')
+            .map_or("", |(_, after)| after)
+            .trim_start();
+    }
+    let requirement = match rest.strip_prefix('@') {
+        Some(url) => format!("url:{}", url.trim()),
+        None if rest.is_empty() => "*".to_string(),
+        None => rest
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .replace(' ', ""),
+    };
+    Some((name.to_string(), requirement))
+}
+
+/// Dependencies declared in `pyproject.toml`: PEP 621 `[project]`
+/// dependencies and extras, PEP 735 `[dependency-groups]`, Poetry's and PDM's
+/// own tables, and `[build-system] requires`.
+fn pyproject_dependencies(pyproject: &toml::Value) -> Vec<Dependency> {
+    let mut deps = Vec::new();
+    let mut pep508 = |list: Option<&toml::Value>, kind: DepKind| {
+        for entry in list.and_then(|l| l.as_array()).into_iter().flatten() {
+            // Skips PEP 735 `{include-group = "..."}` entries.
+            if let Some((name, requirement)) = entry.as_str().and_then(parse_requirement) {
+                deps.push(Dependency {
+                    name,
+                    requirement,
+                    kind,
+                    declared_in: Some("pyproject.toml".to_string()),
+                });
+            }
+        }
+    };
+    let project = pyproject.get("project");
+    let tool = pyproject.get("tool");
+    pep508(project.and_then(|p| p.get("dependencies")), DepKind::Normal);
+    for extra in table_values(project.and_then(|p| p.get("optional-dependencies"))) {
+        pep508(Some(extra), DepKind::Optional);
+    }
+    for group in table_values(pyproject.get("dependency-groups")) {
+        pep508(Some(group), DepKind::Dev);
+    }
+    for group in table_values(tool.and_then(|t| t.get("pdm")?.get("dev-dependencies"))) {
+        pep508(Some(group), DepKind::Dev);
+    }
+    pep508(
+        pyproject
+            .get("build-system")
+            .and_then(|b| b.get("requires")),
+        DepKind::Build,
+    );
+
+    let poetry = tool.and_then(|t| t.get("poetry"));
+    let mut poetry_tables = vec![
+        (poetry.and_then(|p| p.get("dependencies")), DepKind::Normal),
+        (poetry.and_then(|p| p.get("dev-dependencies")), DepKind::Dev),
+    ];
+    for group in table_values(poetry.and_then(|p| p.get("group"))) {
+        poetry_tables.push((group.get("dependencies"), DepKind::Dev));
+    }
+    for (table, kind) in poetry_tables {
+        let Some(table) = table.and_then(|t| t.as_table()) else {
+            continue;
+        };
+        for (name, spec) in table {
+            // The interpreter constraint, not a package.
+            if name == "python" {
+                continue;
+            }
+            deps.push(Dependency {
+                name: name.clone(),
+                requirement: requirement(name, spec, None),
+                kind,
+                declared_in: Some("pyproject.toml".to_string()),
+            });
+        }
+    }
+    deps
+}
+
+/// The values of a TOML table, e.g. the groups of `[dependency-groups]`.
+fn table_values(value: Option<&toml::Value>) -> Vec<&toml::Value> {
+    value
+        .and_then(|v| v.as_table())
+        .map(|t| t.values().collect())
+        .unwrap_or_default()
 }
 
-fn node_report(root: &std::path::Path) {
+/// Packages declared in more than one file, compared by PEP 503 normalized
+/// name (`Foo_Bar` and `foo-bar` are the same package).
+fn python_overlaps(deps: &[Dependency]) -> Vec<Overlap> {
+    let mut by_name: BTreeMap<String, Vec<&Dependency>> = BTreeMap::new();
+    for dep in deps {
+        by_name
+            .entry(normalize_package_name(&dep.name))
+            .or_default()
+            .push(dep);
+    }
+    by_name
+        .into_iter()
+        .filter(|(_, decls)| {
+            let files = decls
+                .iter()
+                .map(|d| &d.declared_in)
+                .collect::<BTreeSet<_>>();
+            files.len() > 1
+        })
+        .map(|(name, decls)| {
+            let requirements = decls
+                .iter()
+                .map(|d| &d.requirement)
+                .collect::<BTreeSet<_>>();
+            Overlap {
+                name,
+                conflicting: requirements.len() > 1,
+                declarations: decls
+                    .iter()
+                    .map(|d| {
+                        let file = d.declared_in.as_deref().unwrap_or("?");
+                        format!("{}: {}", file, d.requirement)
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Lowercases `name` and folds runs of `-`, `_` and `.` into `-`, the way
+/// PyPI (and, for `-`/`_`, crates.io) compares names.
+fn normalize_package_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+/// Distinct names among the normal (runtime) dependencies.
+fn distinct_normal(deps: &[Dependency]) -> usize {
+    deps.iter()
+        .filter(|d| d.kind == DepKind::Normal)
+        .map(|d| normalize_package_name(&d.name))
+        .collect::<BTreeSet<_>>()
+        .len()
+}
+
+fn node_report(root: &Path) -> Result<DepsReport> {
     let pkg = root.join("package.json");
-    let lock = root.join("package-lock.json");
-    println!("node deps");
-    println!("package.json: {}", pkg.exists());
-    println!("lock file: {}", lock.exists());
-    if let Ok(s) = fs::read_to_string(pkg) {
-        if let Ok(v) = serde_json::from_str::<serde_json::Value>(&s) {
-            let dep_count = v["dependencies"].as_object().map(|m| m.len()).unwrap_or(0);
-            println!("declared packages: {}", dep_count);
+    let manifest = fs::read_to_string(&pkg)
+        .ok()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok());
+    let declared_packages = manifest
+        .as_ref()
+        .map(|v| v["dependencies"].as_object().map(|m| m.len()).unwrap_or(0));
+    let mut dependencies = Vec::new();
+    for (table, kind) in [
+        ("dependencies", DepKind::Normal),
+        ("devDependencies", DepKind::Dev),
+        ("optionalDependencies", DepKind::Optional),
+    ] {
+        let Some(entries) = manifest.as_ref().and_then(|v| v[table].as_object()) else {
+            continue;
+        };
+        for (name, requirement) in entries {
+            dependencies.push(Dependency {
+                name: name.clone(),
+                requirement: requirement.as_str().unwrap_or("*").to_string(),
+                kind,
+                declared_in: None,
+            });
+        }
+    }
+    let manager = detect_node_package_manager(root);
+    let lockfile = manager
+        .lockfiles()
+        .iter()
+        .find(|file| root.join(file).exists());
+    let locked_packages = node_lock_entries(root, manager)?.map(|entries| entries.len());
+    Ok(DepsReport {
+        ecosystem: Some("node"),
+        files: vec![
+            FileCheck {
+                name: "package.json",
+                present: pkg.exists(),
+            },
+            FileCheck {
+                name: lockfile.unwrap_or(&manager.lockfiles()[0]),
+                present: lockfile.is_some(),
+            },
+        ],
+        declared_packages,
+        dependencies,
+        locked_packages,
+        package_manager: Some(manager.name()),
+        overlaps: Vec::new(),
+        go_module: None,
+        outdated: Vec::new(),
+        advisories: Vec::new(),
+        notes: Vec::new(),
+    })
+}
+
+/// Packages pinned by a Node lock file as `(name, version)`, one per
+/// install. npm's top-level installs come before nested ones. `None`
+/// without a lock file or for formats that aren't read (bun's).
+fn node_lock_entries(
+    root: &Path,
+    manager: NodePackageManager,
+) -> Result<Option<Vec<(String, String)>>> {
+    let Some(path) = manager
+        .lockfiles()
+        .iter()
+        .map(|file| root.join(file))
+        .find(|path| path.exists())
+    else {
+        return Ok(None);
+    };
+    let content = fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()));
+    let entries = match manager {
+        NodePackageManager::Npm => {
+            let lock: serde_json::Value = serde_json::from_str(&content?)
+                .with_context(|| format!("parsing {}", path.display()))?;
+            // lockfileVersion 2+ keys installs by path; version 1 by name.
+            let mut top_level = Vec::new();
+            let mut nested = Vec::new();
+            if let Some(packages) = lock["packages"].as_object() {
+                for (path, package) in packages {
+                    let (Some((prefix, name)), Some(version)) = (
+                        path.rsplit_once("node_modules/"),
+                        package["version"].as_str(),
+                    ) else {
+                        continue;
+                    };
+                    let entry = (name.to_string(), version.to_string());
+                    if prefix.is_empty() {
+                        top_level.push(entry);
+                    } else {
+                        nested.push(entry);
+                    }
+                }
+            } else if let Some(deps) = lock["dependencies"].as_object() {
+                for (name, package) in deps {
+                    if let Some(version) = package["version"].as_str() {
+                        top_level.push((name.clone(), version.to_string()));
+                    }
+                }
+            }
+            top_level.extend(nested);
+            top_level
+        }
+        NodePackageManager::Yarn => yarn_lock_entries(&content?),
+        NodePackageManager::Pnpm => {
+            let lock: serde_yaml::Value = serde_yaml::from_str(&content?)
+                .with_context(|| format!("parsing {}", path.display()))?;
+            lock.get("packages")
+                .and_then(|p| p.as_mapping())
+                .into_iter()
+                .flatten()
+                .filter_map(|(key, _)| {
+                    // `/react@18.2.0` (v6), `react@18.2.0` (v9), with an
+                    // optional `(peer@version)` suffix.
+                    let key = key.as_str()?.trim_start_matches('/');
+                    let key = key.split('(').next().unwrap_or(key);
+                    let (name, version) = split_package_spec(key)?;
+                    Some((name.to_string(), version.to_string()))
+                })
+                .collect()
+        }
+        NodePackageManager::Bun => return Ok(None),
+    };
+    Ok(Some(entries))
+}
+
+/// Entries of a `yarn.lock`, classic (`version "1.2.3"`) or Berry
+/// (`version: 1.2.3`) format.
+fn yarn_lock_entries(content: &str) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    let mut current: Option<String> = None;
+    for line in content.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            // `"@babel/core@^7.0.0", "@babel/core@^7.1.0":`
+            let spec = line
+                .trim_end_matches(':')
+                .split(',')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_matches('"');
+            current = split_package_spec(spec)
+                .map(|(name, _)| name.to_string())
+                .filter(|name| name != "__metadata");
+            continue;
+        }
+        let Some(version) = line.trim().strip_prefix("version") else {
+            continue;
+        };
+        if let Some(name) = current.take() {
+            let version = version.trim_start_matches(':').trim().trim_matches('"');
+            entries.push((name, version.to_string()));
         }
     }
-    println!("outdated packages: run npm outdated for full list");
+    entries
+}
+
+/// Splits `name@version` at the last `@`, keeping a scope's leading `@`.
+fn split_package_spec(spec: &str) -> Option<(&str, &str)> {
+    let at = spec.get(1..)?.rfind('@')? + 1;
+    Some((&spec[..at], &spec[at + 1..]))
 }
 
-fn rust_report(root: &std::path::Path) {
-    let lock = root.join("Cargo.lock");
-    println!("rust deps");
-    println!("cargo.lock: {}", lock.exists());
-    println!("top transitive bloat: run cargo tree -e features -i <crate>");
-    println!("license_risk_summary: run cargo deny when available");
+async fn rust_report(root: &Path, args: &DepsArgs) -> Result<DepsReport> {
+    let manifest_path = root.join("Cargo.toml");
+    let manifest = read_toml(&manifest_path)?;
+    let workspace = manifest.get("workspace");
+    let workspace_deps = workspace.and_then(|ws| ws.get("dependencies"));
+
+    let mut crates = Vec::new();
+    if manifest.get("package").is_some() {
+        crates.push(manifest.clone());
+    }
+    if let Some(ws) = workspace {
+        let excluded = ws
+            .get("exclude")
+            .map(|patterns| expand_members(root, patterns))
+            .unwrap_or_default();
+        for dir in ws
+            .get("members")
+            .map(|patterns| expand_members(root, patterns))
+            .unwrap_or_default()
+        {
+            if dir == root || excluded.contains(&dir) {
+                continue;
+            }
+            let member = dir.join("Cargo.toml");
+            if member.exists() {
+                crates.push(read_toml(&member)?);
+            }
+        }
+    }
+
+    let multi_crate = crates.len() > 1;
+    let mut dependencies = Vec::new();
+    for krate in &crates {
+        let member = multi_crate
+            .then(|| {
+                krate
+                    .get("package")?
+                    .get("name")?
+                    .as_str()
+                    .map(str::to_string)
+            })
+            .flatten();
+        for (name, spec, kind) in manifest_dependencies(krate) {
+            dependencies.push(Dependency {
+                requirement: requirement(&name, spec, workspace_deps),
+                name,
+                kind,
+                declared_in: member.clone(),
+            });
+        }
+    }
+    let declared_packages = distinct_normal(&dependencies);
+
+    let lock_path = root.join("Cargo.lock");
+    let locked_packages = if lock_path.exists() {
+        let lock = read_toml(&lock_path)?;
+        let packages = lock.get("package").and_then(|p| p.as_array());
+        // Path and workspace crates have no `source`.
+        Some(packages.map_or(0, |packages| {
+            packages
+                .iter()
+                .filter(|p| p.get("source").is_some())
+                .count()
+        }))
+    } else {
+        None
+    };
+
+    let (advisories, advisory_note) = match rust_advisories(root, args).await? {
+        AdvisoryScan::Checked(found) => {
+            let vulnerable = found.iter().filter(|a| a.is_vulnerability()).count();
+            let hint = format!(
+                "{} vulnerable, {} other",
+                vulnerable,
+                found.len() - vulnerable
+            );
+            (
+                found,
+                Note {
+                    topic: "advisories",
+                    hint,
+                },
+            )
+        }
+        AdvisoryScan::Skipped(hint) => (
+            Vec::new(),
+            Note {
+                topic: "advisories",
+                hint,
+            },
+        ),
+    };
+
+    Ok(DepsReport {
+        ecosystem: Some("rust"),
+        files: vec![
+            FileCheck {
+                name: "Cargo.toml",
+                present: true,
+            },
+            FileCheck {
+                name: "cargo.lock",
+                present: locked_packages.is_some(),
+            },
+        ],
+        declared_packages: Some(declared_packages),
+        dependencies,
+        locked_packages,
+        package_manager: Some("cargo"),
+        overlaps: Vec::new(),
+        go_module: None,
+        outdated: Vec::new(),
+        advisories,
+        notes: vec![
+            Note {
+                topic: "top transitive bloat",
+                hint: "run cargo tree -e features -i <crate>".into(),
+            },
+            advisory_note,
+        ],
+    })
+}
+
+/// Asks `registry` for the latest release of each direct dependency with a
+/// known version, and records those with a newer one (or no answer).
+async fn check_outdated(root: &Path, report: &mut DepsReport, registry: Registry) -> Result<()> {
+    let locked = locked_versions(root, registry)?;
+    let mut current = BTreeMap::new();
+    for dep in &report.dependencies {
+        let name = match registry {
+            Registry::PyPi => normalize_package_name(&dep.name),
+            Registry::Npm | Registry::CratesIo => dep.name.clone(),
+        };
+        let version = locked
+            .get(&name)
+            .cloned()
+            .or_else(|| requirement_version(&dep.requirement));
+        if let Some(version) = version {
+            current.entry(name).or_insert(version);
+        }
+    }
+
+    let names = current.keys().cloned().collect::<Vec<_>>();
+    let cache_root = root.to_path_buf();
+    let latest = tokio::task::spawn_blocking(move || {
+        registry::latest_versions(&cache_root, registry, &names)
+    })
+    .await?;
+    let checked = current.len();
+    for (name, current) in current {
+        match latest.get(&name).cloned().flatten() {
+            Some(latest) => {
+                if let Some(bump) = bump(&current, &latest) {
+                    report.outdated.push(Outdated {
+                        name,
+                        current,
+                        latest: Some(latest),
+                        bump: Some(bump),
+                    });
+                }
+            }
+            None => report.outdated.push(Outdated {
+                name,
+                current,
+                latest: None,
+                bump: None,
+            }),
+        }
+    }
+    let unknown = report
+        .outdated
+        .iter()
+        .filter(|u| u.latest.is_none())
+        .count();
+    let mut hint = format!(
+        "{} of {} checked on {}",
+        report.outdated.len() - unknown,
+        checked,
+        registry.name()
+    );
+    if unknown > 0 {
+        hint.push_str(&format!(
+            ", {unknown} unknown (registry unreachable or package not found)"
+        ));
+    }
+    report.notes.push(Note {
+        topic: "outdated packages",
+        hint,
+    });
+    Ok(())
+}
+
+/// Versions pinned by the lock file, by package name (normalized for PyPI).
+fn locked_versions(root: &Path, registry: Registry) -> Result<BTreeMap<String, String>> {
+    let mut versions = BTreeMap::new();
+    match registry {
+        Registry::Npm => {
+            let manager = detect_node_package_manager(root);
+            for (name, version) in node_lock_entries(root, manager)?.into_iter().flatten() {
+                versions.entry(name).or_insert(version);
+            }
+        }
+        Registry::CratesIo | Registry::PyPi => {
+            let lock_files: &[&str] = match registry {
+                Registry::CratesIo => &["Cargo.lock"],
+                _ => &["poetry.lock", "pdm.lock", "uv.lock"],
+            };
+            let Some(path) = lock_files
+                .iter()
+                .map(|file| root.join(file))
+                .find(|path| path.exists())
+            else {
+                return Ok(versions);
+            };
+            let lock = read_toml(&path)?;
+            for package in lock
+                .get("package")
+                .and_then(|p| p.as_array())
+                .into_iter()
+                .flatten()
+            {
+                let name = package.get("name").and_then(|n| n.as_str());
+                let version = package.get("version").and_then(|v| v.as_str());
+                if let (Some(name), Some(version)) = (name, version) {
+                    let name = match registry {
+                        Registry::PyPi => normalize_package_name(name),
+                        _ => name.to_string(),
+                    };
+                    versions.entry(name).or_insert_with(|| version.to_string());
+                }
+            }
+        }
+    }
+    Ok(versions)
+}
+
+/// The version a requirement like `^1.2`, `>=4.2,<5` or `==2.31.0` starts
+/// from; `None` for paths, URLs, tags and `*`.
+fn requirement_version(requirement: &str) -> Option<String> {
+    let rest = requirement.trim_start_matches(['^', '~', '=', '>', '<', '!', 'v', ' ']);
+    if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(rest.len());
+    Some(rest[..end].trim_end_matches('.').to_string())
+}
+
+/// Where `deps --online` keeps its copy of the RustSec advisory database.
+pub const ADVISORY_DB_DIR: &str = ".devflow/advisory-db";
+const ADVISORY_DB_URL: &str = "https://github.com/rustsec/advisory-db.git";
+
+/// Checks `Cargo.lock` against the RustSec advisory database: the one at
+/// `--advisory-db`, else the cached copy under `.devflow/`, which
+/// `--online` clones or updates first.
+async fn rust_advisories(root: &Path, args: &DepsArgs) -> Result<AdvisoryScan> {
+    let lock_path = root.join("Cargo.lock");
+    if !lock_path.exists() {
+        return Ok(AdvisoryScan::Skipped("no Cargo.lock to check".into()));
+    }
+    let db_path = match &args.advisory_db {
+        Some(path) => root.join(path),
+        None => {
+            let path = root.join(ADVISORY_DB_DIR);
+            if args.online {
+                fetch_advisory_db(&path).await?;
+            }
+            if !path.exists() {
+                return Ok(AdvisoryScan::Skipped(
+                    "no advisory database (run devflow deps --online once to fetch it, or pass --advisory-db)".into(),
+                ));
+            }
+            path
+        }
+    };
+    let db = rustsec::Database::open(&db_path)
+        .with_context(|| format!("loading advisory database {}", db_path.display()))?;
+    let lockfile = rustsec::Lockfile::load(&lock_path)
+        .with_context(|| format!("reading {}", lock_path.display()))?;
+    let mut found = db
+        .vulnerabilities(&lockfile)
+        .into_iter()
+        .map(|vuln| Advisory {
+            id: vuln.advisory.id.to_string(),
+            name: vuln.package.name.to_string(),
+            version: vuln.package.version.to_string(),
+            title: vuln.advisory.title,
+            patched: vuln
+                .versions
+                .patched()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            informational: vuln.advisory.informational.map(|kind| kind.to_string()),
+        })
+        .collect::<Vec<_>>();
+    found.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+    Ok(AdvisoryScan::Checked(found))
+}
+
+/// Clones the advisory database into `path`, or fast-forwards an existing
+/// clone, with the `git` binary.
+async fn fetch_advisory_db(path: &Path) -> Result<()> {
+    let mut cmd = tokio::process::Command::new("git");
+    if path.join(".git").exists() {
+        cmd.arg("-C")
+            .arg(path)
+            .args(["pull", "--ff-only", "--quiet"]);
+    } else {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        cmd.args(["clone", "--depth", "1", "--quiet", ADVISORY_DB_URL])
+            .arg(path);
+    }
+    let output = cmd
+        .output()
+        .await
+        .context("running git to fetch the advisory database")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("fetching the advisory database failed: {}", stderr.trim());
+    }
+    Ok(())
+}
+
+/// How long `deps --online` waits for the Go toolchain to resolve updates.
+const GO_LIST_TIMEOUT: Duration = Duration::from_secs(60);
+
+async fn go_report(root: &Path, online: bool) -> Result<DepsReport> {
+    let go_mod_path = root.join("go.mod");
+    let content = fs::read_to_string(&go_mod_path)
+        .with_context(|| format!("reading {}", go_mod_path.display()))?;
+    let go_mod = parse_go_mod(&content);
+
+    let go_sum_path = root.join("go.sum");
+    let locked_packages = if go_sum_path.exists() {
+        let go_sum = fs::read_to_string(&go_sum_path)
+            .with_context(|| format!("reading {}", go_sum_path.display()))?;
+        Some(count_go_sum(&go_sum))
+    } else {
+        None
+    };
+
+    let mut notes = Vec::new();
+    let mut outdated = Vec::new();
+    if online {
+        match go_updates(root).await {
+            Ok(updates) => outdated = updates,
+            Err(err) => notes.push(Note {
+                topic: "outdated modules",
+                hint: format!("unknown ({err:#})"),
+            }),
+        }
+        // Only modules go.mod asks for, not the whole build list.
+        outdated.retain(|u: &Outdated| go_mod.requires.iter().any(|d| d.name == u.name));
+    } else {
+        notes.push(Note {
+            topic: "outdated modules",
+            hint: "run devflow deps --online to check".into(),
+        });
+    }
+
+    Ok(DepsReport {
+        ecosystem: Some("go"),
+        files: vec![
+            FileCheck {
+                name: "go.mod",
+                present: true,
+            },
+            FileCheck {
+                name: "go.sum",
+                present: locked_packages.is_some(),
+            },
+        ],
+        declared_packages: Some(distinct_normal(&go_mod.requires)),
+        dependencies: go_mod.requires,
+        locked_packages,
+        package_manager: Some("go"),
+        overlaps: Vec::new(),
+        go_module: Some(go_mod.module),
+        outdated,
+        advisories: Vec::new(),
+        notes,
+    })
+}
+
+/// `go.mod`, split into its requirements and everything else.
+struct GoMod {
+    module: GoModule,
+    requires: Vec<Dependency>,
+}
+
+/// Parses `module`, `go`, `require`, `replace` and `exclude` directives,
+/// in single-line or `( ... )` block form. Requirements marked
+/// `// indirect` get `DepKind::Indirect`.
+fn parse_go_mod(content: &str) -> GoMod {
+    let mut module = GoModule::default();
+    let mut requires = Vec::new();
+    let mut block: Option<&str> = None;
+    for raw in content.lines() {
+        let indirect = raw.contains("// indirect");
+        let line = raw.split("//").next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (directive, args) = match block {
+            Some(_) if line == ")" => {
+                block = None;
+                continue;
+            }
+            Some(directive) => (directive, line),
+            None => {
+                let (directive, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                let args = args.trim();
+                if args == "(" {
+                    block = Some(directive);
+                    continue;
+                }
+                (directive, args)
+            }
+        };
+        let args_text = args.split_whitespace().collect::<Vec<_>>().join(" ");
+        match directive {
+            "module" => module.path = args.trim_matches('"').to_string(),
+            "go" => module.go_version = Some(args.to_string()),
+            "require" => {
+                let mut parts = args.split_whitespace();
+                if let (Some(name), Some(version)) = (parts.next(), parts.next()) {
+                    requires.push(Dependency {
+                        name: name.to_string(),
+                        requirement: version.to_string(),
+                        kind: if indirect {
+                            DepKind::Indirect
+                        } else {
+                            DepKind::Normal
+                        },
+                        declared_in: None,
+                    });
+                }
+            }
+            "replace" => module.replace.push(args_text),
+            "exclude" => module.exclude.push(args_text),
+            _ => {}
+        }
+    }
+    GoMod { module, requires }
+}
+
+/// Distinct module versions with content hashes in `go.sum`; `/go.mod`-only
+/// entries are modules whose manifest was read but whose code isn't used.
+fn count_go_sum(content: &str) -> usize {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let (module, version) = (parts.next()?, parts.next()?);
+            (!version.ends_with("/go.mod")).then_some((module, version))
+        })
+        .collect::<BTreeSet<_>>()
+        .len()
+}
+
+/// Runs `go list -m -u all`, which prints `path version [latest]` for
+/// modules with a newer release.
+async fn go_updates(root: &Path) -> Result<Vec<Outdated>> {
+    let output = tokio::time::timeout(
+        GO_LIST_TIMEOUT,
+        tokio::process::Command::new("go")
+            .args(["list", "-m", "-u", "all"])
+            .current_dir(root)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("go list timed out after {}s", GO_LIST_TIMEOUT.as_secs()))?
+    .context("running go list (is Go installed?)")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "go list failed: {}",
+            stderr.lines().next().unwrap_or_default()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let (name, current) = (parts.next()?, parts.next()?);
+            let latest = parts.next()?.strip_prefix('[')?.strip_suffix(']')?;
+            Some(Outdated {
+                name: name.to_string(),
+                current: current.to_string(),
+                bump: bump(current, latest),
+                latest: Some(latest.to_string()),
+            })
+        })
+        .collect())
+}
+
+/// How big the step from `current` to `latest` is by semver: `major`,
+/// `minor` or `patch`. `None` when either isn't a version or `latest` isn't
+/// newer. A leading `v` and pre-release/build suffixes are ignored.
+fn bump(current: &str, latest: &str) -> Option<&'static str> {
+    let parse = |version: &str| -> Option<[u64; 3]> {
+        let core = version.trim_start_matches('v').split(['-', '+']).next()?;
+        let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+        Some([
+            parts.next()??,
+            parts.next().unwrap_or(Some(0))?,
+            parts.next().unwrap_or(Some(0))?,
+        ])
+    };
+    let (current, latest) = (parse(current)?, parse(latest)?);
+    if latest <= current {
+        None
+    } else if latest[0] != current[0] {
+        Some("major")
+    } else if latest[1] != current[1] {
+        Some("minor")
+    } else {
+        Some("patch")
+    }
+}
+
+/// Sections of a `pom.xml` whose `<dependency>` entries aren't the
+/// project's own dependencies, and comments.
+static POM_IGNORED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<!--.*?-->|<dependencyManagement>.*?</dependencyManagement>|<build>.*?</build>|<profiles>.*?</profiles>")
+        .expect("valid regex")
+});
+static POM_DEPENDENCY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<dependency>(.*?)</dependency>").expect("valid regex"));
+/// `implementation 'g:a:v'` or `testImplementation("g:a:v")` in Groovy or
+/// Kotlin DSL.
+static GRADLE_DEPENDENCY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\s*([A-Za-z]+)\s*\(?\s*["']([^"':\s]+):([^"':\s]+)(?::([^"'\s]+))?["']"#)
+        .expect("valid regex")
+});
+static GEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^\s*gem\s+["']([^"']+)["']((?:\s*,\s*["'][^"']*["'])*)"#).expect("valid regex")
+});
+
+fn java_report(root: &Path) -> Result<DepsReport> {
+    let pom = root.join("pom.xml");
+    let gradle = ["build.gradle", "build.gradle.kts"]
+        .into_iter()
+        .find(|file| root.join(file).exists());
+    let (dependencies, package_manager, hint) = if pom.exists() {
+        let content =
+            fs::read_to_string(&pom).with_context(|| format!("reading {}", pom.display()))?;
+        (
+            pom_dependencies(&content),
+            "maven",
+            "run mvn versions:display-dependency-updates",
+        )
+    } else {
+        let path = root.join(gradle.unwrap_or("build.gradle"));
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+        (
+            gradle_dependencies(&content),
+            "gradle",
+            "run gradle dependencies to see resolved versions",
+        )
+    };
+    let lock_path = root.join("gradle.lockfile");
+    let locked_packages = match fs::read_to_string(&lock_path) {
+        Ok(lock) => Some(
+            lock.lines()
+                .filter(|line| !line.starts_with('#') && !line.starts_with("empty="))
+                .filter(|line| line.contains('='))
+                .count(),
+        ),
+        Err(_) => None,
+    };
+
+    Ok(DepsReport {
+        ecosystem: Some("java"),
+        files: vec![
+            FileCheck {
+                name: "pom.xml",
+                present: pom.exists(),
+            },
+            FileCheck {
+                name: gradle.unwrap_or("build.gradle"),
+                present: gradle.is_some(),
+            },
+            FileCheck {
+                name: "gradle.lockfile",
+                present: locked_packages.is_some(),
+            },
+        ],
+        declared_packages: Some(distinct_normal(&dependencies)),
+        dependencies,
+        locked_packages,
+        package_manager: Some(package_manager),
+        overlaps: Vec::new(),
+        go_module: None,
+        outdated: Vec::new(),
+        advisories: Vec::new(),
+        notes: vec![Note {
+            topic: "outdated packages",
+            hint: hint.into(),
+        }],
+    })
+}
+
+/// `<dependency>` entries of a `pom.xml`, named `groupId:artifactId`.
+/// Versions left to `<dependencyManagement>` or a parent POM are `*`.
+fn pom_dependencies(content: &str) -> Vec<Dependency> {
+    let content = POM_IGNORED.replace_all(content, "");
+    let tag = |block: &str, name: &str| {
+        let open = format!("<{name}>");
+        let start = block.find(&open)? + open.len();
+        let end = block[start..].find(&format!("</{name}>"))? + start;
+        Some(block[start..end].trim().to_string())
+    };
+    POM_DEPENDENCY
+        .captures_iter(&content)
+        .filter_map(|caps| {
+            let block = caps.get(1)?.as_str();
+            let kind = match tag(block, "scope").as_deref() {
+                Some("test") => DepKind::Dev,
+                _ if tag(block, "optional").as_deref() == Some("true") => DepKind::Optional,
+                _ => DepKind::Normal,
+            };
+            Some(Dependency {
+                name: format!("{}:{}", tag(block, "groupId")?, tag(block, "artifactId")?),
+                requirement: tag(block, "version").unwrap_or_else(|| "*".into()),
+                kind,
+                declared_in: None,
+            })
+        })
+        .collect()
+}
+
+/// String-notation dependencies of a Gradle build script. `test*`
+/// configurations are dev dependencies, annotation processors build ones.
+fn gradle_dependencies(content: &str) -> Vec<Dependency> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let caps = GRADLE_DEPENDENCY.captures(line)?;
+            let configuration = &caps[1];
+            let kind = if configuration.starts_with("test") {
+                DepKind::Dev
+            } else if matches!(configuration, "annotationProcessor" | "kapt") {
+                DepKind::Build
+            } else if matches!(
+                configuration,
+                "implementation" | "api" | "compileOnly" | "runtimeOnly" | "compile" | "runtime"
+            ) {
+                DepKind::Normal
+            } else {
+                return None;
+            };
+            Some(Dependency {
+                name: format!("{}:{}", &caps[2], &caps[3]),
+                requirement: caps.get(4).map_or("*", |v| v.as_str()).to_string(),
+                kind,
+                declared_in: None,
+            })
+        })
+        .collect()
+}
+
+fn ruby_report(root: &Path) -> Result<DepsReport> {
+    let gemfile = root.join("Gemfile");
+    let content =
+        fs::read_to_string(&gemfile).with_context(|| format!("reading {}", gemfile.display()))?;
+    let dependencies = gemfile_dependencies(&content);
+    let lock_path = root.join("Gemfile.lock");
+    // Locked gems are the `    name (version)` lines of the specs sections;
+    // their own dependencies are indented further.
+    let locked_packages = fs::read_to_string(&lock_path).ok().map(|lock| {
+        lock.lines()
+            .filter(|line| {
+                line.strip_prefix("    ")
+                    .is_some_and(|rest| !rest.starts_with(' ') && rest.contains(" ("))
+            })
+            .count()
+    });
+
+    Ok(DepsReport {
+        ecosystem: Some("ruby"),
+        files: vec![
+            FileCheck {
+                name: "Gemfile",
+                present: true,
+            },
+            FileCheck {
+                name: "Gemfile.lock",
+                present: locked_packages.is_some(),
+            },
+        ],
+        declared_packages: Some(distinct_normal(&dependencies)),
+        dependencies,
+        locked_packages,
+        package_manager: Some("bundler"),
+        overlaps: Vec::new(),
+        go_module: None,
+        outdated: Vec::new(),
+        advisories: Vec::new(),
+        notes: vec![Note {
+            topic: "outdated packages",
+            hint: "run bundle outdated".into(),
+        }],
+    })
+}
+
+/// `gem` lines of a Gemfile. Gems inside a `group` block that includes
+/// `:development` or `:test` are dev dependencies.
+fn gemfile_dependencies(content: &str) -> Vec<Dependency> {
+    let mut dependencies = Vec::new();
+    // One entry per open `do` block: whether it is a dev/test group.
+    let mut blocks: Vec<bool> = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line == "end" {
+            blocks.pop();
+            continue;
+        }
+        if line.ends_with(" do") || line.contains(" do |") {
+            let dev = line.starts_with("group")
+                && (line.contains(":development") || line.contains(":test"));
+            blocks.push(dev);
+            continue;
+        }
+        let Some(caps) = GEM.captures(line) else {
+            continue;
+        };
+        let requirements = caps[2]
+            .split(',')
+            .map(|r| r.trim().trim_matches(['"', '\'']))
+            .filter(|r| !r.is_empty())
+            .collect::<Vec<_>>();
+        let dev = blocks.iter().any(|&dev| dev)
+            || line.contains("group: :development")
+            || line.contains("group: :test");
+        dependencies.push(Dependency {
+            name: caps[1].to_string(),
+            requirement: if requirements.is_empty() {
+                "*".to_string()
+            } else {
+                requirements.join(", ")
+            },
+            kind: if dev { DepKind::Dev } else { DepKind::Normal },
+            declared_in: None,
+        });
+    }
+    dependencies
+}
+
+fn php_report(root: &Path) -> Result<DepsReport> {
+    let composer = root.join("composer.json");
+    let manifest: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(&composer)
+            .with_context(|| format!("reading {}", composer.display()))?,
+    )
+    .with_context(|| format!("parsing {}", composer.display()))?;
+    let mut dependencies = Vec::new();
+    for (table, kind) in [("require", DepKind::Normal), ("require-dev", DepKind::Dev)] {
+        for (name, requirement) in manifest[table].as_object().into_iter().flatten() {
+            // Platform requirements: the PHP version and extensions.
+            if name == "php" || name.starts_with("ext-") || name.starts_with("lib-") {
+                continue;
+            }
+            dependencies.push(Dependency {
+                name: name.clone(),
+                requirement: requirement.as_str().unwrap_or("*").to_string(),
+                kind,
+                declared_in: None,
+            });
+        }
+    }
+    let lock_path = root.join("composer.lock");
+    let locked_packages = match fs::read_to_string(&lock_path) {
+        Ok(lock) => {
+            let lock: serde_json::Value = serde_json::from_str(&lock)
+                .with_context(|| format!("parsing {}", lock_path.display()))?;
+            let count = |key: &str| lock[key].as_array().map_or(0, Vec::len);
+            Some(count("packages") + count("packages-dev"))
+        }
+        Err(_) => None,
+    };
+
+    Ok(DepsReport {
+        ecosystem: Some("php"),
+        files: vec![
+            FileCheck {
+                name: "composer.json",
+                present: true,
+            },
+            FileCheck {
+                name: "composer.lock",
+                present: locked_packages.is_some(),
+            },
+        ],
+        declared_packages: Some(distinct_normal(&dependencies)),
+        dependencies,
+        locked_packages,
+        package_manager: Some("composer"),
+        overlaps: Vec::new(),
+        go_module: None,
+        outdated: Vec::new(),
+        advisories: Vec::new(),
+        notes: vec![Note {
+            topic: "outdated packages",
+            hint: "run composer outdated".into(),
+        }],
+    })
+}
+
+fn read_toml(path: &Path) -> Result<toml::Value> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// The `[dependencies]`, `[dev-dependencies]` and `[build-dependencies]` of a
+/// crate manifest, including `[target.'cfg(..)'.*]` ones, in manifest order.
+fn manifest_dependencies(manifest: &toml::Value) -> Vec<(String, &toml::Value, DepKind)> {
+    let mut tables = vec![manifest];
+    if let Some(targets) = manifest.get("target").and_then(|t| t.as_table()) {
+        tables.extend(targets.values());
+    }
+    let mut deps = Vec::new();
+    for table in tables {
+        for (key, kind) in [
+            ("dependencies", DepKind::Normal),
+            ("dev-dependencies", DepKind::Dev),
+            ("build-dependencies", DepKind::Build),
+        ] {
+            if let Some(entries) = table.get(key).and_then(|d| d.as_table()) {
+                deps.extend(
+                    entries
+                        .iter()
+                        .map(|(name, spec)| (name.clone(), spec, kind)),
+                );
+            }
+        }
+    }
+    deps
+}
+
+/// The version requirement of a dependency entry, following
+/// `workspace = true` to `[workspace.dependencies]`.
+fn requirement(name: &str, spec: &toml::Value, workspace_deps: Option<&toml::Value>) -> String {
+    let Some(table) = spec.as_table() else {
+        return spec.as_str().unwrap_or_default().to_string();
+    };
+    if table.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+        return workspace_deps
+            .and_then(|deps| deps.get(name))
+            .map(|spec| requirement(name, spec, None))
+            .unwrap_or_else(|| "workspace".to_string());
+    }
+    if let Some(version) = table.get("version").and_then(|v| v.as_str()) {
+        return version.to_string();
+    }
+    for source in ["path", "git"] {
+        if let Some(location) = table.get(source).and_then(|v| v.as_str()) {
+            return format!("{source}:{location}");
+        }
+    }
+    "*".to_string()
+}
+
+/// Directories matched by workspace `members`/`exclude` patterns, which may
+/// use globs in any path component (`crates/*`).
+fn expand_members(root: &Path, patterns: &toml::Value) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for pattern in patterns.as_array().into_iter().flatten() {
+        let Some(pattern) = pattern.as_str() else {
+            continue;
+        };
+        let mut matches = vec![root.to_path_buf()];
+        for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+            if !component.contains(['*', '?', '[']) {
+                matches = matches.into_iter().map(|dir| dir.join(component)).collect();
+                continue;
+            }
+            let Ok(glob) = Glob::new(component) else {
+                matches.clear();
+                break;
+            };
+            let matcher = glob.compile_matcher();
+            matches = matches
+                .iter()
+                .filter_map(|dir| fs::read_dir(dir).ok())
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.path().is_dir() && matcher.is_match(entry.file_name()))
+                .map(|entry| entry.path())
+                .collect();
+            matches.sort();
+        }
+        dirs.extend(matches);
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requirement_lines() {
+        let parse = |line| parse_requirement(line).unwrap();
+        assert_eq!(parse("requests"), ("requests".into(), "*".into()));
+        assert_eq!(
+            parse("requests[socks] >= 2.31, <3 ; python_version < '3.12'"),
+            ("requests".into(), ">=2.31,<3".into())
+        );
+        assert_eq!(parse("Django==4.2.7"), ("Django".into(), "==4.2.7".into()));
+        assert_eq!(
+            parse("mylib @ git+https://example.com/mylib.git"),
+            (
+                "mylib".into(),
+                "url:git+https://example.com/mylib.git".into()
+            )
+        );
+        assert_eq!(strip_comment("flask>=3  # web"), "flask>=3");
+        assert_eq!(strip_comment("# only a comment"), "");
+    }
+
+    #[test]
+    fn parses_go_mod_directives() {
+        let go_mod = parse_go_mod(
+            "module example.com/app\n\ngo 1.22\n\nrequire (\n\tgithub.com/a/b v1.2.3\n\tgolang.org/x/c v0.1.0 // indirect\n)\n\nrequire github.com/d/e v2.0.0+incompatible\n\nreplace github.com/a/b => ../b\nexclude (\n\tgithub.com/f/g v1.0.0\n)\n",
+        );
+        assert_eq!(go_mod.module.path, "example.com/app");
+        assert_eq!(go_mod.module.go_version.as_deref(), Some("1.22"));
+        assert_eq!(go_mod.module.replace, ["github.com/a/b => ../b"]);
+        assert_eq!(go_mod.module.exclude, ["github.com/f/g v1.0.0"]);
+        let requires = go_mod
+            .requires
+            .iter()
+            .map(|d| (d.name.as_str(), d.requirement.as_str(), d.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            requires,
+            [
+                ("github.com/a/b", "v1.2.3", DepKind::Normal),
+                ("golang.org/x/c", "v0.1.0", DepKind::Indirect),
+                ("github.com/d/e", "v2.0.0+incompatible", DepKind::Normal),
+            ]
+        );
+        assert_eq!(
+            count_go_sum("a v1 h1:x=\na v1/go.mod h1:y=\nb v2/go.mod h1:z=\n"),
+            1
+        );
+    }
+
+    #[test]
+    fn parses_java_ruby_and_php_manifests() {
+        let pom = "<project><dependencies>\n<dependency><groupId>org.slf4j</groupId><artifactId>slf4j-api</artifactId><version>2.0.9</version></dependency>\n<!-- <dependency><groupId>x</groupId><artifactId>y</artifactId></dependency> -->\n<dependency><groupId>org.junit.jupiter</groupId><artifactId>junit-jupiter</artifactId><scope>test</scope></dependency>\n</dependencies><dependencyManagement><dependencies><dependency><groupId>a</groupId><artifactId>b</artifactId><version>1</version></dependency></dependencies></dependencyManagement></project>";
+        let deps = pom_dependencies(pom);
+        let summary = |deps: &[Dependency]| {
+            deps.iter()
+                .map(|d| format!("{} {} {}", d.name, d.requirement, d.kind.name()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summary(&deps),
+            [
+                "org.slf4j:slf4j-api 2.0.9 normal",
+                "org.junit.jupiter:junit-jupiter * dev"
+            ]
+        );
+
+        let gradle = "dependencies {\n    implementation 'com.google.guava:guava:33.0.0-jre'\n    testImplementation(\"junit:junit:4.13.2\")\n    annotationProcessor 'org.projectlombok:lombok:1.18.30'\n    implementation platform('org.springframework:spring-bom:6.1.0')\n}\n";
+        assert_eq!(
+            summary(&gradle_dependencies(gradle)),
+            [
+                "com.google.guava:guava 33.0.0-jre normal",
+                "junit:junit 4.13.2 dev",
+                "org.projectlombok:lombok 1.18.30 build"
+            ]
+        );
+
+        let gemfile = "source 'https://rubygems.org'\ngem 'rails', '~> 7.1', '>= 7.1.2'\ngem \"puma\"\ngroup :development, :test do\n  gem 'rspec-rails' # tests\nend\ngem 'rubocop', group: :development\n";
+        assert_eq!(
+            summary(&gemfile_dependencies(gemfile)),
+            [
+                "rails ~> 7.1, >= 7.1.2 normal",
+                "puma * normal",
+                "rspec-rails * dev",
+                "rubocop * dev"
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_yarn_and_pnpm_lock_entries() {
+        let classic = "# yarn lockfile v1\n\n\"@babel/core@^7.0.0\", \"@babel/core@^7.1.0\":\n  version \"7.23.0\"\n  resolved \"https://registry.yarnpkg.com/x\"\n\nreact@^18.2.0:\n  version \"18.2.0\"\n";
+        assert_eq!(
+            yarn_lock_entries(classic),
+            [
+                ("@babel/core".to_string(), "7.23.0".to_string()),
+                ("react".to_string(), "18.2.0".to_string())
+            ]
+        );
+        let berry = "__metadata:\n  version: 8\n\n\"react@npm:^18.2.0\":\n  version: 18.2.0\n";
+        assert_eq!(
+            yarn_lock_entries(berry),
+            [("react".to_string(), "18.2.0".to_string())]
+        );
+
+        let td = tempfile::tempdir().expect("tempdir");
+        std::fs::write(
+            td.path().join("pnpm-lock.yaml"),
+            "lockfileVersion: '6.0'\npackages:\n  /react@18.2.0:\n    resolution: {integrity: x}\n  /@testing-library/react@14.1.2(react@18.2.0):\n    resolution: {integrity: y}\n",
+        )
+        .expect("write lock");
+        let entries = node_lock_entries(td.path(), NodePackageManager::Pnpm)
+            .expect("parse")
+            .expect("lock file");
+        assert_eq!(
+            entries,
+            [
+                ("react".to_string(), "18.2.0".to_string()),
+                ("@testing-library/react".to_string(), "14.1.2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn takes_current_version_from_requirement() {
+        assert_eq!(requirement_version("^1.2").as_deref(), Some("1.2"));
+        assert_eq!(requirement_version(">=4.2,<5").as_deref(), Some("4.2"));
+        assert_eq!(requirement_version("==2.31.0").as_deref(), Some("2.31.0"));
+        assert_eq!(requirement_version("~1.x").as_deref(), Some("1"));
+        assert_eq!(requirement_version("path:../core"), None);
+        assert_eq!(requirement_version("*"), None);
+    }
+
+    #[test]
+    fn classifies_version_bumps() {
+        assert_eq!(bump("v1.2.3", "v2.0.0"), Some("major"));
+        assert_eq!(bump("1.2.3", "1.3.0"), Some("minor"));
+        assert_eq!(bump("v0.1.0", "v0.1.4"), Some("patch"));
+        assert_eq!(bump("1.2", "1.2.1"), Some("patch"));
+        assert_eq!(bump("v1.2.3", "v1.2.3"), None);
+        assert_eq!(bump("latest", "v1.0.0"), None);
+    }
+
+    #[test]
+    fn overlapping_declarations_are_duplicates_or_conflicts() {
+        let dep = |name: &str, requirement: &str, file: &str| Dependency {
+            name: name.into(),
+            requirement: requirement.into(),
+            kind: DepKind::Normal,
+            declared_in: Some(file.into()),
+        };
+        let overlaps = python_overlaps(&[
+            dep("requests", "==2.31.0", "requirements.txt"),
+            dep("Django", ">=4.2", "requirements.txt"),
+            dep("requests", "==2.31.0", "pyproject.toml"),
+            dep("django", "^5.0", "pyproject.toml"),
+            dep("flask", "*", "pyproject.toml"),
+        ]);
+        assert_eq!(overlaps.len(), 2);
+        assert_eq!(overlaps[0].name, "django");
+        assert!(overlaps[0].conflicting);
+        assert_eq!(
+            overlaps[0].declarations,
+            ["requirements.txt: >=4.2", "pyproject.toml: ^5.0"]
+        );
+        assert_eq!(overlaps[1].name, "requests");
+        assert!(!overlaps[1].conflicting);
+    }
 }
@@ -1,23 +1,103 @@
-use anyhow::Result;
-use std::env;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
-use crate::utils::{
-    config::load_config,
-    envcheck::{doctor_path_issues, parse_dotenv, validate_env_schema},
+use super::print_json;
+use crate::{
+    cli::ShellFormat,
+    utils::{
+        config::{config_exists, load_config},
+        envcheck::{
+            diff_against_template, doctor_path_issues, format_dotenv_line, load_layered_env,
+            parse_dotenv_file, parse_schema, EnvIssue, LayeredEnv, PathIssue, Severity,
+            TemplateDiff,
+        },
+        envcrypt::{decrypt_env, encrypt_env, EnvKey},
+        language::detect_project_language,
+    },
 };
 
-pub async fn doctor() -> Result<()> {
-    let root = env::current_dir()?;
-    let mut issues = doctor_path_issues();
+#[derive(Debug, Serialize)]
+struct DoctorReport {
+    /// No `Error` path issues and no env issues; warnings don't count.
+    healthy: bool,
+    path_issues: Vec<PathIssue>,
+    env_issues: Vec<EnvIssue>,
+}
 
-    if root.join(".devflow.yaml").exists() {
-        let cfg = load_config(&root)?;
-        let vars = parse_dotenv(&root)?;
-        for i in validate_env_schema(&cfg.env, &vars) {
-            issues.push(format!("env {}: {}", i.key, i.reason));
-        }
+#[derive(Debug, Serialize)]
+struct FixReport {
+    dry_run: bool,
+    created: bool,
+    added: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EncryptReport {
+    output: &'static str,
+    /// Keys whose value was encrypted afresh.
+    encrypted: Vec<String>,
+    /// Keys whose value was unchanged, so their ciphertext was kept.
+    unchanged: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DecryptReport {
+    output: &'static str,
+    keys: Vec<String>,
+}
+
+/// Plain env file `env encrypt` reads and `env decrypt` writes.
+const PLAIN_ENV: &str = ".env";
+/// Encrypted env file, meant to be committed.
+const ENCRYPTED_ENV: &str = ".env.enc";
+
+#[derive(Debug, Serialize)]
+struct DiffReport {
+    template: String,
+    files: Vec<String>,
+    #[serde(flatten)]
+    diff: TemplateDiff,
+    sources: HashMap<String, String>,
+}
+
+pub async fn doctor(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    json: bool,
+) -> Result<()> {
+    let cfg = if config_exists(root, config) {
+        Some(load_config(root, config, profile)?)
+    } else {
+        None
+    };
+    let path_issues = doctor_path_issues(detect_project_language(root), cfg.as_ref());
+    let env = load_layered_env(root, profile)?;
+    let mut env_issues = env.issues.clone();
+    if let Some(cfg) = &cfg {
+        env_issues.extend(env.validate(&parse_schema(&cfg.env)));
+    }
+
+    if json {
+        return print_json(&DoctorReport {
+            healthy: path_issues.iter().all(|i| i.severity != Severity::Error)
+                && env_issues.is_empty(),
+            path_issues,
+            env_issues,
+        });
     }
 
+    let mut issues = path_issues
+        .iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>();
+    issues.extend(env_issues.iter().map(|i| format!("env {}", i)));
     if issues.is_empty() {
         println!("env doctor: healthy");
     } else {
@@ -29,45 +109,311 @@ pub async fn doctor() -> Result<()> {
     Ok(())
 }
 
-pub async fn fix() -> Result<()> {
-    let root = env::current_dir()?;
-    if !root.join(".env").exists() {
-        std::fs::write(root.join(".env"), "# generated by devflow env fix\n")?;
+/// Appends every schema key missing from the layered env files to `.env`
+/// with a placeholder for its type. Existing lines are never rewritten.
+pub async fn fix(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    dry_run: bool,
+    json: bool,
+) -> Result<()> {
+    let path = root.join(".env");
+    let schema = if config_exists(root, config) {
+        parse_schema(&load_config(root, config, profile)?.env)
+    } else {
+        HashMap::new()
+    };
+    let current = load_layered_env(root, profile)?.vars;
+
+    let mut missing = schema
+        .iter()
+        .filter(|(key, spec)| {
+            !current.contains_key(*key) && (!spec.optional || spec.default.is_some())
+        })
+        .map(|(key, spec)| format_dotenv_line(key, &spec.fill_value()))
+        .collect::<Vec<_>>();
+    missing.sort();
+
+    if json {
+        let created = !path.exists() && !dry_run;
+        if !dry_run {
+            append_keys(&path, &missing, "# generated by devflow env fix")?;
+        }
+        return print_json(&FixReport {
+            dry_run,
+            created,
+            added: missing,
+        });
+    }
+
+    if dry_run {
+        if !path.exists() {
+            println!("would create .env");
+        }
+        if missing.is_empty() {
+            println!("no missing keys");
+        } else {
+            println!("would add {} key(s) to .env:", missing.len());
+            for line in &missing {
+                println!(" + {}", line);
+            }
+        }
+        return Ok(());
+    }
+
+    if path.exists() && missing.is_empty() {
+        println!(".env already has every schema key; no changes");
+        return Ok(());
+    }
+
+    if append_keys(&path, &missing, "# generated by devflow env fix")? {
         println!("created .env");
+    }
+    if !missing.is_empty() {
+        println!("added {} key(s) to .env:", missing.len());
+        for line in &missing {
+            println!(" + {}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Appends `lines` to the env file at `path`, creating it with `header` if
+/// needed. Returns whether the file was created.
+pub(super) fn append_keys(path: &Path, lines: &[String], header: &str) -> Result<bool> {
+    let existing = fs::read_to_string(path).ok();
+    if existing.is_some() && lines.is_empty() {
+        return Ok(false);
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    match &existing {
+        None => writeln!(file, "{}", header)?,
+        Some(content) if !content.is_empty() && !content.ends_with('\n') => writeln!(file)?,
+        Some(_) => {}
+    }
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(existing.is_none())
+}
+
+/// Compares the layered env files with a template (`.env.example` by default)
+/// and reports missing keys, extra keys, and values still equal to the
+/// placeholder.
+pub async fn diff(
+    root: &Path,
+    profile: Option<&str>,
+    against: Option<PathBuf>,
+    json: bool,
+) -> Result<()> {
+    let template_path = root.join(against.unwrap_or_else(|| PathBuf::from(".env.example")));
+    if !template_path.exists() {
+        bail!(
+            "template {} not found (use --against PATH)",
+            template_path.display()
+        );
+    }
+
+    let template = parse_dotenv_file(&template_path)?;
+    let current = load_layered_env(root, profile)?;
+    let diff = diff_against_template(&current.vars, &template);
+
+    let template_name = template_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| template_path.display().to_string());
+    if json {
+        return print_json(&DiffReport {
+            template: template_name,
+            files: current.files,
+            diff,
+            sources: current.sources,
+        });
+    }
+
+    println!("comparing {} with {}", current.describe(), template_name);
+    print_group("missing from env files", &diff.missing, None);
+    print_group("extra in env files", &diff.extra, Some(&current));
+    print_group(
+        "still equal to the template placeholder",
+        &diff.unchanged,
+        Some(&current),
+    );
+    if diff.missing.is_empty() && diff.extra.is_empty() && diff.unchanged.is_empty() {
+        println!("{} matches {}", current.describe(), template_name);
+    }
+    Ok(())
+}
+
+/// Writes `.env.enc` from `.env`, encrypting each value but leaving keys and
+/// comments readable. Values unchanged since the last `.env.enc` keep their
+/// ciphertext, so the file only changes where `.env` did.
+pub async fn encrypt(root: &Path, key_file: Option<&Path>, json: bool) -> Result<()> {
+    let key = EnvKey::load(key_file)?;
+    let plain_path = root.join(PLAIN_ENV);
+    let plain = fs::read_to_string(&plain_path)
+        .with_context(|| format!("could not read {}", plain_path.display()))?;
+    let enc_path = root.join(ENCRYPTED_ENV);
+    let previous = fs::read_to_string(&enc_path).ok();
+
+    let encrypted = encrypt_env(&plain, &key, previous.as_deref());
+    if previous.as_deref() != Some(encrypted.content.as_str()) {
+        fs::write(&enc_path, &encrypted.content)
+            .with_context(|| format!("could not write {}", enc_path.display()))?;
+    }
+
+    if json {
+        return print_json(&EncryptReport {
+            output: ENCRYPTED_ENV,
+            encrypted: encrypted.changed,
+            unchanged: encrypted.unchanged,
+        });
+    }
+    if encrypted.changed.is_empty() {
+        println!("{ENCRYPTED_ENV} is up to date");
     } else {
-        println!(".env already exists; no changes");
+        println!(
+            "encrypted {} value(s) into {ENCRYPTED_ENV}: {}",
+            encrypted.changed.len(),
+            encrypted.changed.join(", ")
+        );
     }
     Ok(())
 }
 
-pub async fn diff() -> Result<()> {
-    let root = env::current_dir()?;
-    let current = parse_dotenv(&root)?;
-    let snapshot_path = root.join(".devflow/env_snapshot.json");
+/// Writes `.env` from `.env.enc`. Nothing is written unless every value
+/// decrypts, and an existing `.env` with other contents is only replaced
+/// with `force`.
+pub async fn decrypt(root: &Path, key_file: Option<&Path>, force: bool, json: bool) -> Result<()> {
+    let key = EnvKey::load(key_file)?;
+    let enc_path = root.join(ENCRYPTED_ENV);
+    let encrypted = fs::read_to_string(&enc_path)
+        .with_context(|| format!("could not read {}", enc_path.display()))?;
+    let (plain, keys) = decrypt_env(&encrypted, &key)?;
 
-    if !snapshot_path.exists() {
-        let content = serde_json::to_string_pretty(&current)?;
-        std::fs::create_dir_all(root.join(".devflow"))?;
-        std::fs::write(snapshot_path, content)?;
-        println!("saved first env snapshot");
-        return Ok(());
+    let plain_path = root.join(PLAIN_ENV);
+    match fs::read_to_string(&plain_path) {
+        Ok(existing) if existing == plain => {}
+        Ok(_) if !force => bail!(
+            "{PLAIN_ENV} already exists and differs from {ENCRYPTED_ENV}; \
+             run `devflow env encrypt` to keep its changes, or pass --force to overwrite it"
+        ),
+        _ => fs::write(&plain_path, &plain)
+            .with_context(|| format!("could not write {}", plain_path.display()))?,
+    }
+
+    if json {
+        return print_json(&DecryptReport {
+            output: PLAIN_ENV,
+            keys,
+        });
     }
+    println!(
+        "decrypted {} value(s) into {PLAIN_ENV}: {}",
+        keys.len(),
+        keys.join(", ")
+    );
+    Ok(())
+}
 
-    let previous = std::fs::read_to_string(&snapshot_path)?;
-    let old: std::collections::HashMap<String, String> = serde_json::from_str(&previous)?;
+/// Prints the layered env files as shell assignments for
+/// `eval "$(devflow env export)"`. Values are printed as-is, secrets
+/// included; keys the shell can't name are skipped with a warning.
+pub async fn export(
+    root: &Path,
+    profile: Option<&str>,
+    format: ShellFormat,
+    json: bool,
+) -> Result<()> {
+    let env = load_layered_env(root, profile)?;
+    let vars = env.vars.into_iter().collect::<BTreeMap<_, _>>();
+    if json {
+        return print_json(&vars);
+    }
 
-    for (k, v) in &current {
-        match old.get(k) {
-            None => println!("added: {}", k),
-            Some(oldv) if oldv != v => println!("changed: {}", k),
-            _ => {}
+    for (key, value) in &vars {
+        if !is_shell_name(key) {
+            eprintln!("warning: skipping {key}: not a valid shell variable name");
+            continue;
         }
+        if value.chars().any(char::is_control) {
+            eprintln!(
+                "warning: {key} contains control characters such as newlines; \
+                 check the value after loading it"
+            );
+        }
+        println!("{}", export_line(format, key, value));
+    }
+    Ok(())
+}
+
+/// `[A-Za-z_][A-Za-z0-9_]*`, the names every supported shell accepts.
+pub(super) fn is_shell_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// One assignment in `format`'s syntax, with `value` single-quoted so the
+/// shell expands nothing in it.
+pub(super) fn export_line(format: ShellFormat, key: &str, value: &str) -> String {
+    match format {
+        ShellFormat::Posix => format!("export {key}='{}'", value.replace('\'', r"'\''")),
+        ShellFormat::Fish => format!(
+            "set -gx {key} '{}'",
+            value.replace('\\', r"\\").replace('\'', r"\'")
+        ),
+        ShellFormat::Powershell => format!("$env:{key} = '{}'", value.replace('\'', "''")),
     }
-    for k in old.keys() {
-        if !current.contains_key(k) {
-            println!("removed: {}", k);
+}
+
+fn print_group(title: &str, keys: &[String], env: Option<&LayeredEnv>) {
+    if keys.is_empty() {
+        return;
+    }
+    println!("{} ({}):", title, keys.len());
+    for key in keys {
+        match env.and_then(|e| e.source(key)) {
+            Some(file) => println!(" - {} ({})", key, file),
+            None => println!(" - {}", key),
         }
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_lines_quote_values_for_each_shell() {
+        let value = r"it's $HOME \n";
+        assert_eq!(
+            export_line(ShellFormat::Posix, "A", value),
+            r"export A='it'\''s $HOME \n'"
+        );
+        assert_eq!(
+            export_line(ShellFormat::Fish, "A", value),
+            r"set -gx A 'it\'s $HOME \\n'"
+        );
+        assert_eq!(
+            export_line(ShellFormat::Powershell, "A", value),
+            r"$env:A = 'it''s $HOME \n'"
+        );
+    }
+
+    #[test]
+    fn shell_names_exclude_dashes_and_leading_digits() {
+        assert!(is_shell_name("DATABASE_URL"));
+        assert!(is_shell_name("_private"));
+        assert!(!is_shell_name("MY-KEY"));
+        assert!(!is_shell_name("1PASSWORD"));
+        assert!(!is_shell_name(""));
+    }
 }
@@ -1,15 +1,509 @@
 use anyhow::Result;
-use std::env;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::Path,
+};
 
-use crate::utils::config::write_default_config;
+use super::{env::is_shell_name, print_json};
+use crate::{
+    cli::InitArgs,
+    utils::{
+        compose::{find_compose_file, load_compose},
+        config::{
+            find_config, template_config, write_config, DevflowConfig, ReadyCheck, ServiceDef,
+        },
+        envcheck::parse_dotenv_file,
+        language::{detect_node_package_manager, detect_project_language, Language},
+        ports::Proto,
+    },
+};
 
-pub async fn run() -> Result<()> {
-    let root = env::current_dir()?;
-    if root.join(".devflow.yaml").exists() {
-        println!(".devflow.yaml already exists");
+/// Writes a config for the project's language (or `--template`), refined by
+/// what [`discover`] finds in the project. An existing config is kept unless
+/// `--force` is given, which replaces it even when it is in another format.
+/// With `--interactive` on a terminal, that config is only the starting
+/// point for the wizard's answers.
+pub async fn run(root: &Path, args: InitArgs, json: bool) -> Result<()> {
+    let existing = find_config(root, None)?.map(|(path, format)| (path, format.file_name()));
+    if let (Some((_, file)), false) = (&existing, args.force) {
+        if json {
+            return print_json(&serde_json::json!({ "file": file, "created": false }));
+        }
+        println!("{} already exists; pass --force to overwrite it", file);
         return Ok(());
     }
-    write_default_config(&root)?;
-    println!("Created .devflow.yaml");
+
+    let language = args
+        .template
+        .unwrap_or_else(|| detect_project_language(root));
+    let mut cfg = template_config(root, language);
+    let detected = discover(root, language, &mut cfg)?;
+    if args.interactive {
+        if io::stdin().is_terminal() {
+            let mut input = io::stdin().lock();
+            // Prompts stay off stdout when it carries the JSON result.
+            cfg = if json {
+                wizard(cfg, &mut input, &mut io::stderr())?
+            } else {
+                wizard(cfg, &mut input, &mut io::stdout())?
+            };
+        } else {
+            eprintln!(
+                "warning: stdin is not a terminal; skipping the wizard and writing the {} template",
+                language
+            );
+        }
+    }
+    write_config(root, args.format, &cfg)?;
+    let file = args.format.file_name();
+    // Written first so a failed write leaves the old config in place.
+    if let Some((path, replaced)) = &existing {
+        if *replaced != file {
+            fs::remove_file(path)?;
+        }
+    }
+
+    if json {
+        return print_json(&serde_json::json!({
+            "file": file,
+            "created": true,
+            "template": language,
+            "replaced": existing.map(|(_, replaced)| replaced),
+            "detected": detected,
+        }));
+    }
+    match existing {
+        Some((_, replaced)) if replaced == file => {
+            println!("Overwrote {} ({} template)", file, language)
+        }
+        Some((_, replaced)) => println!(
+            "Replaced {} with {} ({} template)",
+            replaced, file, language
+        ),
+        None if language == Language::Unknown && cfg.services.is_empty() => {
+            println!("Created {}", file);
+            eprintln!(
+                "warning: could not detect the project language, so no service or test \
+                 command was added; pass --template LANG for one"
+            );
+        }
+        None => println!("Created {} ({} template)", file, language),
+    }
+    if !detected.is_empty() {
+        println!(
+            "  with services, ports and env keys from {}",
+            detected.join(", ")
+        );
+    }
     Ok(())
 }
+
+/// Refines the template with what the project declares: the `dev` or
+/// `start` script of `package.json`, the services and published ports of
+/// the compose file, and the keys of `.env.example` and `.env`. Returns the
+/// files that contributed.
+fn discover(root: &Path, language: Language, cfg: &mut DevflowConfig) -> Result<Vec<String>> {
+    let mut detected = Vec::new();
+
+    if language == Language::Node {
+        if let Some(script) = node_dev_script(root) {
+            let manager = detect_node_package_manager(root);
+            for service in cfg.services.iter_mut().filter(|s| s.name == "app") {
+                service.command = format!("{manager} run {script}");
+            }
+            detected.push("package.json".to_string());
+        }
+    }
+
+    if let Some(path) = find_compose_file(root) {
+        let file = path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+        match load_compose(&path) {
+            Ok(services) if !services.is_empty() => {
+                let names = services.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+                // A compose service of the same name is the real thing.
+                cfg.services.retain(|s| !names.contains(&s.name));
+                for service in &mut cfg.services {
+                    service.depends_on.extend(names.iter().cloned());
+                }
+                cfg.start_commands.retain(|c| c != "docker compose up -d");
+                let mut compose_services = Vec::new();
+                for service in services {
+                    let tcp = service
+                        .ports
+                        .iter()
+                        .filter(|p| p.protocol == Proto::Tcp)
+                        .map(|p| p.host)
+                        .collect::<Vec<_>>();
+                    cfg.desired_ports.extend(&tcp);
+                    compose_services.push(ServiceDef {
+                        command: format!("docker compose up {}", service.name),
+                        name: service.name,
+                        depends_on: Vec::new(),
+                        ready_when: tcp.first().map(|&port| ReadyCheck::Tcp(port)),
+                        ready_timeout_secs: None,
+                    });
+                }
+                compose_services.append(&mut cfg.services);
+                cfg.services = compose_services;
+                cfg.desired_ports.sort_unstable();
+                cfg.desired_ports.dedup();
+                detected.push(file);
+            }
+            Ok(_) => {}
+            Err(err) => eprintln!("warning: ignoring {file}: {err:#}"),
+        }
+    }
+
+    let mut env = BTreeMap::new();
+    // `.env` last: its real values say more about the type than placeholders.
+    for file in [".env.example", ".env"] {
+        let vars = parse_dotenv_file(&root.join(file))?;
+        if !vars.is_empty() {
+            detected.push(file.to_string());
+        }
+        for (key, value) in vars {
+            env.insert(key, infer_env_type(&value).to_string());
+        }
+    }
+    if !env.is_empty() {
+        cfg.env = env.into_iter().collect();
+    }
+    Ok(detected)
+}
+
+/// The `package.json` script that starts a dev server: `dev`, else `start`.
+fn node_dev_script(root: &Path) -> Option<&'static str> {
+    let content = fs::read_to_string(root.join("package.json")).ok()?;
+    let pkg = serde_json::from_str::<serde_json::Value>(&content).ok()?;
+    let scripts = pkg["scripts"].as_object()?;
+    ["dev", "start"]
+        .into_iter()
+        .find(|name| scripts.contains_key(*name))
+}
+
+/// Schema type for a sample value: `int` for whole numbers, `bool` for
+/// `true`/`false` (lowercase, as `env doctor` accepts), otherwise `string`.
+fn infer_env_type(value: &str) -> &'static str {
+    if value.parse::<i64>().is_ok() {
+        "int"
+    } else if value.parse::<bool>().is_ok() {
+        "bool"
+    } else {
+        "string"
+    }
+}
+
+/// Asks for the services, start commands, test command, ports and env keys,
+/// offering `defaults` for each. Enter keeps the value in brackets, `-` drops
+/// it, and the end of input accepts every remaining default.
+fn wizard(
+    defaults: DevflowConfig,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> Result<DevflowConfig> {
+    let mut prompt = Prompt { input, out };
+    writeln!(
+        prompt.out,
+        "Press Enter to keep the value in brackets, or type - to drop it."
+    )?;
+
+    let services = defaults
+        .services
+        .iter()
+        .map(|s| format!("{}: {}", s.name, s.command))
+        .collect::<Vec<_>>();
+    let services = prompt.ask_many("service (NAME: COMMAND)", &services, parse_service)?;
+    let start_commands = prompt.ask_many("start command", &defaults.start_commands, |s| {
+        Ok(s.to_string())
+    })?;
+    let test_command = prompt.ask(
+        "test command",
+        defaults.test_command.as_deref().unwrap_or("-"),
+        |s| Ok((s != "-").then(|| s.to_string())),
+    )?;
+    let ports = defaults
+        .desired_ports
+        .iter()
+        .map(u16::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let desired_ports = prompt.ask("ports to watch (comma-separated)", &ports, parse_ports)?;
+    let env = defaults
+        .env
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(key, typ)| format!("{key}: {typ}"))
+        .collect::<Vec<_>>();
+    let env = prompt.ask_many("required env var (KEY: TYPE)", &env, parse_env_key)?;
+
+    Ok(DevflowConfig {
+        services,
+        start_commands,
+        test_command,
+        desired_ports,
+        env: env.into_iter().collect(),
+        ..defaults
+    })
+}
+
+struct Prompt<'a, R, W> {
+    input: &'a mut R,
+    out: &'a mut W,
+}
+
+impl<R: BufRead, W: Write> Prompt<'_, R, W> {
+    /// The trimmed answer, or `None` at the end of input.
+    fn line(&mut self, question: &str) -> Result<Option<String>> {
+        write!(self.out, "{question}: ")?;
+        self.out.flush()?;
+        let mut answer = String::new();
+        if self.input.read_line(&mut answer)? == 0 {
+            writeln!(self.out)?;
+            return Ok(None);
+        }
+        Ok(Some(answer.trim().to_string()))
+    }
+
+    /// Asks until `parse` accepts the answer; Enter or the end of input
+    /// stands for `default`.
+    fn ask<T>(
+        &mut self,
+        question: &str,
+        default: &str,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<T> {
+        loop {
+            let answer = self
+                .line(&format!("{question} [{default}]"))?
+                .filter(|a| !a.is_empty());
+            let eof = answer.is_none();
+            match parse(answer.as_deref().unwrap_or(default)) {
+                Ok(value) => return Ok(value),
+                Err(err) if eof => anyhow::bail!("{question}: {err}"),
+                Err(err) => writeln!(self.out, "  {err}")?,
+            }
+        }
+    }
+
+    /// Offers each default in turn (`-` drops it), then asks for more until
+    /// an empty answer.
+    fn ask_many<T>(
+        &mut self,
+        label: &str,
+        defaults: &[String],
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<Vec<T>> {
+        let mut values = Vec::new();
+        for default in defaults {
+            let keep = |s: &str| match s {
+                "-" => Ok(None),
+                s => parse(s).map(Some),
+            };
+            values.extend(self.ask(label, default, keep)?);
+        }
+        loop {
+            let Some(answer) = self
+                .line(&format!("another {label} (Enter to finish)"))?
+                .filter(|a| !a.is_empty())
+            else {
+                return Ok(values);
+            };
+            match parse(&answer) {
+                Ok(value) => values.push(value),
+                Err(err) => writeln!(self.out, "  {err}")?,
+            }
+        }
+    }
+}
+
+/// `web: npm run dev`.
+fn parse_service(answer: &str) -> Result<ServiceDef, String> {
+    let (name, command) = answer
+        .split_once(':')
+        .map(|(name, command)| (name.trim(), command.trim()))
+        .filter(|(name, command)| {
+            !name.is_empty() && !name.contains(char::is_whitespace) && !command.is_empty()
+        })
+        .ok_or("expected NAME: COMMAND, e.g. `web: npm run dev`")?;
+    Ok(ServiceDef {
+        name: name.to_string(),
+        command: command.to_string(),
+        depends_on: Vec::new(),
+        ready_when: None,
+        ready_timeout_secs: None,
+    })
+}
+
+/// `3000, 5432`.
+fn parse_ports(answer: &str) -> Result<Vec<u16>, String> {
+    answer
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty() && *p != "-")
+        .map(|p| p.parse().map_err(|_| format!("`{p}` is not a port number")))
+        .collect()
+}
+
+/// `DATABASE_URL: url`, or a bare key for a string.
+fn parse_env_key(answer: &str) -> Result<(String, String), String> {
+    let (key, typ) = answer.split_once(':').unwrap_or((answer, "string"));
+    let (key, typ) = (key.trim(), typ.trim());
+    if !is_shell_name(key) {
+        return Err(format!("`{key}` is not a valid env var name"));
+    }
+    Ok((key.to_string(), typ.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::envcheck::{parse_schema, validate_env_schema};
+
+    fn run_wizard(answers: &str) -> (DevflowConfig, String) {
+        let defaults = template_config(Path::new("."), Language::Rust);
+        let mut out = Vec::new();
+        let cfg = wizard(defaults, &mut answers.as_bytes(), &mut out).expect("wizard");
+        (cfg, String::from_utf8(out).expect("utf8"))
+    }
+
+    #[test]
+    fn discovers_scripts_compose_services_and_env_types() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        fs::write(
+            root.join("package.json"),
+            r#"{"scripts": {"start": "node server.js"}}"#,
+        )
+        .expect("write");
+        fs::write(
+            root.join("docker-compose.yml"),
+            "services:\n  db:\n    image: postgres\n    ports: [\"5433:5432\"]\n  mail:\n    image: mailhog\n",
+        )
+        .expect("write");
+        fs::write(
+            root.join(".env.example"),
+            "PORT=\nDEBUG=false\nAPI_URL=http://x\n",
+        )
+        .expect("write");
+        fs::write(root.join(".env"), "PORT=3000\n").expect("write");
+
+        let mut cfg = template_config(root, Language::Node);
+        let detected = discover(root, Language::Node, &mut cfg).expect("discover");
+        assert_eq!(
+            detected,
+            ["package.json", "docker-compose.yml", ".env.example", ".env"]
+        );
+        let services = cfg
+            .services
+            .iter()
+            .map(|s| (s.name.as_str(), s.command.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            services,
+            [
+                ("db", "docker compose up db"),
+                ("mail", "docker compose up mail"),
+                ("app", "npm run start"),
+            ]
+        );
+        assert_eq!(cfg.services[0].ready_when, Some(ReadyCheck::Tcp(5433)));
+        assert_eq!(cfg.services[1].ready_when, None);
+        assert_eq!(cfg.services[2].depends_on, ["db", "mail"]);
+        assert!(cfg.start_commands.is_empty());
+        assert_eq!(cfg.desired_ports, vec![3000, 5432, 5433]);
+        assert_eq!(cfg.env.len(), 3);
+        assert_eq!(cfg.env["PORT"], "int");
+        assert_eq!(cfg.env["DEBUG"], "bool");
+        assert_eq!(cfg.env["API_URL"], "string");
+    }
+
+    #[test]
+    fn inferred_env_schema_accepts_the_file_it_came_from() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        fs::write(root.join(".env"), "DEBUG=True\nVERBOSE=false\nWORKERS=4\n").expect("write");
+
+        let mut cfg = template_config(root, Language::Unknown);
+        discover(root, Language::Unknown, &mut cfg).expect("discover");
+        assert_eq!(cfg.env["DEBUG"], "string");
+        assert_eq!(cfg.env["VERBOSE"], "bool");
+
+        let actual = parse_dotenv_file(&root.join(".env")).expect("parse");
+        let schema = parse_schema(&cfg.env);
+        assert!(validate_env_schema(&schema, &actual).is_empty());
+    }
+
+    #[test]
+    fn keeps_the_template_when_nothing_is_detected() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut cfg = template_config(dir.path(), Language::Rust);
+        let detected = discover(dir.path(), Language::Rust, &mut cfg).expect("discover");
+        assert!(detected.is_empty());
+        assert_eq!(
+            cfg.services,
+            template_config(dir.path(), Language::Rust).services
+        );
+        assert_eq!(cfg.start_commands, ["docker compose up -d"]);
+    }
+
+    #[test]
+    fn enter_keeps_every_default() {
+        let (cfg, out) = run_wizard("");
+        let defaults = template_config(Path::new("."), Language::Rust);
+        assert_eq!(cfg.services, defaults.services);
+        assert_eq!(cfg.start_commands, defaults.start_commands);
+        assert_eq!(cfg.test_command, defaults.test_command);
+        assert_eq!(cfg.desired_ports, defaults.desired_ports);
+        assert_eq!(cfg.env, defaults.env);
+        assert!(
+            out.contains("service (NAME: COMMAND) [app: cargo run]: "),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn answers_replace_drop_and_add_entries() {
+        let answers = [
+            "api: cargo run --bin api", // replaces app
+            "oops",                     // another service: invalid, asked again
+            "worker: cargo run --bin worker",
+            "",        // done with services
+            "-",       // drop docker compose up -d
+            "",        // no more start commands
+            "-",       // no test command
+            "8080, x", // invalid ports, asked again
+            "8080",
+            "",  // keep DATABASE_URL
+            "-", // drop PORT
+            "API_KEY",
+            "",
+        ]
+        .join("\n");
+        let (cfg, out) = run_wizard(&answers);
+        let services = cfg
+            .services
+            .iter()
+            .map(|s| (s.name.as_str(), s.command.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            services,
+            [
+                ("api", "cargo run --bin api"),
+                ("worker", "cargo run --bin worker")
+            ]
+        );
+        assert!(cfg.start_commands.is_empty());
+        assert_eq!(cfg.test_command, None);
+        assert_eq!(cfg.desired_ports, vec![8080]);
+        assert_eq!(cfg.env.len(), 2);
+        assert_eq!(cfg.env["DATABASE_URL"], "string");
+        assert_eq!(cfg.env["API_KEY"], "string");
+        assert!(out.contains("expected NAME: COMMAND"), "{out}");
+        assert!(out.contains("`x` is not a port number"), "{out}");
+    }
+}
@@ -1,54 +1,991 @@
 use anyhow::Result;
-use chrono::Utc;
-use std::{collections::HashMap, env, fs};
-
-pub async fn run() -> Result<()> {
-    let root = env::current_dir()?;
-    let log_file = root.join("devflow.log");
-    if !log_file.exists() {
-        println!("No devflow.log found");
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{self, File, Metadata},
+    future::Future,
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+
+use super::print_json;
+use crate::{
+    cli::{LogFormat, LogLevel, LogsArgs},
+    utils::{
+        config::{config_exists, load_config},
+        sanitize::{Redactor, MAX_PARTIAL_KEEP},
+    },
+};
+
+/// Log read when neither `--file` nor the `log_file` config key is given.
+pub const DEFAULT_LOG_FILE: &str = "devflow.log";
+
+/// Where `logs` remembers the groups it has seen between runs.
+pub const STATE_PATH: &str = ".devflow/last_logs_state.json";
+
+/// How often `--follow` checks the log for appended lines.
+const FOLLOW_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Serialize)]
+struct LogsReport {
+    /// Normalized error trace -> number of occurrences.
+    groups: HashMap<String, usize>,
+    /// Normalized error trace -> log files it appeared in.
+    sources: HashMap<String, BTreeSet<String>>,
+    /// Lines at each level, whether or not they were grouped.
+    levels: BTreeMap<LogLevel, usize>,
+    /// Normalized error trace -> when it was first and last seen, across runs.
+    history: HashMap<String, GroupHistory>,
+    new_since_last_run: Vec<String>,
+    generated_at: DateTime<Utc>,
+}
+
+/// What the state file remembers about a group. `count` is the number of
+/// occurrences in the logs when it was last read.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct GroupHistory {
+    count: usize,
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+impl GroupHistory {
+    fn seen_at(&mut self, at: DateTime<Utc>) {
+        self.first_seen = self.first_seen.min(at);
+        self.last_seen = self.last_seen.max(at);
+    }
+}
+
+/// A state file entry. Files written before first/last-seen tracking map
+/// each group to a bare count.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StoredGroup {
+    History(GroupHistory),
+    Count(usize),
+}
+
+/// An error group seen for the first time while following, printed as one
+/// JSON line with `--json`.
+#[derive(Debug, Serialize)]
+struct NewGroup<'a> {
+    trace: &'a str,
+    source: &'a str,
+    first_seen: DateTime<Utc>,
+}
+
+/// A log to read: the path as given (for display) and resolved against the
+/// project root.
+struct LogFile {
+    label: String,
+    path: PathBuf,
+}
+
+pub async fn run(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    mut args: LogsArgs,
+    json: bool,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let files = log_files(root, config, profile, std::mem::take(&mut args.files))?;
+    let redactor = Redactor::for_project(root, config, profile)?;
+    let tag_sources = files.len() > 1;
+    let state_path = root.join(STATE_PATH);
+    let had_state = state_path.exists();
+    let mut history = load_state(&state_path)?;
+    let (present, missing): (Vec<_>, Vec<_>) = files.iter().partition(|f| f.path.exists());
+    if present.is_empty() {
+        if json {
+            print_json(&LogsReport {
+                groups: HashMap::new(),
+                sources: HashMap::new(),
+                levels: BTreeMap::new(),
+                history: HashMap::new(),
+                new_since_last_run: Vec::new(),
+                generated_at: Utc::now(),
+            })?;
+        } else {
+            let labels = files.iter().map(|f| f.label.as_str()).collect::<Vec<_>>();
+            println!("No {} found", labels.join(", "));
+        }
+        if args.follow {
+            let following = follow(
+                root,
+                &files,
+                &args,
+                &redactor,
+                HashMap::new(),
+                history,
+                json,
+            );
+            return until_shutdown(shutdown, json, following).await;
+        }
         return Ok(());
     }
+    for file in &missing {
+        eprintln!("warning: {} not found", file.label);
+    }
 
-    let content = fs::read_to_string(&log_file)?;
+    let now = Utc::now();
+    let cutoff = args.since.map(|since| now - since);
     let mut groups: HashMap<String, usize> = HashMap::new();
-    for line in content.lines() {
-        if line.contains("ERROR") || line.contains("panic") {
-            *groups.entry(normalize_trace(line)).or_insert(0) += 1;
+    // Earliest and latest timestamp of each group's lines, or now for lines
+    // without one.
+    let mut spans: HashMap<String, (DateTime<Utc>, DateTime<Utc>)> = HashMap::new();
+    let mut sources: HashMap<String, BTreeSet<String>> = HashMap::new();
+    let mut levels: BTreeMap<LogLevel, usize> = BTreeMap::new();
+    for file in &present {
+        let content = fs::read_to_string(&file.path)?;
+        // Lines without a timestamp (e.g. stack frames) belong to the entry
+        // above them.
+        let mut entry_time = None;
+        for line in content.lines() {
+            let Some(parsed) = parse_redacted(line, &args, &redactor) else {
+                continue;
+            };
+            entry_time = parsed.time.or(entry_time);
+            if cutoff
+                .zip(entry_time)
+                .is_some_and(|(cutoff, at)| at < cutoff)
+            {
+                continue;
+            }
+            if let Some(level) = parsed.level {
+                *levels.entry(level).or_insert(0) += 1;
+            }
+            if let Some(trace) = parsed.group(args.level) {
+                let at = entry_time.unwrap_or(now);
+                let span = spans.entry(trace.clone()).or_insert((at, at));
+                *span = (span.0.min(at), span.1.max(at));
+                *groups.entry(trace.clone()).or_insert(0) += 1;
+                sources.entry(trace).or_default().insert(file.label.clone());
+            }
         }
     }
 
-    println!("Grouped errors:");
-    for (trace, count) in &groups {
-        println!("freq={} trace={}", count, trace);
+    let mut new_since_last_run = Vec::new();
+    if had_state {
+        new_since_last_run = groups
+            .keys()
+            .filter(|k| !history.contains_key(*k))
+            .cloned()
+            .collect();
+    }
+    for (trace, &count) in &groups {
+        let (first, last) = spans[trace];
+        history
+            .entry(trace.clone())
+            .and_modify(|h| {
+                h.count = count;
+                h.seen_at(first);
+                h.seen_at(last);
+            })
+            .or_insert(GroupHistory {
+                count,
+                first_seen: first,
+                last_seen: last,
+            });
     }
+    save_state(root, &history)?;
 
-    let state_path = root.join(".devflow/last_logs_state.json");
-    fs::create_dir_all(root.join(".devflow"))?;
-    if state_path.exists() {
-        let old = fs::read_to_string(&state_path)?;
-        let old_groups: HashMap<String, usize> = serde_json::from_str(&old)?;
-        for k in groups.keys() {
-            if !old_groups.contains_key(k) {
-                println!("new_error_since_last_run: {}", k);
+    let report = LogsReport {
+        history: groups_history(&history, &groups),
+        groups,
+        sources,
+        levels,
+        new_since_last_run,
+        generated_at: now,
+    };
+    if json {
+        print_json(&report)?;
+    } else {
+        println!("Grouped errors:");
+        for (trace, count) in &report.groups {
+            let seen = &report.history[trace];
+            let timeline = format!(
+                "(first seen {}, last seen {})",
+                ago(seen.first_seen, now),
+                ago(seen.last_seen, now)
+            );
+            if tag_sources {
+                let files = report.sources[trace].iter().cloned().collect::<Vec<_>>();
+                println!(
+                    "freq={} trace={} source={} {}",
+                    count,
+                    trace,
+                    files.join(","),
+                    timeline
+                );
+            } else {
+                println!("freq={} trace={} {}", count, trace, timeline);
             }
         }
+        if !report.levels.is_empty() {
+            let counts = report
+                .levels
+                .iter()
+                .rev()
+                .map(|(level, count)| format!("{}={}", level_name(*level), count))
+                .collect::<Vec<_>>();
+            println!("levels: {}", counts.join(" "));
+        }
+        for k in &report.new_since_last_run {
+            println!("new_error_since_last_run: {}", k);
+        }
+        println!("first_seen_reference: {}", report.generated_at);
+    }
+    if args.follow {
+        let following = follow(root, &files, &args, &redactor, report.groups, history, json);
+        return until_shutdown(shutdown, json, following).await;
     }
+    Ok(())
+}
+
+/// The `--file` paths, else the configured `log_file`, else
+/// [`DEFAULT_LOG_FILE`]. Relative paths are resolved against `root`.
+fn log_files(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    files: Vec<PathBuf>,
+) -> Result<Vec<LogFile>> {
+    let files = if files.is_empty() {
+        vec![default_log_file(root, config, profile)?]
+    } else {
+        files
+    };
+    Ok(files
+        .into_iter()
+        .map(|file| LogFile {
+            label: file.display().to_string(),
+            path: root.join(file),
+        })
+        .collect())
+}
 
-    fs::write(state_path, serde_json::to_string_pretty(&groups)?)?;
-    println!("first_seen_reference: {}", Utc::now());
+/// The configured `log_file`, else [`DEFAULT_LOG_FILE`], relative to `root`
+/// unless it is absolute. Fails if a config file exists but can't be loaded.
+pub fn default_log_file(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+) -> Result<PathBuf> {
+    let configured = if config_exists(root, config) {
+        load_config(root, config, profile)?.log_file
+    } else {
+        None
+    };
+    Ok(PathBuf::from(
+        configured.unwrap_or_else(|| DEFAULT_LOG_FILE.to_string()),
+    ))
+}
+
+/// Runs `following` until it fails or `shutdown` is cancelled. Following
+/// only waits between polls, so it is always dropped there, between batches
+/// of lines.
+async fn until_shutdown(
+    shutdown: &CancellationToken,
+    json: bool,
+    following: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    match shutdown.run_until_cancelled(following).await {
+        Some(result) => result,
+        None => {
+            if !json {
+                println!("stopped following");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Streams lines appended to `files` until stopped, counting lines at
+/// `args.level` or above into `groups` and announcing each group the first
+/// time it shows up. Without `json` every new line is echoed (prefixed with
+/// its file when following several); with it only new groups are printed.
+async fn follow(
+    root: &Path,
+    files: &[LogFile],
+    args: &LogsArgs,
+    redactor: &Redactor,
+    mut groups: HashMap<String, usize>,
+    mut history: HashMap<String, GroupHistory>,
+    json: bool,
+) -> Result<()> {
+    let tag_sources = files.len() > 1;
+    let mut tails = files
+        .iter()
+        .map(|file| Ok((file.label.as_str(), Tail::at_end(&file.path)?)))
+        .collect::<Result<Vec<_>>>()?;
+    if !json {
+        let paths = files
+            .iter()
+            .map(|f| f.path.display().to_string())
+            .collect::<Vec<_>>();
+        println!("following {} (Ctrl+C to stop)", paths.join(", "));
+    }
+    loop {
+        tokio::time::sleep(FOLLOW_POLL).await;
+        let mut changed = false;
+        for (source, tail) in &mut tails {
+            let source = *source;
+            for line in tail.read_lines()? {
+                if !json {
+                    let shown = redact_line(redactor, &line, args.partial_redact);
+                    if tag_sources {
+                        println!("[{}] {}", source, shown);
+                    } else {
+                        println!("{}", shown);
+                    }
+                }
+                let Some(parsed) = parse_redacted(&line, args, redactor) else {
+                    continue;
+                };
+                let Some(trace) = parsed.group(args.level) else {
+                    continue;
+                };
+                let at = parsed.time.unwrap_or_else(Utc::now);
+                let count = groups.entry(trace.clone()).or_insert(0);
+                *count += 1;
+                history
+                    .entry(trace.clone())
+                    .and_modify(|h| h.seen_at(at))
+                    .or_insert(GroupHistory {
+                        count: 0,
+                        first_seen: at,
+                        last_seen: at,
+                    })
+                    .count = *count;
+                changed = true;
+                if *count > 1 {
+                    continue;
+                }
+                if json {
+                    let group = NewGroup {
+                        trace: &trace,
+                        source,
+                        first_seen: Utc::now(),
+                    };
+                    println!("{}", serde_json::to_string(&group)?);
+                } else if tag_sources {
+                    println!("new_error_group: {} source={}", trace, source);
+                } else {
+                    println!("new_error_group: {}", trace);
+                }
+            }
+        }
+        // Keep the state current so the next one-shot run doesn't report
+        // groups already announced here as new.
+        if changed {
+            save_state(root, &history)?;
+        }
+    }
+}
+
+/// Reads the state file, if any. Bare counts from older versions are dated
+/// to the file's last write, when they were last known to be present.
+fn load_state(path: &Path) -> Result<HashMap<String, GroupHistory>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let stored: HashMap<String, StoredGroup> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let written = fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now());
+    Ok(stored
+        .into_iter()
+        .map(|(trace, group)| {
+            let history = match group {
+                StoredGroup::History(history) => history,
+                StoredGroup::Count(count) => GroupHistory {
+                    count,
+                    first_seen: written,
+                    last_seen: written,
+                },
+            };
+            (trace, history)
+        })
+        .collect())
+}
+
+fn save_state(root: &Path, history: &HashMap<String, GroupHistory>) -> Result<()> {
+    fs::create_dir_all(root.join(".devflow"))?;
+    fs::write(
+        root.join(STATE_PATH),
+        serde_json::to_string_pretty(history)?,
+    )?;
     Ok(())
 }
 
-fn normalize_trace(line: &str) -> String {
-    line.split_whitespace()
-        .map(|t| {
-            if t.chars().all(|c| c.is_numeric()) {
-                "<n>"
-            } else {
-                t
+/// The history entries of the groups in the current report.
+fn groups_history(
+    history: &HashMap<String, GroupHistory>,
+    groups: &HashMap<String, usize>,
+) -> HashMap<String, GroupHistory> {
+    groups
+        .keys()
+        .filter_map(|trace| Some((trace.clone(), history.get(trace)?.clone())))
+        .collect()
+}
+
+/// How long before `now` `at` was, coarsely: `just now`, `5m ago`, `3h ago`,
+/// `2d ago`.
+fn ago(at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let secs = (now - at).num_seconds();
+    match secs {
+        ..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Reads complete lines appended to a file, starting over from the top when
+/// the file is truncated or replaced (log rotation).
+struct Tail {
+    path: PathBuf,
+    file: Option<File>,
+    id: Option<u64>,
+    pos: u64,
+    /// Bytes of a line whose newline hasn't been written yet.
+    partial: Vec<u8>,
+}
+
+impl Tail {
+    /// Starts at the current end of `path`, or at the beginning of the file
+    /// once it's created if it doesn't exist yet.
+    fn at_end(path: &Path) -> Result<Self> {
+        let mut tail = Tail {
+            path: path.to_path_buf(),
+            file: None,
+            id: None,
+            pos: 0,
+            partial: Vec::new(),
+        };
+        match File::open(path) {
+            Ok(file) => {
+                let meta = file.metadata()?;
+                tail.id = Some(file_id(&meta));
+                tail.pos = meta.len();
+                tail.file = Some(file);
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        Ok(tail)
+    }
+
+    fn read_lines(&mut self) -> Result<Vec<String>> {
+        let meta = match fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            // Mid-rotation: the old file is gone and the new one isn't there yet.
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let id = file_id(&meta);
+        if self.file.is_none() || self.id != Some(id) || meta.len() < self.pos {
+            self.file = Some(File::open(&self.path)?);
+            self.id = Some(id);
+            self.pos = 0;
+            self.partial.clear();
+        }
+        let Some(file) = self.file.as_mut() else {
+            return Ok(Vec::new());
+        };
+        file.seek(SeekFrom::Start(self.pos))?;
+        let read = file.read_to_end(&mut self.partial)?;
+        self.pos += read as u64;
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line = self.partial.drain(..=end).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line);
+            lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+        }
+        Ok(lines)
+    }
+}
+
+/// Identifies the file behind a path so a replaced log is noticed even when
+/// the new one has already grown past the old read position.
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> u64 {
+    0
+}
+
+/// What `devflow logs` reads from one line.
+#[derive(Debug)]
+struct LogLine {
+    level: Option<LogLevel>,
+    /// What the group key is made from: a JSON line's message, else the
+    /// whole line.
+    message: String,
+    time: Option<DateTime<Utc>>,
+}
+
+impl LogLine {
+    /// The group key, if the line is at `min_level` or above.
+    fn group(&self, min_level: LogLevel) -> Option<String> {
+        (self.level? >= min_level).then(|| normalize_trace(&self.message))
+    }
+}
+
+/// Reads a JSON object line (unless `LogFormat::Text`) from its
+/// `level`/`severity`, `message`/`msg` and `time`/`timestamp`/`ts` fields, and
+/// any other line (unless `LogFormat::Json`, which skips it) as plain text
+/// with an optional leading timestamp.
+fn parse_line(line: &str, format: LogFormat) -> Option<LogLine> {
+    let parsed = match format {
+        LogFormat::Text => None,
+        LogFormat::Json | LogFormat::Auto => parse_json_line(line),
+    };
+    match parsed {
+        Some(parsed) => Some(parsed),
+        None if format == LogFormat::Json => None,
+        None => Some(LogLine {
+            level: text_level(line),
+            message: line.to_string(),
+            time: leading_timestamp(line),
+        }),
+    }
+}
+
+/// [`parse_line`] with secrets in the message redacted, so that they never
+/// reach group keys, the state file or the output.
+fn parse_redacted(line: &str, args: &LogsArgs, redactor: &Redactor) -> Option<LogLine> {
+    let mut parsed = parse_line(line, args.format)?;
+    parsed.message = redact_line(redactor, &parsed.message, args.partial_redact);
+    Some(parsed)
+}
+
+fn redact_line(redactor: &Redactor, line: &str, partial: bool) -> String {
+    if partial {
+        redactor.redact_partial(line, MAX_PARTIAL_KEEP)
+    } else {
+        redactor.redact(line)
+    }
+}
+
+fn parse_json_line(line: &str) -> Option<LogLine> {
+    let line = line.trim();
+    if !line.starts_with('{') {
+        return None;
+    }
+    let serde_json::Value::Object(fields) = serde_json::from_str(line).ok()? else {
+        return None;
+    };
+    let field = |keys: &[&str]| keys.iter().find_map(|key| fields.get(*key));
+    let message = field(&["message", "msg"])
+        .map(|value| match value {
+            serde_json::Value::String(text) => text.clone(),
+            other => other.to_string(),
+        })
+        .unwrap_or_else(|| line.to_string());
+    let level = field(&["level", "severity"])
+        .and_then(json_level)
+        .or_else(|| text_level(&message));
+    let time = field(&["time", "timestamp", "ts", "@timestamp"]).and_then(|value| match value {
+        serde_json::Value::String(text) => leading_timestamp(text),
+        // Epoch seconds, or milliseconds as written by pino.
+        serde_json::Value::Number(n) => {
+            let n = n.as_f64()?;
+            let millis = if n < 1e11 { n * 1000.0 } else { n };
+            DateTime::from_timestamp_millis(millis as i64)
+        }
+        _ => None,
+    });
+    Some(LogLine {
+        level,
+        message,
+        time,
+    })
+}
+
+/// Level names, and numeric levels as used by pino and bunyan (50 = error,
+/// 40 = warn, 30 = info).
+fn json_level(level: &serde_json::Value) -> Option<LogLevel> {
+    match level {
+        serde_json::Value::String(name) => match name.to_lowercase().as_str() {
+            "error" | "err" | "fatal" | "critical" | "crit" | "alert" | "emergency" | "panic" => {
+                Some(LogLevel::Error)
             }
+            "warn" | "warning" => Some(LogLevel::Warn),
+            "info" | "notice" => Some(LogLevel::Info),
+            "debug" | "trace" | "verbose" => Some(LogLevel::Debug),
+            _ => None,
+        },
+        serde_json::Value::Number(n) => match n.as_f64()? {
+            n if n >= 50.0 => Some(LogLevel::Error),
+            n if n >= 40.0 => Some(LogLevel::Warn),
+            n if n >= 30.0 => Some(LogLevel::Info),
+            _ => Some(LogLevel::Debug),
+        },
+        _ => None,
+    }
+}
+
+fn text_level(line: &str) -> Option<LogLevel> {
+    if line.contains("ERROR") || line.contains("panic") {
+        Some(LogLevel::Error)
+    } else if line.contains("WARN") {
+        Some(LogLevel::Warn)
+    } else if line.contains("INFO") {
+        Some(LogLevel::Info)
+    } else if line.contains("DEBUG") || line.contains("TRACE") {
+        Some(LogLevel::Debug)
+    } else {
+        None
+    }
+}
+
+fn level_name(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::Debug => "debug",
+        LogLevel::Info => "info",
+        LogLevel::Warn => "warn",
+        LogLevel::Error => "error",
+    }
+}
+
+/// `2026-02-24T10:30:00Z`, `2026-02-24 10:30:00,123` and Go's
+/// `2026/02/24 10:30:00`, optionally in brackets and with a UTC offset.
+static TIMESTAMP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\[?(\d{4})[-/](\d{2})[-/](\d{2})[T ](\d{2}:\d{2}:\d{2})(?:[.,]\d+)?(Z|[+-]\d{2}:?\d{2})?",
+    )
+    .expect("valid timestamp pattern")
+});
+
+/// The timestamp at the start of `text`. Without an offset it's taken as
+/// local time.
+fn leading_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    let caps = TIMESTAMP.captures(text.trim_start())?;
+    let naive = NaiveDateTime::parse_from_str(
+        &format!("{}-{}-{} {}", &caps[1], &caps[2], &caps[3], &caps[4]),
+        "%Y-%m-%d %H:%M:%S",
+    )
+    .ok()?;
+    let at = match caps.get(5).map(|m| m.as_str()) {
+        None => Local
+            .from_local_datetime(&naive)
+            .earliest()?
+            .with_timezone(&Utc),
+        Some("Z") => naive.and_utc(),
+        Some(offset) => {
+            let digits = offset[1..].replace(':', "");
+            let secs =
+                digits[..2].parse::<i32>().ok()? * 3600 + digits[2..].parse::<i32>().ok()? * 60;
+            let offset = if offset.starts_with('-') {
+                FixedOffset::west_opt(secs)?
+            } else {
+                FixedOffset::east_opt(secs)?
+            };
+            offset
+                .from_local_datetime(&naive)
+                .single()?
+                .with_timezone(&Utc)
+        }
+    };
+    Some(at)
+}
+
+/// Parses a duration such as `90s`, `15m`, `1h30m`, `2d` or `1w`.
+pub fn parse_duration(s: &str) -> std::result::Result<TimeDelta, String> {
+    let invalid = || format!("invalid duration `{s}`: expected e.g. 30s, 15m, 1h30m, 2d");
+    let mut total = TimeDelta::zero();
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let amount = rest[..digits].parse::<i64>().map_err(|_| invalid())?;
+        let unit = rest[digits..].chars().next().ok_or_else(invalid)?;
+        let part = match unit {
+            's' => TimeDelta::try_seconds(amount),
+            'm' => TimeDelta::try_minutes(amount),
+            'h' => TimeDelta::try_hours(amount),
+            'd' => TimeDelta::try_days(amount),
+            'w' => TimeDelta::try_weeks(amount),
+            _ => None,
+        }
+        .ok_or_else(invalid)?;
+        total = total.checked_add(&part).ok_or_else(invalid)?;
+        rest = &rest[digits + unit.len_utf8()..];
+    }
+    Ok(total)
+}
+
+/// Patterns for the variable parts of an error message and what
+/// [`normalize_trace`] replaces them with, applied in order so that e.g. the
+/// digits inside a timestamp or UUID aren't taken for plain numbers first.
+const NORMALIZE_RULES: &[(&str, &str)] = &[
+    // 2026-02-24T10:30:00.123Z, 2026-02-24 10:30:00+01:00
+    (
+        r"\b\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+        "<ts>",
+    ),
+    (
+        r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+        "<uuid>",
+    ),
+    (r"\b0x[0-9a-fA-F]+\b", "<hex>"),
+    // IPv4 with an optional port.
+    (r"\b\d{1,3}(?:\.\d{1,3}){3}(?::\d+)?\b", "<ip>"),
+    // Absolute paths to files (the last segment has an extension), so that
+    // checkouts in different directories group together; URL paths like
+    // `/api/users` are kept.
+    (
+        r#"(^|[\s'"(=\[])(?:[A-Za-z]:\\|/)(?:[^\s'"():,\[\]/\\]+[/\\])+[^\s'"():,\[\]/\\]+\.[A-Za-z0-9]+"#,
+        "${1}<path>",
+    ),
+    (r"\b\d+\b", "<n>"),
+];
+
+static NORMALIZE_REGEXES: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    NORMALIZE_RULES
+        .iter()
+        .map(|(pattern, placeholder)| {
+            (
+                Regex::new(pattern).expect("valid normalize rule"),
+                *placeholder,
+            )
         })
-        .collect::<Vec<_>>()
-        .join(" ")
+        .collect()
+});
+
+/// Replaces the variable parts of `line` (see [`NORMALIZE_RULES`]) with
+/// placeholders and collapses whitespace, so that occurrences of the same
+/// error share one group.
+fn normalize_trace(line: &str) -> String {
+    let mut text = line.to_string();
+    for (re, placeholder) in NORMALIZE_REGEXES.iter() {
+        text = re.replace_all(&text, *placeholder).into_owned();
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    fn group_of(line: &str, format: LogFormat) -> Option<String> {
+        parse_line(line, format)?.group(LogLevel::Error)
+    }
+
+    #[test]
+    fn levels_are_read_from_text_and_json_lines() {
+        let level = |line: &str| parse_line(line, LogFormat::Auto).and_then(|l| l.level);
+        assert_eq!(level("2026-02-24 WARN disk 91% full"), Some(LogLevel::Warn));
+        assert_eq!(
+            level(r#"{"level":40,"msg":"slow query"}"#),
+            Some(LogLevel::Warn)
+        );
+        assert_eq!(
+            level(r#"{"severity":"notice","msg":"up"}"#),
+            Some(LogLevel::Info)
+        );
+        assert_eq!(level("thread 'main' panicked"), Some(LogLevel::Error));
+        assert_eq!(level("    at foo (bar.js:1:2)"), None);
+
+        let warn = parse_line("WARN cache miss 12", LogFormat::Auto).unwrap();
+        assert_eq!(warn.group(LogLevel::Error), None);
+        assert_eq!(
+            warn.group(LogLevel::Warn).as_deref(),
+            Some("WARN cache miss <n>")
+        );
+    }
+
+    #[test]
+    fn leading_timestamps_in_common_formats() {
+        let utc = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        assert_eq!(
+            leading_timestamp("2026-02-24T10:30:00.123Z ERROR x"),
+            Some(utc("2026-02-24T10:30:00Z"))
+        );
+        assert_eq!(
+            leading_timestamp("[2026-02-24 10:30:00+01:00] ERROR x"),
+            Some(utc("2026-02-24T09:30:00Z"))
+        );
+        let go = Local
+            .with_ymd_and_hms(2026, 2, 24, 10, 30, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(leading_timestamp("2026/02/24 10:30:00 ERROR x"), Some(go));
+        assert_eq!(leading_timestamp("ERROR at 2026-02-24T10:30:00Z"), None);
+        assert_eq!(
+            parse_line(
+                r#"{"level":"error","msg":"x","time":1771929000000}"#,
+                LogFormat::Auto
+            )
+            .and_then(|l| l.time),
+            Some(utc("2026-02-24T10:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn durations_parse_with_units() {
+        assert_eq!(parse_duration("90s"), Ok(TimeDelta::seconds(90)));
+        assert_eq!(parse_duration("1h30m"), Ok(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("2d"), Ok(TimeDelta::days(2)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("3y").is_err());
+    }
+
+    #[test]
+    fn normalize_rules_replace_variable_tokens() {
+        let cases = [
+            ("retry 3 of 5", "retry <n> of <n>"),
+            ("at 2026-02-24T10:30:00.123Z failed", "at <ts> failed"),
+            (
+                "job 3f2b8c1e-9a4d-4e6f-8b1a-0c2d3e4f5a6b lost",
+                "job <uuid> lost",
+            ),
+            ("null deref at 0x7ffdc0de", "null deref at <hex>"),
+            ("connect 10.0.3.17:5432 refused", "connect <ip> refused"),
+            (
+                "panicked at /home/ci/app/src/main.rs:42:7",
+                "panicked at <path>:<n>:<n>",
+            ),
+            (r"open C:\work\app\data.db failed", "open <path> failed"),
+            ("GET /api/users 500", "GET /api/users <n>"),
+            ("v2 db12 ready", "v2 db12 ready"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(normalize_trace(line), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn differently_addressed_panics_share_a_group() {
+        let a = "thread 'main' panicked at /home/alice/app/src/lib.rs:10:5: null pointer 0x7f00aa12 (req 1b4e28ba-2fa1-11d2-883f-0016d3cca427)";
+        let b = "thread 'main' panicked at /srv/build/app/src/lib.rs:10:5: null pointer 0x55d0be01 (req 6fa459ea-ee8a-3ca4-894e-db77e160355e)";
+        assert_eq!(group_of(a, LogFormat::Auto), group_of(b, LogFormat::Auto));
+    }
+
+    #[test]
+    fn json_lines_are_grouped_on_their_message_by_level() {
+        let a = r#"{"level":"error","msg":"db timeout after 30 ms","ts":1700000000}"#;
+        let b = r#"{"severity":"ERROR","message":"db timeout after 45 ms","ts":1700000999}"#;
+        let pino = r#"{"level":50,"msg":"db timeout after 5 ms"}"#;
+        for line in [a, b, pino] {
+            assert_eq!(
+                group_of(line, LogFormat::Auto).as_deref(),
+                Some("db timeout after <n> ms")
+            );
+        }
+        assert_eq!(
+            group_of(
+                r#"{"level":"info","msg":"ERROR page rendered"}"#,
+                LogFormat::Auto
+            ),
+            None
+        );
+        assert_eq!(
+            group_of("ERROR plain 12", LogFormat::Auto).as_deref(),
+            Some("ERROR plain <n>")
+        );
+        assert_eq!(group_of("ERROR plain 12", LogFormat::Json), None);
+        assert_eq!(
+            group_of(r#"{"level":"error","msg":"x"}"#, LogFormat::Text),
+            None
+        );
+    }
+
+    #[test]
+    fn legacy_count_state_is_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        fs::write(
+            &path,
+            r#"{"ERROR old <n>": 3, "ERROR new <n>": {"count": 1, "first_seen": "2026-02-20T00:00:00Z", "last_seen": "2026-02-24T00:00:00Z"}}"#,
+        )
+        .unwrap();
+
+        let state = load_state(&path).unwrap();
+        let old = &state["ERROR old <n>"];
+        assert_eq!(old.count, 3);
+        assert_eq!(old.first_seen, old.last_seen);
+        let new = &state["ERROR new <n>"];
+        assert_eq!(new.first_seen.to_rfc3339(), "2026-02-20T00:00:00+00:00");
+        assert!(load_state(&dir.path().join("missing.json"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn ages_are_coarse() {
+        let now = Utc::now();
+        assert_eq!(ago(now, now), "just now");
+        assert_eq!(ago(now - TimeDelta::minutes(2), now), "2m ago");
+        assert_eq!(ago(now - TimeDelta::hours(5), now), "5h ago");
+        assert_eq!(ago(now - TimeDelta::days(3), now), "3d ago");
+    }
+
+    #[test]
+    fn tail_reads_appended_complete_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("devflow.log");
+        append(&path, "old line\n");
+
+        let mut tail = Tail::at_end(&path).unwrap();
+        assert!(tail.read_lines().unwrap().is_empty());
+
+        append(&path, "first\nsec");
+        assert_eq!(tail.read_lines().unwrap(), ["first"]);
+        append(&path, "ond\n");
+        assert_eq!(tail.read_lines().unwrap(), ["second"]);
+    }
+
+    #[test]
+    fn tail_starts_over_after_truncation_and_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("devflow.log");
+        let mut tail = Tail::at_end(&path).unwrap();
+
+        append(&path, "created later\n");
+        assert_eq!(tail.read_lines().unwrap(), ["created later"]);
+
+        fs::write(&path, "").unwrap();
+        append(&path, "truncated\n");
+        assert_eq!(tail.read_lines().unwrap(), ["truncated"]);
+
+        // Only unix exposes an inode to tell a replaced file apart.
+        #[cfg(unix)]
+        {
+            fs::rename(&path, dir.path().join("devflow.log.1")).unwrap();
+            append(&path, "rotated and already longer than before\n");
+            assert_eq!(
+                tail.read_lines().unwrap(),
+                ["rotated and already longer than before"]
+            );
+        }
+    }
 }
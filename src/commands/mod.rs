@@ -1,3 +1,5 @@
+pub mod clean;
+pub mod completions;
 pub mod dash;
 pub mod deps;
 pub mod env;
@@ -5,31 +7,174 @@ pub mod init;
 pub mod logs;
 pub mod plugin;
 pub mod port;
+pub mod redact;
+pub mod run;
+pub mod schema;
 pub mod snap;
 pub mod up;
 pub mod watch;
 
-use crate::cli::{Cli, Command, EnvMode, SnapMode};
-use anyhow::Result;
+use crate::{
+    cli::{Cli, Command, EnvMode, SnapMode},
+    utils::{
+        config::{check_config_path, config_exists, load_config},
+        shutdown::cancel_on_ctrl_c,
+        snapshot::CaptureOptions,
+    },
+};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+};
+use tokio_util::sync::CancellationToken;
 
 pub async fn run(cli: Cli) -> Result<()> {
+    let color = cli.color_choice();
+    let root = resolve_root(cli.root.as_deref())?;
+    // Relative to where devflow was started, like --root.
+    let config = cli.config.as_deref();
+    if let Some(path) = config {
+        if matches!(cli.command, Command::Init(_)) {
+            bail!(
+                "init writes the config to the project root; use --root to pick it, not --config"
+            );
+        }
+        check_config_path(path)?;
+    }
+    let profile = cli.profile.as_deref();
+    if profile.is_some() {
+        // Fail fast on an unknown profile instead of letting commands that
+        // tolerate a missing config silently fall back to defaults.
+        if config_exists(&root, config) {
+            load_config(&root, config, profile)?;
+        }
+    }
+    let json = cli.json;
+    let shutdown = CancellationToken::new();
+    if runs_until_interrupted(&cli.command) {
+        cancel_on_ctrl_c(shutdown.clone());
+    }
     match cli.command {
-        Command::Up => up::run().await,
-        Command::Port(args) => port::run(args).await,
-        Command::Watch => watch::run().await,
+        Command::Up(args) => up::run(&root, config, profile, args, json, &shutdown).await,
+        Command::Port(args) => port::run(&root, config, profile, args, json, &shutdown).await,
+        Command::Watch(args) => watch::run(&root, config, profile, args, color, &shutdown).await,
         Command::Env(args) => match args.mode {
-            EnvMode::Doctor => env::doctor().await,
-            EnvMode::Fix => env::fix().await,
-            EnvMode::Diff => env::diff().await,
+            EnvMode::Doctor => env::doctor(&root, config, profile, json).await,
+            EnvMode::Fix => env::fix(&root, config, profile, args.dry_run, json).await,
+            EnvMode::Diff => env::diff(&root, profile, args.against, json).await,
+            EnvMode::Export => env::export(&root, profile, args.format, json).await,
+            EnvMode::Encrypt => env::encrypt(&root, args.key_file.as_deref(), json).await,
+            EnvMode::Decrypt => {
+                env::decrypt(&root, args.key_file.as_deref(), args.force, json).await
+            }
         },
-        Command::Logs => logs::run().await,
-        Command::Deps => deps::run().await,
+        Command::Logs(args) => logs::run(&root, config, profile, args, json, &shutdown).await,
+        Command::Deps(args) => deps::run(&root, args, json).await,
         Command::Snap(args) => match args.mode {
-            SnapMode::Save => snap::save().await,
-            SnapMode::Restore => snap::restore().await,
+            SnapMode::Save => {
+                let opts = CaptureOptions {
+                    all: args.all,
+                    include_secrets: args.include_secrets,
+                    partial_redact: args.partial_redact,
+                };
+                snap::save(&root, config, profile, args.name, opts, json).await
+            }
+            SnapMode::Restore if args.env => {
+                snap::restore_env(&root, args.name, args.format, args.apply, json).await
+            }
+            SnapMode::Restore => snap::restore(&root, args.name, args.apply, json).await,
+            SnapMode::List => snap::list(&root, json).await,
+            SnapMode::Diff => snap::diff(&root, config, profile, &args.snapshots, json).await,
         },
-        Command::Dash => dash::run().await,
-        Command::Init => init::run().await,
-        Command::Plugin(args) => plugin::run(args).await,
+        Command::Dash(args) => dash::run(&root, args, color).await,
+        Command::Init(args) => init::run(&root, args, json).await,
+        Command::Plugin(args) => plugin::run(&root, config, profile, args, json).await,
+        Command::Clean(args) => clean::run(&root, config, profile, args, json).await,
+        Command::Run(args) => run::run(&root, config, profile, args, &shutdown).await,
+        Command::Schema => schema::run().await,
+        Command::Redact(args) => redact::run(&root, config, profile, args).await,
+        Command::Completions(args) => completions::run(args).await,
+    }
+}
+
+/// Invocations that keep going until Ctrl+C. They get a `shutdown` token that
+/// Ctrl+C cancels, and stop their children cleanly on it; everything else
+/// keeps the default of exiting at once.
+fn runs_until_interrupted(command: &Command) -> bool {
+    match command {
+        Command::Up(args) => !args.detach,
+        Command::Port(args) => args.watch,
+        Command::Watch(args) => !args.once,
+        Command::Run(_) => true,
+        Command::Logs(args) => args.follow,
+        _ => false,
+    }
+}
+
+/// Where `--log-file`/`DEVFLOW_LOG` asks devflow to log, relative to the
+/// project root: the given file, else `devflow.log`. The configured
+/// `log_file` is left alone, as it is usually the application's.
+pub fn log_file_path(cli: &Cli) -> Result<Option<PathBuf>> {
+    let Some(file) = &cli.log_file else {
+        return Ok(None);
+    };
+    let root = resolve_root(cli.root.as_deref())?;
+    let file = match file {
+        Some(file) => file.clone(),
+        None => PathBuf::from(logs::DEFAULT_LOG_FILE),
+    };
+    Ok(Some(root.join(file)))
+}
+
+/// Resolves `--root` against the working directory, defaulting to the working
+/// directory itself.
+fn resolve_root(root: Option<&Path>) -> Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    let Some(root) = root else {
+        return Ok(cwd);
+    };
+    let path = cwd.join(root);
+    if !path.exists() {
+        bail!("--root {}: no such directory", root.display());
+    }
+    if !path.is_dir() {
+        bail!("--root {}: not a directory", root.display());
     }
+    Ok(path.canonicalize()?)
+}
+
+/// An error for a child process that failed: `main` prints it like any other
+/// error, then exits with the child's exit code instead of 1, so scripts and
+/// CI see the code of the tool devflow ran.
+#[derive(Debug)]
+pub struct ExitStatusError {
+    message: String,
+    /// The child's exit code, or 1 if a signal killed it.
+    pub code: i32,
+}
+
+impl ExitStatusError {
+    pub fn new(message: impl Into<String>, status: ExitStatus) -> Self {
+        Self {
+            message: message.into(),
+            code: status.code().filter(|code| *code != 0).unwrap_or(1),
+        }
+    }
+}
+
+impl fmt::Display for ExitStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ExitStatusError {}
+
+/// Prints `value` as pretty JSON on stdout, for `--json` output.
+pub(crate) fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
 }
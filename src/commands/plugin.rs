@@ -1,16 +1,195 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::Path,
+};
+use tokio::time::Duration;
 
-use crate::{cli::PluginArgs, plugin};
+use super::print_json;
+use crate::{
+    cli::{PluginAction, PluginArgs},
+    plugin::{self, PluginContext, PluginSource},
+    utils::{
+        config::{config_exists, load_config},
+        envcheck::load_layered_env,
+    },
+};
 
-pub async fn run(args: PluginArgs) -> Result<()> {
-    let payload = match args.payload {
-        Some(raw) => {
-            serde_json::from_str(&raw).unwrap_or_else(|_| serde_json::json!({ "raw": raw }))
-        }
+pub async fn run(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    args: PluginArgs,
+    json: bool,
+) -> Result<()> {
+    if let Some(PluginAction::List { manifest }) = args.action {
+        return list(root, config, profile, manifest, json).await;
+    }
+
+    let name = args.name.context("missing plugin name")?;
+    let source = match (args.payload, &args.payload_file) {
+        (Some(raw), _) => Some((raw, "--payload".to_string())),
+        (None, Some(path)) => Some(read_payload_file(path)?),
+        (None, None) => None,
+    };
+    let payload = match source {
         None => serde_json::json!({}),
+        Some((raw, _)) if args.raw => serde_json::json!({ "raw": raw }),
+        Some((raw, source)) => parse_payload(&raw, &source)?,
     };
 
-    let response = plugin::dispatch(&args.name, payload).await?;
+    let context = PluginContext::collect(root, config, profile)?;
+    let opts = plugin::DispatchOptions {
+        allow_read: args.allow_read,
+        timeout: Duration::from_secs(args.timeout),
+        plugin_paths: plugin::plugin_search_path(root, context.config.as_ref()),
+        context,
+        env: forwarded_env(root, profile, &args.pass_env)?,
+        root: root.to_path_buf(),
+    };
+    if args.stream {
+        return plugin::dispatch_stream(&name, payload, &opts, &mut |response| {
+            println!("{}", serde_json::to_string(&response)?);
+            Ok(())
+        })
+        .await;
+    }
+    let response = plugin::dispatch(&name, payload, &opts).await?;
     println!("{}", serde_json::to_string_pretty(&response)?);
     Ok(())
 }
+
+/// The content of `path`, or of stdin for `-`, and how to name it in errors.
+fn read_payload_file(path: &Path) -> Result<(String, String)> {
+    let (content, source) = if path == Path::new("-") {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .context("failed to read the payload from stdin")?;
+        (content, "stdin".to_string())
+    } else {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read payload file {}", path.display()))?;
+        (content, path.display().to_string())
+    };
+    Ok((content, source))
+}
+
+/// Parses a payload strictly; the error carries serde's message and
+/// position, and points at `--raw` for payloads that aren't meant as JSON.
+fn parse_payload(raw: &str, source: &str) -> Result<serde_json::Value> {
+    match serde_json::from_str(raw) {
+        Ok(payload) => Ok(payload),
+        Err(err) => bail!(
+            "invalid JSON payload in {source}: {err} (pass --raw to send it as a string instead)"
+        ),
+    }
+}
+
+/// Values for `--pass-env` keys: devflow's own environment first, then the
+/// project's layered env files. Keys found in neither are skipped with a
+/// warning.
+fn forwarded_env(
+    root: &Path,
+    profile: Option<&str>,
+    keys: &[String],
+) -> Result<Vec<(String, String)>> {
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+    let files = load_layered_env(root, profile)?;
+    let mut forwarded = Vec::new();
+    for key in keys {
+        match env::var(key).ok().or_else(|| files.vars.get(key).cloned()) {
+            Some(value) => forwarded.push((key.clone(), value)),
+            None => eprintln!("warning: --pass-env {key}: not set in the environment or env files"),
+        }
+    }
+    Ok(forwarded)
+}
+
+async fn list(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    with_manifest: bool,
+    json: bool,
+) -> Result<()> {
+    let cfg = if config_exists(root, config) {
+        Some(load_config(root, config, profile)?)
+    } else {
+        None
+    };
+    let mut plugins =
+        plugin::discover_plugins(root, &plugin::plugin_search_path(root, cfg.as_ref()));
+    if with_manifest {
+        for p in plugins.iter_mut().filter(|p| p.shadowed_by.is_none()) {
+            p.manifest = plugin::read_manifest(&p.path).await;
+        }
+    }
+
+    if json {
+        return print_json(&plugins);
+    }
+    if plugins.is_empty() {
+        println!("No plugins found in the plugin path, on PATH or in ./plugins");
+        return Ok(());
+    }
+
+    let width = plugins
+        .iter()
+        .map(|p| p.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    println!("{:<width$}  {:<11} PATH", "NAME", "SOURCE");
+    for p in &plugins {
+        let source = match p.source {
+            PluginSource::PluginPath => "plugin_path",
+            PluginSource::Path => "path",
+            PluginSource::Local => "local",
+        };
+        print!("{:<width$}  {:<11} {}", p.name, source, p.path.display());
+        match &p.shadowed_by {
+            Some(winner) => println!(" (shadowed by {})", winner.display()),
+            None => println!(),
+        }
+        if let Some(m) = &p.manifest {
+            let mut line = m.name.clone();
+            if let Some(version) = &m.version {
+                line.push_str(&format!(" {version}"));
+            }
+            if let Some(description) = &m.description {
+                line.push_str(&format!(" - {description}"));
+            }
+            if !m.commands.is_empty() {
+                line.push_str(&format!(" [commands: {}]", m.commands.join(", ")));
+            }
+            println!("{:<width$}  {}", "", line);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_payload_reports_the_parse_position() {
+        assert_eq!(
+            parse_payload(r#"{"a": [1, 2]}"#, "--payload").expect("valid"),
+            serde_json::json!({ "a": [1, 2] })
+        );
+        let err = parse_payload("{\"a\": 1,\n}", "--payload")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("invalid JSON payload in --payload:"),
+            "{err}"
+        );
+        assert!(err.contains("line 2 column 1"), "{err}");
+        assert!(parse_payload("", "stdin").is_err());
+    }
+}
@@ -1,26 +1,140 @@
-use anyhow::Result;
-use tokio::time::{sleep, Duration};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+    sync::Arc,
+};
+use tokio::{
+    sync::Semaphore,
+    task::JoinSet,
+    time::{sleep, Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
 
+use super::print_json;
 use crate::{
     cli::PortArgs,
-    utils::ports::{common_free_ports, find_owner_by_port, safe_kill_suggestion},
+    utils::{
+        config::{config_exists, load_config},
+        ports::{
+            check_bind_addr, find_owner_by_port, free_ports, safe_kill_suggestion,
+            terminate_process, PortOwner, PortRange, PortScanner, Proto, DEFAULT_DEV_PORTS,
+            LOOPBACK_ADDRS,
+        },
+    },
 };
 
-pub async fn run(args: PortArgs) -> Result<()> {
+/// `--json` result of a single-port lookup.
+#[derive(Debug, Serialize)]
+struct PortReport {
+    port: u16,
+    protocol: Proto,
+    owner: Option<PortOwner>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kill: Option<KillOutcome>,
+}
+
+/// `--json` line printed by `port --watch` after each check, listing the
+/// watched ports that are in use.
+#[derive(Debug, Serialize)]
+struct WatchTick {
+    checked_at: DateTime<Local>,
+    protocol: Proto,
+    owners: Vec<PortOwner>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct KillOutcome {
+    confirmed: bool,
+    escalated: bool,
+    freed: bool,
+}
+
+pub async fn run(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    args: PortArgs,
+    json: bool,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let proto = if args.udp { Proto::Udp } else { Proto::Tcp };
+
     if args.free {
-        let ports = common_free_ports();
+        let addrs = if args.bind.is_empty() {
+            LOOPBACK_ADDRS.to_vec()
+        } else {
+            for &addr in &args.bind {
+                check_bind_addr(addr, proto)?;
+            }
+            args.bind
+        };
+        let candidates = match args.port {
+            Some(port) => vec![port],
+            None => DEFAULT_DEV_PORTS.to_vec(),
+        };
+        let ports = free_ports(&candidates, proto, &addrs);
         println!("{}", serde_json::to_string_pretty(&ports)?);
         return Ok(());
     }
 
+    if let Some(range) = args.range {
+        let owners = scan_range(range, proto).await?;
+        if json {
+            return print_json(&owners);
+        }
+        if owners.is_empty() {
+            println!(
+                "No occupied {} ports in {}-{}",
+                proto, range.start, range.end
+            );
+            return Ok(());
+        }
+        println!("{:<7} {:<6} {:<8} CMD", "PORT", "PROTO", "PID");
+        for owner in owners {
+            println!(
+                "{:<7} {:<6} {:<8} {}",
+                owner.port, owner.protocol, owner.pid, owner.cmd
+            );
+        }
+        return Ok(());
+    }
+
     if args.watch {
-        println!("Watching common dev ports every 2s (ctrl+c to stop)");
+        let desired = if config_exists(root, config) {
+            load_config(root, config, profile)?.desired_ports
+        } else {
+            Vec::new()
+        };
+        let ports = watched_ports(&desired, args.config_ports_only);
+        if !json {
+            println!("Watching ports {:?} every 2s (ctrl+c to stop)", ports);
+        }
+        let mut scanner = PortScanner::new();
+        let mut checks = 0;
         loop {
-            for p in [3000_u16, 5173, 5432, 6379, 8080] {
-                if let Some(owner) = find_owner_by_port(p) {
+            scanner.refresh();
+            let owners = ports
+                .iter()
+                .filter_map(|&p| scanner.owner(p, proto))
+                .collect::<Vec<_>>();
+            if json {
+                // One line per check, even when every port is free, so the
+                // stream can be consumed incrementally and shows it is alive.
+                let tick = WatchTick {
+                    checked_at: Local::now(),
+                    protocol: proto,
+                    owners,
+                };
+                println!("{}", serde_json::to_string(&tick)?);
+            } else {
+                for owner in owners {
                     println!(
-                        "port {} pid={} parent={:?} mem={}KB uptime={}s cmd={}",
+                        "port {}/{} pid={} parent={:?} mem={}KB uptime={}s cmd={}",
                         owner.port,
+                        owner.protocol,
                         owner.pid,
                         owner.parent_pid,
                         owner.memory_kb,
@@ -29,22 +143,187 @@ pub async fn run(args: PortArgs) -> Result<()> {
                     );
                 }
             }
-            sleep(Duration::from_secs(2)).await;
+            checks += 1;
+            tokio::select! {
+                _ = sleep(Duration::from_secs(2)) => {}
+                _ = shutdown.cancelled() => break,
+            }
         }
+        if !json {
+            println!("stopped watching after {} check(s)", checks);
+        }
+        return Ok(());
     }
 
     let target = args.port.unwrap_or(3000);
-    if let Some(owner) = find_owner_by_port(target) {
-        println!("Port {} is owned by pid {}", target, owner.pid);
+    let owner = find_owner_by_port(target, proto);
+    if json {
+        let kill = match (&owner, args.kill) {
+            (Some(owner), true) => Some(kill_owner(owner, args.force, args.yes, true).await?),
+            _ => None,
+        };
+        return print_json(&PortReport {
+            port: target,
+            protocol: proto,
+            owner,
+            kill,
+        });
+    }
+
+    if let Some(owner) = owner {
+        println!(
+            "Port {}/{} is owned by pid {}",
+            target, owner.protocol, owner.pid
+        );
         println!("parent pid: {:?}", owner.parent_pid);
         println!("cmd: {}", owner.cmd);
         println!("mem: {} KB", owner.memory_kb);
         println!("uptime: {} sec", owner.uptime_secs);
+        if args.kill {
+            kill_owner(&owner, args.force, args.yes, false).await?;
+            return Ok(());
+        }
         for tip in safe_kill_suggestion(owner.pid) {
             println!("tip: {}", tip);
         }
     } else {
-        println!("No process found for port {}", target);
+        println!("No process found for port {}/{}", target, proto);
     }
     Ok(())
 }
+
+const DEFAULT_WATCH_PORTS: [u16; 5] = [3000, 5173, 5432, 6379, 8080];
+
+/// Ports monitored by `port --watch`: the defaults plus any configured
+/// `desired_ports`, or only the configured ones when `config_only` is set.
+/// Without configured ports the defaults are always used.
+fn watched_ports(desired: &[u16], config_only: bool) -> Vec<u16> {
+    let mut ports = if config_only && !desired.is_empty() {
+        Vec::new()
+    } else {
+        DEFAULT_WATCH_PORTS.to_vec()
+    };
+    ports.extend_from_slice(desired);
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+/// Maximum number of concurrent owner lookups during a range scan.
+const SCAN_CONCURRENCY: usize = 32;
+
+async fn scan_range(range: PortRange, proto: Proto) -> Result<Vec<PortOwner>> {
+    let permits = Arc::new(Semaphore::new(SCAN_CONCURRENCY));
+    let scanner = Arc::new(PortScanner::new());
+    let mut tasks = JoinSet::new();
+    for port in range.ports() {
+        let permit = permits.clone().acquire_owned().await?;
+        let scanner = scanner.clone();
+        tasks.spawn_blocking(move || {
+            let _permit = permit;
+            scanner.owner(port, proto)
+        });
+    }
+
+    let mut owners = Vec::new();
+    while let Some(res) = tasks.join_next().await {
+        if let Some(owner) = res? {
+            owners.push(owner);
+        }
+    }
+    owners.sort_by_key(|o| o.port);
+    Ok(owners)
+}
+
+/// Terminates `owner`, escalating to a hard kill with `force`. Progress is
+/// printed unless `quiet` is set (JSON mode), where the prompt goes to stderr.
+async fn kill_owner(owner: &PortOwner, force: bool, yes: bool, quiet: bool) -> Result<KillOutcome> {
+    if owner.pid == 1 {
+        bail!("refusing to kill pid 1");
+    }
+    if owner.pid == std::process::id() {
+        bail!("refusing to kill devflow itself");
+    }
+    let mut outcome = KillOutcome::default();
+    let confirmed = yes || {
+        let prompt = format!("Kill pid {} ({})?", owner.pid, owner.cmd);
+        tokio::task::spawn_blocking(move || confirm(&prompt, quiet)).await??
+    };
+    if !confirmed {
+        if !quiet {
+            println!("aborted");
+        }
+        return Ok(outcome);
+    }
+    outcome.confirmed = true;
+
+    terminate_process(owner.pid, false)?;
+    if !quiet {
+        println!("sent terminate signal to pid {}", owner.pid);
+    }
+    outcome.freed = wait_until_free(owner, Duration::from_secs(3)).await;
+
+    if !outcome.freed && force {
+        terminate_process(owner.pid, true)?;
+        if !quiet {
+            println!("sent kill signal to pid {}", owner.pid);
+        }
+        outcome.escalated = true;
+        outcome.freed = wait_until_free(owner, Duration::from_secs(3)).await;
+    }
+
+    if quiet {
+        return Ok(outcome);
+    }
+    if outcome.freed {
+        println!("port {}/{} is now free", owner.port, owner.protocol);
+    } else {
+        println!("port {}/{} is still in use", owner.port, owner.protocol);
+        if !force {
+            println!("tip: re-run with --force to escalate");
+        }
+    }
+    Ok(outcome)
+}
+
+async fn wait_until_free(owner: &PortOwner, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if find_owner_by_port(owner.port, owner.protocol).is_none() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Blocks on stdin; call it from `spawn_blocking` in async code.
+fn confirm(prompt: &str, to_stderr: bool) -> Result<bool> {
+    if to_stderr {
+        eprint!("{prompt} [y/N] ");
+        io::stderr().flush()?;
+    } else {
+        print!("{prompt} [y/N] ");
+        io::stdout().flush()?;
+    }
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::watched_ports;
+
+    #[test]
+    fn watched_ports_merges_config_with_defaults() {
+        assert_eq!(
+            watched_ports(&[8080, 9000], false),
+            vec![3000, 5173, 5432, 6379, 8080, 9000]
+        );
+        assert_eq!(watched_ports(&[9000, 9000], true), vec![9000]);
+        assert_eq!(watched_ports(&[], true), vec![3000, 5173, 5432, 6379, 8080]);
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{bail, Result};
+use std::path::Path;
+use tokio_util::sync::CancellationToken;
+
+use super::ExitStatusError;
+use crate::{
+    cli::RunArgs,
+    utils::{
+        config::load_config,
+        containers::{compose_command, detect_container_runtime, uses_docker_compose},
+        services::{await_ready, spawn_streaming_group, wait_or_stop},
+    },
+};
+
+/// Starts the one configured service named `args.name` and streams its
+/// output until it exits or Ctrl+C stops it. Its `depends_on` services are
+/// not started; a failed readiness check is reported but doesn't stop it.
+pub async fn run(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    args: RunArgs,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let cfg = load_config(root, config, profile)?;
+    let Some(svc) = cfg.services.iter().find(|svc| svc.name == args.name) else {
+        if cfg.services.is_empty() {
+            bail!(
+                "no service named `{}`: the config has no `services`",
+                args.name
+            );
+        }
+        let names = cfg
+            .services
+            .iter()
+            .map(|svc| svc.name.as_str())
+            .collect::<Vec<_>>();
+        bail!(
+            "no service named `{}`; available: {}",
+            args.name,
+            names.join(", ")
+        );
+    };
+
+    if !svc.depends_on.is_empty() {
+        println!(
+            "[{}] not starting its dependencies: {}",
+            svc.name,
+            svc.depends_on.join(", ")
+        );
+    }
+    let runtime = uses_docker_compose(&svc.command)
+        .then(detect_container_runtime)
+        .flatten();
+    let command = compose_command(&svc.command, runtime.as_ref())?;
+
+    println!("[{}] $ {}", svc.name, command);
+    let mut child = spawn_streaming_group(&svc.name, &command, root)?;
+    let running = wait_or_stop(&mut child, shutdown.cancelled());
+    tokio::pin!(running);
+    let status = tokio::select! {
+        status = &mut running => status?,
+        ready = await_ready(svc) => {
+            if !ready {
+                println!("[{}] did not become ready; leaving it running", svc.name);
+            }
+            running.await?
+        }
+    };
+
+    if shutdown.is_cancelled() {
+        println!("[{}] stopped", svc.name);
+        return Ok(());
+    }
+    println!("[{}] exited with {}", svc.name, status);
+    if !status.success() {
+        let message = format!("service {} exited with {}", svc.name, status);
+        return Err(ExitStatusError::new(message, status).into());
+    }
+    Ok(())
+}
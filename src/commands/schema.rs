@@ -0,0 +1,10 @@
+use anyhow::Result;
+
+use super::print_json;
+use crate::utils::config::config_schema;
+
+/// Prints the JSON Schema of the config file, for editors' YAML and JSON
+/// language servers.
+pub async fn run() -> Result<()> {
+    print_json(&config_schema())
+}
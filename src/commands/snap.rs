@@ -1,22 +1,432 @@
-use anyhow::Result;
-use std::env;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
 
-use crate::utils::snapshot::{read_snapshot, save_snapshot};
+use super::{
+    env::{append_keys, export_line, is_shell_name},
+    print_json,
+};
+use crate::{
+    cli::ShellFormat,
+    utils::{
+        envcheck::{format_dotenv_line, parse_dotenv},
+        sanitize::Redactor,
+        services::spawn_detached_argv,
+        snapshot::{
+            capture_snapshot, default_snapshot_name, diff_snapshots, git_state, list_snapshots,
+            read_snapshot, running_commands, save_snapshot, CaptureOptions, GitState, ProcSnapshot,
+            SnapshotDiff,
+        },
+    },
+};
 
-pub async fn save() -> Result<()> {
-    let root = env::current_dir()?;
-    save_snapshot(&root)?;
-    println!("snapshot saved to .devflow/snapshot.json");
+/// What `snap restore` would bring back, or did. Captured env values are
+/// left out.
+#[derive(Debug, Serialize)]
+struct RestorePlan<'a> {
+    name: String,
+    saved_at: DateTime<Utc>,
+    repo: &'a str,
+    applied: bool,
+    /// Env vars whose values were redacted at save time.
+    redacted: &'a [String],
+    processes: Vec<RestoreEntry<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct RestoreEntry<'a> {
+    #[serde(flatten)]
+    process: &'a ProcSnapshot,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Outcome {
+    /// Not running; `--apply` would start it.
+    WouldStart,
+    AlreadyRunning,
+    Started {
+        new_pid: u32,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+pub async fn save(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    name: Option<String>,
+    opts: CaptureOptions,
+    json: bool,
+) -> Result<()> {
+    let name = name.unwrap_or_else(default_snapshot_name);
+    let redactor = Redactor::for_project(root, config, profile)?;
+    let path = save_snapshot(root, config, profile, &name, opts, &redactor)?;
+    if json {
+        return print_json(&serde_json::json!({ "name": name, "path": path }));
+    }
+    println!("snapshot {} saved to {}", name, path);
     Ok(())
 }
 
-pub async fn restore() -> Result<()> {
-    let root = env::current_dir()?;
-    let snap = read_snapshot(&root)?;
-    println!("snapshot from {}", snap.saved_at);
+pub async fn list(root: &Path, json: bool) -> Result<()> {
+    let snapshots = list_snapshots(root)?;
+    if json {
+        return print_json(&snapshots);
+    }
+    if snapshots.is_empty() {
+        println!("no snapshots saved; run `devflow snap save`");
+        return Ok(());
+    }
+    println!("{:<24} {:<26} PROCESSES", "NAME", "SAVED AT");
+    for info in snapshots {
+        let name = if info.legacy {
+            format!("{} (legacy)", info.name)
+        } else {
+            info.name
+        };
+        println!(
+            "{:<24} {:<26} {}",
+            name,
+            info.saved_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            info.processes
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct DiffReport<'a> {
+    from: &'a str,
+    to: &'a str,
+    #[serde(flatten)]
+    diff: SnapshotDiff,
+}
+
+/// Compares two saved snapshots, or one with the current state.
+pub async fn diff(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    names: &[String],
+    json: bool,
+) -> Result<()> {
+    let (from, old, to, new) = match names {
+        [a, b] => {
+            let (from, old) = read_snapshot(root, Some(a))?;
+            let (to, new) = read_snapshot(root, Some(b))?;
+            (from, old, to, new)
+        }
+        [a] => {
+            let (from, old) = read_snapshot(root, Some(a))?;
+            (
+                from,
+                old,
+                "current".to_string(),
+                capture_snapshot(
+                    root,
+                    config,
+                    profile,
+                    CaptureOptions::default(),
+                    &Redactor::for_project(root, config, profile)?,
+                )?,
+            )
+        }
+        _ => bail!("snap diff needs one or two snapshot names (see `devflow snap list`)"),
+    };
+    let diff = diff_snapshots(&old, &new);
+
+    if json {
+        return print_json(&DiffReport {
+            from: &from,
+            to: &to,
+            diff,
+        });
+    }
+
+    println!("comparing {} -> {}", from, to);
+    if diff.is_empty() {
+        println!("no differences");
+        return Ok(());
+    }
+    if !diff.processes_added.is_empty() || !diff.processes_removed.is_empty() {
+        println!("processes:");
+        for cmd in &diff.processes_added {
+            println!("  + {}", cmd);
+        }
+        for cmd in &diff.processes_removed {
+            println!("  - {}", cmd);
+        }
+    }
+    if !diff.env_added.is_empty() || !diff.env_removed.is_empty() || !diff.env_changed.is_empty() {
+        println!("env:");
+        for key in &diff.env_added {
+            println!("  + {}", key);
+        }
+        for key in &diff.env_removed {
+            println!("  - {}", key);
+        }
+        for key in &diff.env_changed {
+            println!("  ~ {}", key);
+        }
+    }
+    Ok(())
+}
+
+/// Lists the snapshotted processes, or with `apply` relaunches each one that
+/// isn't already running (matched by command line) in its recorded working
+/// directory, in snapshot order.
+pub async fn restore(root: &Path, name: Option<String>, apply: bool, json: bool) -> Result<()> {
+    let (name, snap) = read_snapshot(root, name.as_deref())?;
+    if let Some(warning) = branch_warning(snap.git.as_ref(), git_state(root).as_ref()) {
+        eprintln!("warning: {}", warning);
+    }
+    if !snap.redacted.is_empty() {
+        eprintln!(
+            "warning: these env values were redacted when the snapshot was saved and must be set again: {}",
+            snap.redacted.join(", ")
+        );
+    }
+    let running = running_commands();
+
+    let processes = snap
+        .processes
+        .iter()
+        .map(|process| {
+            let outcome = if running.contains(&process.cmd) {
+                Outcome::AlreadyRunning
+            } else if !apply {
+                Outcome::WouldStart
+            } else {
+                match start(root, &snap.cwd, process) {
+                    Ok(new_pid) => Outcome::Started { new_pid },
+                    Err(err) => Outcome::Failed {
+                        error: format!("{err:#}"),
+                    },
+                }
+            };
+            RestoreEntry { process, outcome }
+        })
+        .collect::<Vec<_>>();
+
+    if json {
+        return print_json(&RestorePlan {
+            name,
+            saved_at: snap.saved_at,
+            repo: &snap.cwd,
+            applied: apply,
+            redacted: &snap.redacted,
+            processes,
+        });
+    }
+
+    println!("snapshot {} from {}", name, snap.saved_at);
     println!("repo: {}", snap.cwd);
-    for p in snap.processes {
-        println!("would restore: {} {}", p.name, p.cmd);
+    let mut pending = 0;
+    for entry in &processes {
+        let p = entry.process;
+        match &entry.outcome {
+            Outcome::WouldStart => {
+                pending += 1;
+                println!("would start: {} {}", p.name, p.cmd);
+            }
+            Outcome::AlreadyRunning => println!("already running: {} {}", p.name, p.cmd),
+            Outcome::Started { new_pid } => println!("started: {} (pid {})", p.cmd, new_pid),
+            Outcome::Failed { error } => println!("failed: {}: {}", p.cmd, error),
+        }
+    }
+    if pending > 0 {
+        println!(
+            "run `devflow snap restore --apply` to start {} process(es)",
+            pending
+        );
     }
     Ok(())
 }
+
+/// Variables that describe the shell session or machine rather than the
+/// project; restoring the snapshot's values would only clobber the current
+/// ones, so `restore --env` leaves them out.
+const SESSION_VARS: &[&str] = &[
+    "_", "HOME", "HOSTNAME", "LOGNAME", "OLDPWD", "PATH", "PWD", "SHELL", "SHLVL", "TERM", "USER",
+];
+
+/// `snap restore --env` result.
+#[derive(Debug, Serialize)]
+struct EnvRestore {
+    name: String,
+    saved_at: DateTime<Utc>,
+    applied: bool,
+    /// Restorable vars: recorded in full, not session vars, valid shell names.
+    vars: BTreeMap<String, String>,
+    /// Keys whose values were redacted at save time, so they can't be
+    /// restored; with `--apply`, only those `.env` doesn't define.
+    redacted: Vec<String>,
+    /// With `--apply`, keys appended to `.env`.
+    added: Vec<String>,
+    /// With `--apply`, keys `.env` already defines; their values are kept.
+    kept: Vec<String>,
+}
+
+/// Prints the snapshot's env as shell exports, or with `apply` appends the
+/// vars `.env` doesn't define yet. Values redacted at save time are never
+/// written; their keys are listed so they can be set again by hand.
+pub async fn restore_env(
+    root: &Path,
+    name: Option<String>,
+    format: ShellFormat,
+    apply: bool,
+    json: bool,
+) -> Result<()> {
+    let (name, snap) = read_snapshot(root, name.as_deref())?;
+    if let Some(warning) = branch_warning(snap.git.as_ref(), git_state(root).as_ref()) {
+        eprintln!("warning: {}", warning);
+    }
+    let redacted = snap.redacted.iter().collect::<HashSet<_>>();
+    let mut vars = BTreeMap::new();
+    for (key, value) in &snap.env {
+        if SESSION_VARS.contains(&key.as_str()) || redacted.contains(key) {
+            continue;
+        }
+        if !is_shell_name(key) {
+            eprintln!("warning: skipping {key}: not a valid shell variable name");
+            continue;
+        }
+        vars.insert(key.clone(), value.clone());
+    }
+
+    let mut report = EnvRestore {
+        name,
+        saved_at: snap.saved_at,
+        applied: apply,
+        vars,
+        redacted: snap.redacted.clone(),
+        added: Vec::new(),
+        kept: Vec::new(),
+    };
+    if apply {
+        let current = parse_dotenv(root)?;
+        let (kept, added): (Vec<_>, Vec<_>) = report
+            .vars
+            .keys()
+            .cloned()
+            .partition(|key| current.contains_key(key));
+        let lines = added
+            .iter()
+            .map(|key| format_dotenv_line(key, &report.vars[key]))
+            .collect::<Vec<_>>();
+        append_keys(
+            &root.join(".env"),
+            &lines,
+            &format!(
+                "# restored by devflow snap restore --env from {}",
+                report.name
+            ),
+        )?;
+        report.redacted.retain(|key| !current.contains_key(key));
+        (report.added, report.kept) = (added, kept);
+    }
+    if !report.redacted.is_empty() {
+        eprintln!(
+            "warning: these env values were redacted when the snapshot was saved and must be set again: {}",
+            report.redacted.join(", ")
+        );
+    }
+
+    if json {
+        return print_json(&report);
+    }
+    if !apply {
+        for (key, value) in &report.vars {
+            println!("{}", export_line(format, key, value));
+        }
+        return Ok(());
+    }
+    if report.added.is_empty() {
+        println!(".env already defines every restorable key; no changes");
+    } else {
+        println!(
+            "added {} key(s) to .env: {}",
+            report.added.len(),
+            report.added.join(", ")
+        );
+    }
+    if !report.kept.is_empty() {
+        println!(
+            "kept {} key(s) .env already sets: {}",
+            report.kept.len(),
+            report.kept.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// A warning when the checkout is on a different branch than when the
+/// snapshot was taken.
+fn branch_warning(saved: Option<&GitState>, current: Option<&GitState>) -> Option<String> {
+    let (saved, current) = (saved?, current?);
+    if saved.branch == current.branch {
+        return None;
+    }
+    // `head` comes from the snapshot file, so it may not be ASCII.
+    let describe = |git: &GitState| {
+        git.branch.clone().unwrap_or_else(|| {
+            format!(
+                "detached HEAD {}",
+                git.head.chars().take(7).collect::<String>()
+            )
+        })
+    };
+    Some(format!(
+        "snapshot was taken on {} but the checkout is on {}",
+        describe(saved),
+        describe(current)
+    ))
+}
+
+fn start(root: &Path, repo: &str, process: &ProcSnapshot) -> Result<u32> {
+    let cwd = Path::new(process.cwd.as_deref().unwrap_or(repo));
+    spawn_detached_argv(&process.name, &process.argv()?, cwd, root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(branch: Option<&str>) -> GitState {
+        GitState {
+            branch: branch.map(str::to_string),
+            head: "0123456789abcdef0123456789abcdef01234567".into(),
+            dirty: false,
+        }
+    }
+
+    #[test]
+    fn warns_only_when_branch_differs() {
+        assert_eq!(
+            branch_warning(Some(&git(Some("main"))), Some(&git(Some("main")))),
+            None
+        );
+        assert_eq!(branch_warning(None, Some(&git(Some("main")))), None);
+        assert_eq!(
+            branch_warning(Some(&git(Some("main"))), Some(&git(None))).as_deref(),
+            Some("snapshot was taken on main but the checkout is on detached HEAD 0123456")
+        );
+        let edited = GitState {
+            head: "abcdefé0123".into(),
+            ..git(None)
+        };
+        assert_eq!(
+            branch_warning(Some(&git(Some("main"))), Some(&edited)).as_deref(),
+            Some("snapshot was taken on main but the checkout is on detached HEAD abcdefé")
+        );
+    }
+}
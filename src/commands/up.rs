@@ -1,61 +1,556 @@
-use anyhow::Result;
-use std::env;
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::{path::Path, process::ExitStatus, sync::LazyLock};
+use tokio::{sync::watch, task::JoinSet};
+use tokio_util::sync::CancellationToken;
 
-use crate::utils::{
-    config::load_config,
-    envcheck::{parse_dotenv, validate_env_schema},
-    language::{detect_project_language, expected_toolchain_hint, Language},
+use super::{print_json, ExitStatusError};
+use crate::{
+    cli::UpArgs,
+    utils::{
+        compose::{find_compose_file, load_compose, ComposeService},
+        config::{config_exists, load_config, DevflowConfig},
+        containers::{
+            compose_command, detect_container_runtime, uses_docker_compose, RuntimeStatus,
+        },
+        envcheck::{load_layered_env, parse_schema, EnvIssue},
+        language::{
+            detect_project_language, detect_project_languages, expected_toolchain_hint, Language,
+            ToolchainHint,
+        },
+        ports::{common_free_ports, common_free_udp_ports, next_free_port, PortScanner, Proto},
+        services::{
+            await_ready, order_services, spawn_detached, spawn_streaming, spawn_streaming_group,
+            wait_or_stop,
+        },
+    },
 };
 
-pub async fn run() -> Result<()> {
-    let root = env::current_dir()?;
-    let language = detect_project_language(&root);
+/// Environment status reported by `devflow up` before services start.
+#[derive(Debug, Serialize)]
+struct UpStatus {
+    language: Language,
+    /// Languages besides `language` found in the project, e.g. in monorepo
+    /// subdirectories.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    other_languages: Vec<OtherLanguage>,
+    toolchain: Option<ToolchainStatus>,
+    version_hint: Option<ToolchainHint>,
+    /// The installed version of the hinted tool, checked against the hint.
+    version_check: Option<VersionCheck>,
+    compose_file: bool,
+    /// Services declared in the compose file, in file order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    compose_services: Vec<ComposeService>,
+    /// Why the compose file couldn't be read, when it couldn't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compose_error: Option<String>,
+    /// Published compose ports that something already listens on.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    compose_port_conflicts: Vec<ComposePortConflict>,
+    /// Probed only when there is a compose file or a `docker compose`
+    /// command; `None` then means no runtime is installed.
+    container_runtime: Option<RuntimeStatus>,
+    #[serde(skip)]
+    needs_runtime: bool,
+    config: bool,
+    env_files: Vec<String>,
+    env_issues: Vec<EnvIssue>,
+    busy_ports: Vec<BusyPort>,
+}
 
-    println!("devflow up status");
-    println!("-----------------");
-    println!("language: {:?}", language);
+#[derive(Debug, Serialize)]
+struct ComposePortConflict {
+    service: String,
+    port: u16,
+    protocol: Proto,
+    /// `None` when the owner isn't visible to us, e.g. another user's process.
+    pid: Option<u32>,
+    cmd: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct OtherLanguage {
+    language: Language,
+    /// Marker file relative to the project root, e.g. `backend/Cargo.toml`.
+    marker: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ToolchainStatus {
+    tool: String,
+    path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct VersionCheck {
+    tool: &'static str,
+    /// `None` when the tool isn't installed or its version couldn't be read.
+    installed: Option<String>,
+    expected: String,
+    /// `None` when the two can't be compared, e.g. against `stable`.
+    satisfied: Option<bool>,
+}
+
+impl VersionCheck {
+    fn failed(&self) -> bool {
+        self.installed.is_none() || self.satisfied == Some(false)
+    }
+}
 
+/// First `x.y[.z]` in a tool's version output.
+static VERSION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\d+\.\d+(?:\.\d+)?").expect("valid regex"));
+
+#[derive(Debug, Serialize)]
+struct BusyPort {
+    port: u16,
+    next_free: Option<u16>,
+}
+
+pub async fn run(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    args: UpArgs,
+    json: bool,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let cfg = if config_exists(root, config) {
+        Some(load_config(root, config, profile)?)
+    } else {
+        None
+    };
+    let status = collect_status(root, profile, cfg.as_ref())?;
+
+    if json {
+        print_json(&status)?;
+    } else {
+        print_status(&status);
+    }
+
+    if args.strict {
+        if let Some(toolchain) = status.toolchain.as_ref().filter(|t| t.path.is_none()) {
+            bail!("--strict: {} is not installed", toolchain.tool);
+        }
+        if let Some(check) = status.version_check.as_ref().filter(|c| c.failed()) {
+            bail!(
+                "--strict: {} {} does not satisfy {}",
+                check.tool,
+                check.installed.as_deref().unwrap_or("(unknown version)"),
+                check.expected
+            );
+        }
+        if status.needs_runtime
+            && !status
+                .container_runtime
+                .as_ref()
+                .is_some_and(|r| r.reachable)
+        {
+            bail!("--strict: no reachable container runtime for the compose setup");
+        }
+    }
+
+    if let Some(cfg) = &cfg {
+        let cfg = with_container_runtime(cfg, status.container_runtime.as_ref())?;
+        start(root, &cfg, args.detach, shutdown).await?;
+    }
+    Ok(())
+}
+
+fn collect_status(
+    root: &Path,
+    profile: Option<&str>,
+    cfg: Option<&DevflowConfig>,
+) -> Result<UpStatus> {
+    let language = detect_project_language(root);
     let tool = match language {
         Language::Python => "python",
         Language::Node => "node",
         Language::Go => "go",
         Language::Rust => "rustc",
+        Language::Java => "java",
+        Language::Ruby => "ruby",
+        Language::Php => "php",
         Language::Unknown => "",
     };
+    let toolchain = (!tool.is_empty()).then(|| ToolchainStatus {
+        tool: tool.to_string(),
+        path: which::which(tool).ok().map(|p| p.display().to_string()),
+    });
+
+    let other_languages = detect_project_languages(root)
+        .into_iter()
+        .filter(|(other, marker)| !(*other == language && marker.parent() == Some(root)))
+        .map(|(language, marker)| OtherLanguage {
+            language,
+            marker: marker
+                .strip_prefix(root)
+                .unwrap_or(&marker)
+                .display()
+                .to_string(),
+        })
+        .collect();
+
+    let version_hint = expected_toolchain_hint(root);
+    let version_check = version_hint.as_ref().map(|hint| {
+        let installed = installed_version(hint.tool);
+        VersionCheck {
+            tool: hint.tool,
+            satisfied: installed
+                .as_deref()
+                .and_then(|version| hint.satisfied_by(version)),
+            installed,
+            expected: hint.version.clone(),
+        }
+    });
+
+    let compose_path = find_compose_file(root);
+    let compose_file = compose_path.is_some();
+    let (compose_services, compose_error) = match compose_path.as_deref().map(load_compose) {
+        Some(Ok(services)) => (services, None),
+        Some(Err(err)) => (Vec::new(), Some(format!("{err:#}"))),
+        None => (Vec::new(), None),
+    };
+    let compose_port_conflicts = compose_conflicts(&compose_services);
+    let needs_runtime =
+        compose_file || cfg.is_some_and(|cfg| compose_commands(cfg).next().is_some());
+
+    let mut status = UpStatus {
+        language,
+        other_languages,
+        toolchain,
+        version_hint,
+        version_check,
+        compose_file,
+        compose_services,
+        compose_error,
+        compose_port_conflicts,
+        container_runtime: needs_runtime.then(detect_container_runtime).flatten(),
+        needs_runtime,
+        config: cfg.is_some(),
+        env_files: Vec::new(),
+        env_issues: Vec::new(),
+        busy_ports: Vec::new(),
+    };
+
+    if let Some(cfg) = cfg {
+        let env = load_layered_env(root, profile)?;
+        status.env_issues = env.validate(&parse_schema(&cfg.env));
+        status.env_files = env.files;
+
+        let free = common_free_ports(&cfg.desired_ports);
+        status.busy_ports = cfg
+            .desired_ports
+            .iter()
+            .filter(|p| !free.contains(p))
+            .map(|&port| BusyPort {
+                port,
+                next_free: next_free_port(port),
+            })
+            .collect();
+    }
+    Ok(status)
+}
+
+/// Published ports of `services` that can't be bound because something
+/// already listens on them, with the listener when it can be found.
+fn compose_conflicts(services: &[ComposeService]) -> Vec<ComposePortConflict> {
+    let published = |protocol| {
+        services
+            .iter()
+            .flat_map(|svc| svc.ports.iter().map(move |port| (svc, port)))
+            .filter(move |(_, port)| port.protocol == protocol)
+    };
+    let tcp: Vec<u16> = published(Proto::Tcp).map(|(_, port)| port.host).collect();
+    let udp: Vec<u16> = published(Proto::Udp).map(|(_, port)| port.host).collect();
+    let (free_tcp, free_udp) = (common_free_ports(&tcp), common_free_udp_ports(&udp));
+    let scanner = PortScanner::new();
+
+    published(Proto::Tcp)
+        .filter(|(_, port)| !free_tcp.contains(&port.host))
+        .chain(published(Proto::Udp).filter(|(_, port)| !free_udp.contains(&port.host)))
+        .map(|(svc, port)| {
+            let owner = scanner.owner(port.host, port.protocol);
+            ComposePortConflict {
+                service: svc.name.clone(),
+                port: port.host,
+                protocol: port.protocol,
+                pid: owner.as_ref().map(|o| o.pid),
+                cmd: owner.map(|o| o.cmd).filter(|cmd| !cmd.is_empty()),
+            }
+        })
+        .collect()
+}
+
+/// Version of `tool` from its version output: `rustc 1.78.0 (9b00956e5
+/// 2024-04-29)`, `v18.17.0`, `Python 3.12.1`, `go version go1.22.1 ...`.
+/// rustup and Go would otherwise download the toolchain a project pins
+/// just to answer, so that is turned off.
+fn installed_version(tool: &str) -> Option<String> {
+    let arg = if tool == "go" { "version" } else { "--version" };
+    let output = std::process::Command::new(tool)
+        .arg(arg)
+        .env("RUSTUP_AUTO_INSTALL", "0")
+        .env("GOTOOLCHAIN", "local")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // Older Pythons print their version to stderr.
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    VERSION.find(&text).map(|m| m.as_str().to_string())
+}
 
-    if !tool.is_empty() {
-        match which::which(tool) {
-            Ok(path) => println!("toolchain: ok ({})", path.display()),
-            Err(_) => println!("toolchain: missing ({})", tool),
+fn print_status(status: &UpStatus) {
+    println!("devflow up status");
+    println!("-----------------");
+    println!("language: {:?}", status.language);
+    if !status.other_languages.is_empty() {
+        let others = status
+            .other_languages
+            .iter()
+            .map(|other| format!("{} ({})", other.language, other.marker))
+            .collect::<Vec<_>>();
+        println!("also detected: {}", others.join(", "));
+    }
+
+    if let Some(toolchain) = &status.toolchain {
+        match &toolchain.path {
+            Some(path) => println!("toolchain: ok ({})", path),
+            None => println!("toolchain: missing ({})", toolchain.tool),
         }
     }
 
-    if let Some(hint) = expected_toolchain_hint(&root) {
+    if let Some(hint) = &status.version_hint {
         println!("expected version hint: {}", hint);
     }
+    if let Some(check) = &status.version_check {
+        match (&check.installed, check.satisfied) {
+            (None, _) => println!(
+                "toolchain version: unknown (couldn't run {} to ask), project expects {}",
+                check.tool, check.expected
+            ),
+            (Some(installed), Some(true)) => println!(
+                "toolchain version: ok ({} {} satisfies {})",
+                check.tool, installed, check.expected
+            ),
+            (Some(installed), Some(false)) => println!(
+                "toolchain version: mismatch ({} {} installed, project expects {})",
+                check.tool, installed, check.expected
+            ),
+            (Some(installed), None) => println!(
+                "toolchain version: {} {} installed, project expects {} (not compared)",
+                check.tool, installed, check.expected
+            ),
+        }
+    }
 
-    if root.join("docker-compose.yml").exists() || root.join("compose.yaml").exists() {
+    if status.compose_file {
         println!("services: docker-compose file detected");
+        for svc in &status.compose_services {
+            let image = svc.image.as_deref().unwrap_or("build");
+            if svc.ports.is_empty() {
+                println!(" - {} ({})", svc.name, image);
+            } else {
+                let ports = svc
+                    .ports
+                    .iter()
+                    .map(|p| format!("{}/{}", p.host, p.protocol))
+                    .collect::<Vec<_>>();
+                println!(" - {} ({}): {}", svc.name, image, ports.join(", "));
+            }
+        }
+        if let Some(err) = &status.compose_error {
+            println!("compose: could not read services ({})", err);
+        }
+        for conflict in &status.compose_port_conflicts {
+            let owner = match (conflict.pid, &conflict.cmd) {
+                (Some(pid), Some(cmd)) => format!(" by pid {} ({})", pid, cmd),
+                (Some(pid), None) => format!(" by pid {}", pid),
+                _ => String::new(),
+            };
+            println!(
+                "compose port {}/{} ({}): in use{}",
+                conflict.port, conflict.protocol, conflict.service, owner
+            );
+        }
+        if !status.compose_port_conflicts.is_empty() {
+            println!(
+                "recommendation: free these ports or change their mappings before `docker compose up`"
+            );
+        }
     } else {
         println!("services: no compose file");
     }
+    if status.needs_runtime {
+        match &status.container_runtime {
+            Some(rt) if rt.reachable => println!("container runtime: {} ({})", rt.runtime, rt.path),
+            Some(rt) => println!(
+                "container runtime: {} installed but not reachable (`{} info` failed)",
+                rt.runtime, rt.runtime
+            ),
+            None => println!(
+                "container runtime: none found (install docker, podman or nerdctl to run compose)"
+            ),
+        }
+    }
+
+    if !status.config {
+        println!("recommendation: run `devflow init` to create a devflow config");
+        return;
+    }
 
-    if root.join(".devflow.yaml").exists() {
-        let cfg = load_config(&root)?;
-        let dotenv = parse_dotenv(&root)?;
-        let issues = validate_env_schema(&cfg.env, &dotenv);
-        if issues.is_empty() {
-            println!("env: schema matches .env");
+    if status.env_issues.is_empty() {
+        let files = if status.env_files.is_empty() {
+            ".env".to_string()
         } else {
-            println!("env: {} issues", issues.len());
-            for issue in issues {
-                println!(" - {}: {}", issue.key, issue.reason);
+            status.env_files.join(" + ")
+        };
+        println!("env: schema matches {}", files);
+    } else {
+        println!("env: {} issues", status.env_issues.len());
+        for issue in &status.env_issues {
+            println!(" - {}", issue);
+        }
+        println!("recommendation: run `devflow env doctor` and `devflow env fix`");
+    }
+
+    for busy in &status.busy_ports {
+        match busy.next_free {
+            Some(alt) => println!("port {}: in use (next free: {})", busy.port, alt),
+            None => println!("port {}: in use", busy.port),
+        }
+    }
+}
+
+/// Start commands and service commands that call `docker compose`.
+fn compose_commands(cfg: &DevflowConfig) -> impl Iterator<Item = &String> {
+    cfg.start_commands
+        .iter()
+        .chain(cfg.services.iter().map(|svc| &svc.command))
+        .filter(|command| uses_docker_compose(command))
+}
+
+/// `cfg` with `docker compose` in its commands switched to the detected
+/// runtime's compose. Fails up front, before anything starts, when a command
+/// needs compose and no runtime can serve it.
+fn with_container_runtime(
+    cfg: &DevflowConfig,
+    runtime: Option<&RuntimeStatus>,
+) -> Result<DevflowConfig> {
+    let mut cfg = cfg.clone();
+    for command in &mut cfg.start_commands {
+        *command = compose_command(command, runtime)?;
+    }
+    for svc in &mut cfg.services {
+        svc.command = compose_command(&svc.command, runtime)?;
+    }
+    Ok(cfg)
+}
+
+/// Runs `start_commands` to completion, then launches every configured
+/// service in dependency order, waiting for each one's readiness check before
+/// starting the next. In the foreground, services are supervised until Ctrl+C
+/// or until one of them fails, at which point the rest are stopped.
+async fn start(
+    root: &Path,
+    cfg: &DevflowConfig,
+    detach: bool,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let services = order_services(&cfg.services)?;
+
+    for (i, command) in cfg.start_commands.iter().enumerate() {
+        let name = format!("start-{}", i + 1);
+        println!("[{}] $ {}", name, command);
+        let status = spawn_streaming(&name, command, root)?.wait().await?;
+        if !status.success() {
+            let message = format!("start command `{}` exited with {}", command, status);
+            return Err(ExitStatusError::new(message, status).into());
+        }
+    }
+
+    if services.is_empty() {
+        return Ok(());
+    }
+
+    if detach {
+        for svc in services {
+            let pid = spawn_detached(&svc.name, &svc.command, root)?;
+            println!(
+                "[{}] started in background (pid {}, log .devflow/logs/{}.log)",
+                svc.name, pid, svc.name
+            );
+            if !await_ready(svc).await {
+                bail!("service {} did not become ready", svc.name);
             }
-            println!("recommendation: run `devflow env doctor` and `devflow env fix`");
         }
-    } else {
-        println!("recommendation: run `devflow init` to create .devflow.yaml");
+        return Ok(());
     }
 
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let mut running = JoinSet::new();
+    let mut startup_error = None;
+    for svc in services {
+        println!("[{}] $ {}", svc.name, svc.command);
+        let mut child = spawn_streaming_group(&svc.name, &svc.command, root)?;
+        let mut stop = stop_rx.clone();
+        let name = svc.name.clone();
+        running.spawn(async move {
+            let stopped = async move {
+                let _ = stop.changed().await;
+            };
+            (name, wait_or_stop(&mut child, stopped).await)
+        });
+
+        let ready = tokio::select! {
+            ready = await_ready(svc) => ready,
+            _ = shutdown.cancelled() => false,
+        };
+        if !ready {
+            startup_error = Some(format!("service {} did not become ready", svc.name));
+            let _ = stop_tx.send(true);
+            break;
+        }
+    }
+
+    let mut failed: Option<(String, ExitStatus)> = None;
+    loop {
+        tokio::select! {
+            _ = shutdown.cancelled(), if !*stop_tx.borrow() => {
+                println!("stopping services...");
+                let _ = stop_tx.send(true);
+            }
+            joined = running.join_next() => {
+                let Some(joined) = joined else { break };
+                let (name, status) = joined?;
+                let status = status?;
+                println!("[{}] exited with {}", name, status);
+                if !status.success() && !*stop_tx.borrow() {
+                    failed = Some((name, status));
+                    let _ = stop_tx.send(true);
+                }
+            }
+        }
+    }
+
+    if shutdown.is_cancelled() {
+        println!("all services stopped");
+        return Ok(());
+    }
+    if let Some(err) = startup_error {
+        bail!(err);
+    }
+    if let Some((name, status)) = failed {
+        let message = format!("service {} exited with {}", name, status);
+        return Err(ExitStatusError::new(message, status).into());
+    }
     Ok(())
 }
@@ -1,81 +1,936 @@
-use anyhow::Result;
-use globset::{Glob, GlobSetBuilder};
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
-use std::{env, path::Path, sync::mpsc::channel, time::Duration};
-use tokio::process::Command;
-
-use crate::utils::{
-    config::load_config,
-    language::{detect_project_language, Language},
+use anyhow::{anyhow, bail, Result};
+use crossterm::{
+    cursor::MoveTo,
+    execute,
+    style::{Color, Stylize},
+    terminal::{Clear, ClearType},
 };
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::{gitignore::Gitignore, Match, WalkBuilder};
+use notify::{Config, ErrorKind, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+    process::ExitStatus,
+    time::Duration,
+};
+use tokio::{
+    process::{Child, Command},
+    sync::mpsc::{unbounded_channel, UnboundedReceiver},
+};
+use tokio_util::sync::CancellationToken;
+
+use super::ExitStatusError;
+use crate::{
+    cli::WatchArgs,
+    utils::{
+        color::ColorChoice,
+        config::{config_exists, load_config, DevflowConfig},
+        language::{detect_node_package_manager, detect_project_language, Language},
+        services::{shell_command, spawn_streaming_group, stop_group},
+    },
+};
+
+/// Quiet period after the last filesystem event before tests run.
+pub const DEFAULT_DEBOUNCE_MS: u64 = 300;
 
-pub async fn run() -> Result<()> {
-    let root = env::current_dir()?;
-    let language = detect_project_language(&root);
-    let cfg = load_config(&root).unwrap_or_default();
+/// How long a `--restart` command gets to exit after SIGTERM.
+const RESTART_GRACE: Duration = Duration::from_secs(5);
 
-    let mut builder = GlobSetBuilder::new();
-    for g in &cfg.ignore_globs {
-        let _ = builder.add(Glob::new(g)?);
+pub async fn run(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    args: WatchArgs,
+    color: ColorChoice,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let language = detect_project_language(root);
+    let cfg = if config_exists(root, config) {
+        load_config(root, config, profile)?
+    } else {
+        DevflowConfig::default()
+    };
+    let notify = args.notify || cfg.watch_notify;
+
+    if args.once {
+        return run_once(
+            root,
+            language,
+            cfg.test_command.as_deref(),
+            &args.files,
+            color,
+        )
+        .await;
     }
-    let ignore_set = builder.build()?;
 
-    let (tx, rx) = channel();
-    let mut watcher = RecommendedWatcher::new(tx, Config::default())?;
-    watcher.watch(&root, RecursiveMode::Recursive)?;
+    let rules = IgnoreRules::new(root, &cfg.ignore_globs, !args.no_gitignore)?;
+
+    // notify calls back on its own thread; an unbounded send never blocks it.
+    let (tx, mut rx) = unbounded_channel();
+    let mut watcher = RecommendedWatcher::new(
+        move |event| {
+            let _ = tx.send(event);
+        },
+        Config::default(),
+    )?;
+    let paths = if args.paths.is_empty() {
+        &cfg.watch_paths
+    } else {
+        &args.paths
+    };
+    for path in watch_roots(root, paths)? {
+        watcher
+            .watch(&path, RecursiveMode::Recursive)
+            .map_err(watcher_error)?;
+    }
 
     println!("watching for changes...");
 
-    loop {
-        if let Ok(event) = rx.recv_timeout(Duration::from_secs(1)) {
-            let Ok(ev) = event else {
-                continue;
+    let debounce = Duration::from_millis(args.debounce);
+    if let Some(command) = args.restart.or_else(|| cfg.watch_restart.clone()) {
+        return restart_on_change(root, &command, rx, debounce, rules, shutdown).await;
+    }
+    let (mut runs, mut failures) = (0, 0);
+    // A Ctrl+C during a test run also reaches the test process, which exits;
+    // its result is still reported before stopping.
+    while !shutdown.is_cancelled() {
+        // Changes made while tests run stay queued in `rx`, so they are
+        // picked up here as exactly one follow-up run.
+        let changes = tokio::select! {
+            changed = collect_changes(&mut rx, debounce, |p| !rules.is_ignored(p)) => changed?,
+            _ = shutdown.cancelled() => break,
+        };
+        if args.clear {
+            execute!(std::io::stdout(), Clear(ClearType::All), MoveTo(0, 0))?;
+        }
+        // Without the full list of changes, tests can't be narrowed safely.
+        let impacted = if changes.missed_events {
+            eprintln!("warning: {OVERFLOW_WARNING}; running the full test suite");
+            Vec::new()
+        } else {
+            changes.paths
+        };
+        let time = chrono::Local::now().format("%H:%M:%S").to_string();
+        println!("{}", run_header(&time, impacted.len(), language));
+        let status =
+            run_impacted_tests(root, language, cfg.test_command.as_deref(), &impacted).await?;
+        if let Some(status) = status {
+            print_banner(status, color);
+            runs += 1;
+            if !status.success() {
+                failures += 1;
+            }
+        }
+        if notify {
+            if let Some(status) = status.filter(|s| !s.success()) {
+                notify_failure(language, impacted.len(), status);
+            }
+        }
+    }
+    println!("stopped after {} test run(s), {} failed", runs, failures);
+    Ok(())
+}
+
+/// `watch --once`: runs the tests for `files` (the full suite when empty)
+/// a single time, failing with the test command's exit code.
+async fn run_once(
+    root: &Path,
+    language: Language,
+    test_command: Option<&str>,
+    files: &[PathBuf],
+    color: ColorChoice,
+) -> Result<()> {
+    let changed = files.iter().map(|f| root.join(f)).collect::<Vec<_>>();
+    let Some(status) = run_impacted_tests(root, language, test_command, &changed).await? else {
+        bail!(
+            "no test runner for {} projects; set `test_command` in the config",
+            language
+        );
+    };
+    print_banner(status, color);
+    if !status.success() {
+        return Err(ExitStatusError::new(format!("tests failed with {status}"), status).into());
+    }
+    Ok(())
+}
+
+/// The paths `watch` registers: each of `paths` joined onto `root`, or
+/// `root` itself when there are none. Fails if one doesn't exist or
+/// resolves outside `root`.
+fn watch_roots(root: &Path, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    if paths.is_empty() {
+        return Ok(vec![root.to_path_buf()]);
+    }
+    let canonical_root = root.canonicalize()?;
+    paths
+        .iter()
+        .map(|path| {
+            let full = root.join(path);
+            let Ok(canonical) = full.canonicalize() else {
+                bail!("watch path {} does not exist", path.display());
             };
-            let impacted = ev
-                .paths
-                .iter()
-                .filter(|p| !is_ignored(p, &ignore_set, &root))
-                .collect::<Vec<_>>();
-            if impacted.is_empty() {
+            if !canonical.starts_with(&canonical_root) {
+                bail!(
+                    "watch path {} is outside the project root {}",
+                    path.display(),
+                    root.display()
+                );
+            }
+            Ok(full)
+        })
+        .collect()
+}
+
+/// Keeps `command` running and restarts it, process group and all, after
+/// each debounced change until Ctrl+C. A command that exits on its own is
+/// started again on the next change.
+async fn restart_on_change(
+    root: &Path,
+    command: &str,
+    mut rx: UnboundedReceiver<notify::Result<Event>>,
+    debounce: Duration,
+    rules: IgnoreRules,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    // Batches are collected in their own task so that a debounce in progress
+    // isn't cut short when the command exits.
+    let (batch_tx, mut batches) = tokio::sync::mpsc::channel(1);
+    let collector = tokio::spawn(async move {
+        loop {
+            let changed = collect_changes(&mut rx, debounce, |p| !rules.is_ignored(p)).await?;
+            if batch_tx.send(changed).await.is_err() {
+                return Ok(());
+            }
+        }
+    });
+
+    println!("[watch] $ {}", command);
+    let mut child = Some(spawn_streaming_group("watch", command, root)?);
+    loop {
+        tokio::select! {
+            status = wait_running(&mut child) => {
+                println!("[watch] exited with {}; restarting on next change", status?);
+                child = None;
+            }
+            batch = batches.recv() => {
+                if let Some(mut running) = child.take() {
+                    stop_group(&mut running, RESTART_GRACE).await?;
+                }
+                let Some(changed) = batch else {
+                    return collector.await?;
+                };
+                if changed.missed_events {
+                    eprintln!("warning: {OVERFLOW_WARNING}");
+                }
+                println!("changed files: {}; restarting", changed.paths.len());
+                println!("[watch] $ {}", command);
+                child = Some(spawn_streaming_group("watch", command, root)?);
+            }
+            _ = shutdown.cancelled() => {
+                println!("stopping {}...", command);
+                if let Some(mut running) = child.take() {
+                    stop_group(&mut running, RESTART_GRACE).await?;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Waits for `child` to exit, or forever if nothing is running.
+async fn wait_running(child: &mut Option<Child>) -> std::io::Result<ExitStatus> {
+    match child {
+        Some(child) => child.wait().await,
+        None => std::future::pending().await,
+    }
+}
+
+const OVERFLOW_WARNING: &str =
+    "the file watcher's event queue overflowed, so some changes were missed";
+
+/// A debounced batch of filesystem changes.
+#[derive(Debug, Default, PartialEq)]
+struct Changes {
+    /// The distinct relevant paths that changed.
+    paths: Vec<PathBuf>,
+    /// The OS dropped events (e.g. an inotify queue overflow), so `paths`
+    /// may be incomplete.
+    missed_events: bool,
+}
+
+/// Waits until a relevant path changes, then keeps collecting events until
+/// none has arrived for `debounce`, so an editor save or a large checkout
+/// triggers a single test run. A notice that events were dropped starts a
+/// batch too. Watcher errors about a single unreadable or vanished path are
+/// printed as warnings; any other watcher error ends the watch.
+async fn collect_changes(
+    rx: &mut UnboundedReceiver<notify::Result<Event>>,
+    debounce: Duration,
+    relevant: impl Fn(&Path) -> bool,
+) -> Result<Changes> {
+    let mut changed = BTreeSet::new();
+    let mut missed_events = false;
+    loop {
+        let event = if changed.is_empty() && !missed_events {
+            rx.recv().await
+        } else {
+            match tokio::time::timeout(debounce, rx.recv()).await {
+                Ok(event) => event,
+                Err(_) => {
+                    return Ok(Changes {
+                        paths: changed.into_iter().collect(),
+                        missed_events,
+                    })
+                }
+            }
+        };
+        match event {
+            Some(Ok(ev)) if ev.need_rescan() => missed_events = true,
+            Some(Ok(ev)) => changed.extend(ev.paths.into_iter().filter(|p| relevant(p))),
+            Some(Err(err)) if is_path_error(&err) => {
+                let paths = err
+                    .paths
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>();
+                if paths.is_empty() {
+                    eprintln!("warning: file watcher: {err}");
+                } else {
+                    eprintln!("warning: file watcher: {}: {err}", paths.join(", "));
+                }
+            }
+            Some(Err(err)) => return Err(watcher_error(err)),
+            None => bail!("file watcher stopped unexpectedly"),
+        }
+    }
+}
+
+/// Errors that only lose the events of the paths they name: a directory
+/// that can't be read, or one removed while being watched.
+fn is_path_error(err: &notify::Error) -> bool {
+    match &err.kind {
+        ErrorKind::Io(io) => matches!(
+            io.kind(),
+            std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::NotFound
+        ),
+        ErrorKind::PathNotFound | ErrorKind::WatchNotFound => true,
+        _ => false,
+    }
+}
+
+fn watcher_error(err: notify::Error) -> anyhow::Error {
+    match err.kind {
+        ErrorKind::MaxFilesWatch => anyhow!(
+            "file watcher failed: the OS limit on watched files was reached; \
+             watch fewer directories with --path, or raise fs.inotify.max_user_watches"
+        ),
+        _ => anyhow!("file watcher failed: {err}"),
+    }
+}
+
+/// Decides which changed paths `watch` skips: anything matching the
+/// configured `ignore_globs`, plus, unless `--no-gitignore` is given, paths
+/// excluded by a `.gitignore` anywhere in the tree and `.git` directories.
+struct IgnoreRules {
+    root: PathBuf,
+    globs: GlobSet,
+    gitignore: bool,
+    /// One matcher per `.gitignore`, deepest directory first so that nested
+    /// files take precedence, as in git.
+    gitignores: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+    fn new(root: &Path, globs: &[String], gitignore: bool) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for g in globs {
+            builder.add(Glob::new(g)?);
+        }
+
+        let mut gitignores = Vec::new();
+        if gitignore {
+            let files = WalkBuilder::new(root)
+                .hidden(false)
+                .require_git(false)
+                .filter_entry(|e| e.file_name() != ".git")
+                .build()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_name() == ".gitignore")
+                .map(|entry| entry.into_path());
+            for file in files {
+                let (matcher, err) = Gitignore::new(&file);
+                if let Some(err) = err {
+                    eprintln!("warning: {}: {}", file.display(), err);
+                }
+                gitignores.push(matcher);
+            }
+            gitignores.sort_by_key(|m| std::cmp::Reverse(m.path().components().count()));
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            globs: builder.build()?,
+            gitignore,
+            gitignores,
+        })
+    }
+
+    fn is_ignored(&self, path: &Path) -> bool {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        if self.globs.is_match(rel) {
+            return true;
+        }
+        if !self.gitignore {
+            return false;
+        }
+        if rel
+            .components()
+            .any(|c| c == Component::Normal(".git".as_ref()))
+        {
+            return true;
+        }
+        let is_dir = path.is_dir();
+        for matcher in &self.gitignores {
+            if !path.starts_with(matcher.path()) {
                 continue;
             }
-            println!("changed files: {}", impacted.len());
-            run_impacted_tests(language).await?;
+            match matcher.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
         }
+        false
+    }
+}
+
+/// The line printed before each test run.
+fn run_header(time: &str, changed: usize, language: Language) -> String {
+    format!(
+        "== {} | {} changed file(s) | {} ==",
+        time, changed, language
+    )
+}
+
+/// Prints a PASS/FAIL banner for a finished test run.
+fn print_banner(status: ExitStatus, color: ColorChoice) {
+    println!("{}", banner(status, color.enabled(&io::stdout())));
+}
+
+fn banner(status: ExitStatus, colored: bool) -> String {
+    let (label, color) = if status.success() {
+        ("PASS", Color::Green)
+    } else {
+        ("FAIL", Color::Red)
+    };
+    if colored {
+        let label = format!(" {} ", label).with(Color::Black).on(color).bold();
+        format!("{} {}", label, status)
+    } else {
+        format!("{} {}", label, status)
     }
 }
 
-fn is_ignored(path: &Path, set: &globset::GlobSet, root: &Path) -> bool {
-    let rel = path.strip_prefix(root).unwrap_or(path);
-    set.is_match(rel)
+/// Runs the tests affected by `changed` (absolute paths under `root`),
+/// returning the exit status, or `None` when the language has no known test
+/// runner. A configured `test_command` is run through the shell as-is in
+/// place of the language default.
+async fn run_impacted_tests(
+    root: &Path,
+    language: Language,
+    test_command: Option<&str>,
+    changed: &[PathBuf],
+) -> Result<Option<ExitStatus>> {
+    if let Some(command) = test_command {
+        println!("$ {}", command);
+        let status = shell_command(command).current_dir(root).status().await?;
+        return Ok(Some(status));
+    }
+
+    let relative = changed
+        .iter()
+        .map(|p| p.strip_prefix(root).unwrap_or(p).to_path_buf())
+        .collect::<Vec<_>>();
+    let Some((program, args)) = test_invocation(root, language, &relative) else {
+        return Ok(None);
+    };
+
+    println!("$ {} {}", program, args.join(" "));
+    let status = Command::new(program)
+        .args(&args)
+        .current_dir(root)
+        .status()
+        .await?;
+    Ok(Some(status))
 }
 
-async fn run_impacted_tests(language: Language) -> Result<()> {
-    let mut cmd = match language {
+/// Chooses the test command for `language`, narrowed to the tests related to
+/// `changed` (paths relative to the root) when they map cleanly onto test
+/// targets, and the full suite otherwise:
+///
+/// - Rust: `cargo test -- <stems>`, using each changed file's stem (or its
+///   directory for `mod.rs`) as a test name filter.
+/// - Node: `jest --findRelatedTests <files>`, run through the project's
+///   package manager (`npx`, `yarn`, `pnpm exec` or `bunx`).
+/// - Python: `pytest <files>` when every change is a `test_*.py` file.
+/// - Go: `go test` on the packages containing the changed files.
+/// - Java: `mvn test -Dtest=<classes>` with a `pom.xml`, else
+///   `gradle test --tests <classes>` (`./gradlew` when present), when every
+///   change is a `*Test.java` file.
+/// - Ruby: `bundle exec rspec <files>` when every change is a `*_spec.rb` file.
+/// - PHP: `phpunit <files>` when every change is a `*Test.php` file.
+fn test_invocation(
+    root: &Path,
+    language: Language,
+    changed: &[PathBuf],
+) -> Option<(&'static str, Vec<String>)> {
+    let all_with_ext = |exts: &[&str]| {
+        !changed.is_empty()
+            && changed.iter().all(|p| {
+                p.extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| exts.contains(&e))
+            })
+    };
+    let display = |p: &PathBuf| p.display().to_string();
+    // Changed files that all end in `suffix`, e.g. `_spec.rb`.
+    let only_tests = |suffix: &str| {
+        (!changed.is_empty()
+            && changed.iter().all(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.ends_with(suffix))
+            }))
+        .then_some(changed)
+    };
+
+    match language {
         Language::Python => {
-            let mut c = Command::new("pytest");
-            c.arg("-q");
-            c
+            let mut args = vec!["-q".to_string()];
+            let tests_only = all_with_ext(&["py"])
+                && changed.iter().all(|p| {
+                    p.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.starts_with("test_"))
+                });
+            if tests_only {
+                args.extend(changed.iter().map(display));
+            }
+            Some(("pytest", args))
         }
         Language::Node => {
-            let mut c = Command::new("npx");
-            c.args(["jest", "--passWithNoTests"]);
-            c
+            let (program, exec_args) = detect_node_package_manager(root).exec();
+            let mut args = exec_args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+            args.extend(["jest".to_string(), "--passWithNoTests".to_string()]);
+            if all_with_ext(&["js", "jsx", "ts", "tsx", "mjs", "cjs"]) {
+                args.push("--findRelatedTests".into());
+                args.extend(changed.iter().map(display));
+            }
+            Some((program, args))
         }
         Language::Rust => {
-            let mut c = Command::new("cargo");
-            c.arg("test");
-            c
+            let mut args = vec!["test".to_string()];
+            if all_with_ext(&["rs"]) {
+                if let Some(filters) = rust_test_filters(changed) {
+                    args.push("--".into());
+                    args.extend(filters);
+                }
+            }
+            Some(("cargo", args))
         }
         Language::Go => {
-            let mut c = Command::new("go");
-            c.args(["test", "./..."]);
-            c
+            let mut args = vec!["test".to_string()];
+            if all_with_ext(&["go"]) {
+                let packages = changed
+                    .iter()
+                    .map(|p| match p.parent().filter(|d| !d.as_os_str().is_empty()) {
+                        Some(dir) => format!("./{}", dir.display()),
+                        None => ".".to_string(),
+                    })
+                    .collect::<BTreeSet<_>>();
+                args.extend(packages);
+            } else {
+                args.push("./...".into());
+            }
+            Some(("go", args))
         }
-        Language::Unknown => return Ok(()),
-    };
+        Language::Java => {
+            let classes = only_tests("Test.java").map(|files| {
+                files
+                    .iter()
+                    .filter_map(|p| p.file_stem()?.to_str().map(str::to_string))
+                    .collect::<Vec<_>>()
+            });
+            if root.join("pom.xml").exists() {
+                let mut args = vec!["test".to_string()];
+                if let Some(classes) = classes {
+                    args.push(format!("-Dtest={}", classes.join(",")));
+                }
+                return Some(("mvn", args));
+            }
+            let mut args = vec!["test".to_string()];
+            for class in classes.into_iter().flatten() {
+                args.push("--tests".into());
+                args.push(class);
+            }
+            let program = if root.join("gradlew").exists() {
+                "./gradlew"
+            } else {
+                "gradle"
+            };
+            Some((program, args))
+        }
+        Language::Ruby => {
+            let mut args = vec!["exec".to_string(), "rspec".to_string()];
+            if let Some(specs) = only_tests("_spec.rb") {
+                args.extend(specs.iter().map(display));
+            }
+            Some(("bundle", args))
+        }
+        Language::Php => {
+            let args = only_tests("Test.php")
+                .map(|tests| tests.iter().map(display).collect())
+                .unwrap_or_default();
+            Some(("phpunit", args))
+        }
+        Language::Unknown => None,
+    }
+}
 
-    let status = cmd.status().await?;
-    println!("test run status: {}", status);
-    Ok(())
+/// Test name filters for changed Rust files, or `None` if any of them (a
+/// crate root such as `lib.rs` or `main.rs`) can't be narrowed to a module.
+fn rust_test_filters(changed: &[PathBuf]) -> Option<Vec<String>> {
+    let mut filters = BTreeSet::new();
+    for path in changed {
+        let stem = path.file_stem()?.to_str()?;
+        let module = match stem {
+            "lib" | "main" | "build" => return None,
+            "mod" => path.parent()?.file_name()?.to_str()?,
+            _ => stem,
+        };
+        filters.insert(module.to_string());
+    }
+    Some(filters.into_iter().collect())
+}
+
+/// Tells a developer who has switched windows that the tests failed: a
+/// desktop notification when built with `desktop-notify` and a notifier is
+/// available, otherwise the terminal bell.
+fn notify_failure(language: Language, changed: usize, status: ExitStatus) {
+    let body = format!(
+        "{:?} tests failed ({}) after {} changed file(s)",
+        language, status, changed
+    );
+    if !desktop_notify("devflow watch: tests failed", &body) {
+        print!("\x07");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+#[cfg(feature = "desktop-notify")]
+fn desktop_notify(summary: &str, body: &str) -> bool {
+    notify_rust::Notification::new()
+        .summary(summary)
+        .body(body)
+        .appname("devflow")
+        .show()
+        .is_ok()
+}
+
+#[cfg(not(feature = "desktop-notify"))]
+fn desktop_notify(_summary: &str, _body: &str) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::EventKind;
+
+    fn event(path: &str) -> notify::Result<Event> {
+        Ok(Event::new(EventKind::Any).add_path(PathBuf::from(path)))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn banner_is_plain_when_color_is_off() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let failed = ExitStatus::from_raw(1 << 8);
+        let plain = banner(failed, ColorChoice::Never.enabled(&io::stdout()));
+        assert_eq!(plain, "FAIL exit status: 1");
+        let colored = banner(failed, ColorChoice::Always.enabled(&io::stdout()));
+        assert!(
+            colored.contains('\x1b') && colored.contains(" FAIL "),
+            "{colored:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn burst_of_events_becomes_one_batch() {
+        let (tx, mut rx) = unbounded_channel();
+        for path in ["src/a.rs", "src/a.rs", "target/x", "src/b.rs"] {
+            tx.send(event(path)).expect("send");
+        }
+        let changed = collect_changes(&mut rx, Duration::from_millis(20), |p| {
+            !p.starts_with("target")
+        })
+        .await
+        .expect("changes");
+        assert_eq!(
+            changed.paths,
+            vec![PathBuf::from("src/a.rs"), PathBuf::from("src/b.rs")]
+        );
+
+        tx.send(event("src/c.rs")).expect("send");
+        let changed = collect_changes(&mut rx, Duration::from_millis(20), |_| true)
+            .await
+            .expect("changes");
+        assert_eq!(changed.paths, vec![PathBuf::from("src/c.rs")]);
+    }
+
+    #[tokio::test]
+    async fn waiting_for_changes_leaves_the_runtime_free() {
+        let (tx, mut rx) = unbounded_channel();
+        let collect = tokio::spawn(async move {
+            collect_changes(&mut rx, Duration::from_millis(20), |_| true).await
+        });
+        // On the single-threaded test runtime this only runs if the
+        // collector yields while it waits.
+        tokio::task::yield_now().await;
+        tx.send(event("src/a.rs")).expect("send");
+        let changed = collect.await.expect("join").expect("changes");
+        assert_eq!(changed.paths, vec![PathBuf::from("src/a.rs")]);
+    }
+
+    fn invocation(language: Language, changed: &[&str]) -> Vec<String> {
+        let changed = changed.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let (program, args) = test_invocation(Path::new(""), language, &changed).expect("runner");
+        std::iter::once(program.to_string()).chain(args).collect()
+    }
+
+    #[test]
+    fn rust_changes_become_test_filters() {
+        assert_eq!(
+            invocation(
+                Language::Rust,
+                &["src/commands/watch.rs", "src/utils/mod.rs"]
+            ),
+            ["cargo", "test", "--", "utils", "watch"]
+        );
+        assert_eq!(
+            invocation(Language::Rust, &["src/lib.rs", "src/cli.rs"]),
+            ["cargo", "test"]
+        );
+        assert_eq!(
+            invocation(Language::Rust, &["Cargo.toml"]),
+            ["cargo", "test"]
+        );
+    }
+
+    #[test]
+    fn node_and_python_narrow_only_unambiguous_changes() {
+        assert_eq!(
+            invocation(Language::Node, &["src/app.ts"]),
+            [
+                "npx",
+                "jest",
+                "--passWithNoTests",
+                "--findRelatedTests",
+                "src/app.ts"
+            ]
+        );
+        assert_eq!(
+            invocation(Language::Python, &["tests/test_api.py"]),
+            ["pytest", "-q", "tests/test_api.py"]
+        );
+        assert_eq!(
+            invocation(Language::Python, &["app/api.py", "tests/test_api.py"]),
+            ["pytest", "-q"]
+        );
+    }
+
+    #[test]
+    fn go_changes_map_to_packages() {
+        assert_eq!(
+            invocation(
+                Language::Go,
+                &["main.go", "pkg/db/db.go", "pkg/db/db_test.go"]
+            ),
+            ["go", "test", ".", "./pkg/db"]
+        );
+        assert_eq!(
+            invocation(Language::Go, &["go.mod"]),
+            ["go", "test", "./..."]
+        );
+        assert!(test_invocation(Path::new(""), Language::Unknown, &[]).is_none());
+    }
+
+    #[test]
+    fn node_tests_run_through_the_package_manager() {
+        let td = tempfile::tempdir().expect("tempdir");
+        std::fs::write(td.path().join("pnpm-lock.yaml"), "").expect("write lock");
+        let changed = [PathBuf::from("src/app.ts")];
+        let (program, args) = test_invocation(td.path(), Language::Node, &changed).expect("runner");
+        assert_eq!(program, "pnpm");
+        assert_eq!(
+            args,
+            [
+                "exec",
+                "jest",
+                "--passWithNoTests",
+                "--findRelatedTests",
+                "src/app.ts"
+            ]
+        );
+    }
+
+    #[test]
+    fn java_ruby_and_php_narrow_to_changed_tests() {
+        assert_eq!(
+            invocation(Language::Java, &["src/test/java/app/UserServiceTest.java"]),
+            ["gradle", "test", "--tests", "UserServiceTest"]
+        );
+        assert_eq!(
+            invocation(Language::Java, &["src/main/java/app/UserService.java"]),
+            ["gradle", "test"]
+        );
+        let td = tempfile::tempdir().expect("tempdir");
+        std::fs::write(td.path().join("pom.xml"), "<project/>").expect("write pom");
+        let changed = [PathBuf::from("src/test/java/AppTest.java")];
+        let (program, args) = test_invocation(td.path(), Language::Java, &changed).expect("runner");
+        assert_eq!(
+            (program, args),
+            ("mvn", vec!["test".into(), "-Dtest=AppTest".into()])
+        );
+
+        assert_eq!(
+            invocation(Language::Ruby, &["spec/models/user_spec.rb"]),
+            ["bundle", "exec", "rspec", "spec/models/user_spec.rb"]
+        );
+        assert_eq!(
+            invocation(Language::Ruby, &["app/models/user.rb"]),
+            ["bundle", "exec", "rspec"]
+        );
+        assert_eq!(
+            invocation(Language::Php, &["tests/UserTest.php"]),
+            ["phpunit", "tests/UserTest.php"]
+        );
+        assert_eq!(invocation(Language::Php, &["src/User.php"]), ["phpunit"]);
+    }
+
+    #[test]
+    fn header_names_time_change_count_and_language() {
+        assert_eq!(
+            run_header("09:15:02", 3, Language::Rust),
+            "== 09:15:02 | 3 changed file(s) | rust =="
+        );
+    }
+
+    #[tokio::test]
+    async fn configured_test_command_replaces_language_default() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let status = run_impacted_tests(td.path(), Language::Unknown, Some("exit 3"), &[])
+            .await
+            .expect("run")
+            .expect("status");
+        assert_eq!(status.code(), Some(3));
+    }
+
+    #[test]
+    fn gitignore_rules_are_unioned_with_globs() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let root = td.path().canonicalize().expect("canonicalize");
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n!keep.log\n").expect("write");
+        std::fs::create_dir_all(root.join("gen")).expect("mkdir");
+        std::fs::write(root.join("gen/.gitignore"), "*.rs\n").expect("write");
+
+        let rules = IgnoreRules::new(&root, &["dist/**".into()], true).expect("rules");
+        for ignored in [
+            "target/debug/app",
+            "app.log",
+            "gen/out.rs",
+            "dist/a.js",
+            ".git/index",
+        ] {
+            assert!(rules.is_ignored(&root.join(ignored)), "{ignored}");
+        }
+        for watched in ["src/main.rs", "keep.log", "gen/README.md"] {
+            assert!(!rules.is_ignored(&root.join(watched)), "{watched}");
+        }
+
+        let rules = IgnoreRules::new(&root, &["dist/**".into()], false).expect("rules");
+        assert!(!rules.is_ignored(&root.join("app.log")));
+        assert!(rules.is_ignored(&root.join("dist/a.js")));
+    }
+
+    #[test]
+    fn watch_roots_must_exist_under_the_root() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let root = td.path();
+        std::fs::create_dir_all(root.join("src")).expect("mkdir");
+        std::fs::create_dir_all(root.join("tests")).expect("mkdir");
+
+        assert_eq!(watch_roots(root, &[]).expect("roots"), [root]);
+        assert_eq!(
+            watch_roots(root, &["src".into(), "tests".into()]).expect("roots"),
+            [root.join("src"), root.join("tests")]
+        );
+        let err = watch_roots(root, &["docs".into()]).unwrap_err();
+        assert!(err.to_string().contains("does not exist"), "{err}");
+        let err = watch_roots(root, &["..".into()]).unwrap_err();
+        assert!(
+            err.to_string().contains("outside the project root"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn overflow_starts_a_batch_and_path_errors_are_skipped() {
+        let (tx, mut rx) = unbounded_channel();
+        tx.send(Ok(
+            Event::new(EventKind::Other).set_flag(notify::event::Flag::Rescan)
+        ))
+        .expect("send");
+        let changed = collect_changes(&mut rx, Duration::from_millis(20), |_| true)
+            .await
+            .expect("changes");
+        assert_eq!(
+            changed,
+            Changes {
+                paths: Vec::new(),
+                missed_events: true
+            }
+        );
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        tx.send(Err(notify::Error::io(denied).add_path("secret".into())))
+            .expect("send");
+        tx.send(event("src/a.rs")).expect("send");
+        let changed = collect_changes(&mut rx, Duration::from_millis(20), |_| true)
+            .await
+            .expect("changes");
+        assert_eq!(changed.paths, vec![PathBuf::from("src/a.rs")]);
+        assert!(!changed.missed_events);
+
+        tx.send(Err(notify::Error::new(ErrorKind::MaxFilesWatch)))
+            .expect("send");
+        let err = collect_changes(&mut rx, Duration::from_millis(20), |_| true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--path"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn closed_watcher_is_an_error() {
+        let (tx, mut rx) = unbounded_channel::<notify::Result<Event>>();
+        drop(tx);
+        assert!(
+            collect_changes(&mut rx, Duration::from_millis(20), |_| true)
+                .await
+                .is_err()
+        );
+    }
 }
@@ -1,15 +1,35 @@
 use anyhow::Result;
-use clap::Parser;
-use devflow::{cli::Cli, commands};
-use tracing_subscriber::{fmt, EnvFilter};
+use clap::{CommandFactory, FromArgMatches};
+use devflow::{
+    cli::Cli,
+    commands::{self, ExitStatusError},
+    utils::logging::{self, RESULT_TARGET},
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .without_time()
-        .init();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    let command = matches.subcommand_name().unwrap_or_default().to_string();
+    let log_guard = logging::init(
+        commands::log_file_path(&cli)?.as_deref(),
+        logging::verbosity_level(cli.verbose, cli.quiet),
+        cli.color_choice(),
+    )?;
 
-    let cli = Cli::parse();
-    commands::run(cli).await
+    tracing::info!("{command}: started");
+    let result = commands::run(cli).await;
+    match &result {
+        Ok(()) => tracing::info!(target: RESULT_TARGET, "{command}: done"),
+        Err(err) => tracing::error!(target: RESULT_TARGET, "{command}: {err:#}"),
+    }
+    if let Err(err) = &result {
+        if let Some(failed) = err.downcast_ref::<ExitStatusError>() {
+            eprintln!("Error: {err:?}");
+            // `exit` skips destructors, so flush the log file first.
+            drop(log_guard);
+            std::process::exit(failed.code);
+        }
+    }
+    result
 }
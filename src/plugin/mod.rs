@@ -1,12 +1,90 @@
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, process::Stdio};
-use tokio::process::Command;
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+    time::{timeout, Duration},
+};
+
+use crate::utils::{
+    config::{config_exists, load_config, DevflowConfig},
+    language::{detect_project_language, Language},
+    sanitize::{is_secret_key, redact},
+};
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// Version of the JSON protocol spoken with plugins, as `MAJOR.MINOR`. Minor
+/// bumps only add optional fields; a plugin replying with another major
+/// version is refused.
+pub const PROTOCOL_VERSION: &str = "1.0";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginRequest {
     pub command: String,
     pub payload: serde_json::Value,
+    #[serde(default)]
+    pub context: PluginContext,
+    /// Always [`PROTOCOL_VERSION`] from this devflow; missing means `1.0`.
+    #[serde(default)]
+    pub protocol_version: Option<String>,
+}
+
+/// Project state handed to plugins so they don't have to re-detect it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginContext {
+    #[serde(default)]
+    pub language: Language,
+    #[serde(default)]
+    pub root: PathBuf,
+    /// The loaded config (active profile applied) with secrets removed.
+    #[serde(default)]
+    pub config: Option<DevflowConfig>,
+}
+
+impl PluginContext {
+    pub fn collect(root: &Path, config_path: Option<&Path>, profile: Option<&str>) -> Result<Self> {
+        let config = if config_exists(root, config_path) {
+            Some(without_secrets(load_config(root, config_path, profile)?))
+        } else {
+            None
+        };
+        Ok(Self {
+            language: detect_project_language(root),
+            root: root.to_path_buf(),
+            config,
+        })
+    }
+}
+
+/// Drops profile overlays and schema defaults of secret-looking keys, and
+/// redacts inline credentials in commands.
+fn without_secrets(mut cfg: DevflowConfig) -> DevflowConfig {
+    cfg.profiles.clear();
+    for (key, spec) in cfg.env.iter_mut() {
+        if is_secret_key(key) {
+            if let Some((typ, _)) = spec.split_once(" default=") {
+                *spec = typ.to_string();
+            }
+        }
+    }
+    for svc in &mut cfg.services {
+        svc.command = redact(&svc.command);
+    }
+    for command in &mut cfg.start_commands {
+        *command = redact(command);
+    }
+    if let Some(command) = &mut cfg.test_command {
+        *command = redact(command);
+    }
+    cfg
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,59 +92,606 @@ pub struct PluginResponse {
     pub ok: bool,
     pub message: String,
     pub data: serde_json::Value,
+    /// The protocol version the plugin speaks; plugins that leave it out are
+    /// taken to speak `1.0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<String>,
+}
+
+/// Fails when `response` declares a `protocol_version` whose major version
+/// differs from [`PROTOCOL_VERSION`]'s, before the rest of it is interpreted.
+/// Responses that aren't JSON objects are left to the caller's parse to
+/// report.
+pub fn check_protocol_version(response: &[u8]) -> Result<()> {
+    #[derive(Deserialize)]
+    struct Probe {
+        #[serde(default)]
+        protocol_version: Option<serde_json::Value>,
+    }
+    let Ok(Probe {
+        protocol_version: Some(version),
+    }) = serde_json::from_slice::<Probe>(response)
+    else {
+        return Ok(());
+    };
+    let version = match version {
+        serde_json::Value::String(version) => version,
+        other => other.to_string(),
+    };
+    let Some(theirs) = protocol_major(&version) else {
+        bail!("plugin sent an invalid protocol_version {version:?}; expected MAJOR.MINOR, e.g. \"{PROTOCOL_VERSION}\"");
+    };
+    let ours = protocol_major(PROTOCOL_VERSION).expect("valid protocol version");
+    if theirs > ours {
+        bail!(
+            "plugin speaks protocol {version}, but this devflow only speaks {PROTOCOL_VERSION}; upgrade devflow or use a plugin release for protocol {ours}.x"
+        );
+    }
+    if theirs < ours {
+        bail!(
+            "plugin speaks protocol {version}, which this devflow ({PROTOCOL_VERSION}) no longer supports; update the plugin to protocol {ours}.x"
+        );
+    }
+    Ok(())
+}
+
+fn protocol_major(version: &str) -> Option<u64> {
+    let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+    minor.parse::<u64>().ok()?;
+    major.parse().ok()
+}
+
+/// File name prefix shared by every executable plugin.
+pub const PLUGIN_PREFIX: &str = "devflow-plugin-";
+
+/// Extra plugin directories, separated like `PATH`, searched before the
+/// config's `plugin_paths`.
+pub const PLUGIN_PATH_ENV: &str = "DEVFLOW_PLUGIN_PATH";
+
+/// How long an executable plugin may run when no `--timeout` is given.
+pub const DEFAULT_PLUGIN_TIMEOUT_SECS: u64 = 30;
+
+/// Stderr kept for error messages, from the end of the output.
+const STDERR_TAIL_BYTES: usize = 4096;
+
+/// Per-invocation settings for `dispatch`.
+#[derive(Debug, Clone)]
+pub struct DispatchOptions {
+    /// Directories a WASM plugin may read through `devflow.read_file`.
+    pub allow_read: Vec<PathBuf>,
+    /// Deadline for an executable plugin; it is killed when exceeded.
+    pub timeout: Duration,
+    /// Project context included in the request.
+    pub context: PluginContext,
+    /// Extra variables set in an executable plugin's environment.
+    pub env: Vec<(String, String)>,
+    /// Directories searched for executable plugins before `PATH`, from
+    /// [`plugin_search_path`].
+    pub plugin_paths: Vec<PathBuf>,
+    /// Project root; its `plugins/` directory is searched last.
+    pub root: PathBuf,
+}
+
+impl Default for DispatchOptions {
+    fn default() -> Self {
+        Self {
+            allow_read: Vec::new(),
+            timeout: Duration::from_secs(DEFAULT_PLUGIN_TIMEOUT_SECS),
+            context: PluginContext::default(),
+            env: Vec::new(),
+            plugin_paths: Vec::new(),
+            root: PathBuf::new(),
+        }
+    }
+}
+
+/// Directories searched for executable plugins before `PATH`:
+/// `DEVFLOW_PLUGIN_PATH` first, then the config's `plugin_paths`, relative to
+/// `root`. A leading `~` in either is the home directory.
+pub fn plugin_search_path(root: &Path, cfg: Option<&DevflowConfig>) -> Vec<PathBuf> {
+    let from_env = env::var_os(PLUGIN_PATH_ENV)
+        .map(|value| env::split_paths(&value).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let from_config = cfg.map(|cfg| cfg.plugin_paths.clone()).unwrap_or_default();
+    from_env
+        .into_iter()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| expand_home(&dir))
+        .chain(from_config.iter().map(|dir| root.join(expand_home(dir))))
+        .collect()
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    let Ok(rest) = path.strip_prefix("~") else {
+        return path.to_path_buf();
+    };
+    match env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+        Some(home) => PathBuf::from(home).join(rest),
+        None => path.to_path_buf(),
+    }
 }
 
-pub async fn dispatch(name: &str, payload: serde_json::Value) -> Result<PluginResponse> {
+pub async fn dispatch(
+    name: &str,
+    payload: serde_json::Value,
+    opts: &DispatchOptions,
+) -> Result<PluginResponse> {
     let req = PluginRequest {
         command: name.to_string(),
         payload,
+        context: opts.context.clone(),
+        protocol_version: Some(PROTOCOL_VERSION.to_string()),
     };
 
     if name.ends_with(".wasm") {
+        #[cfg(feature = "wasm")]
+        {
+            let path = resolve_wasm_plugin(name, &opts.root)?;
+            let allow_read = opts.allow_read.clone();
+            return tokio::task::spawn_blocking(move || {
+                wasm::run(&path, &req, &allow_read, wasm::WasmLimits::default())
+            })
+            .await?;
+        }
+        #[cfg(not(feature = "wasm"))]
         bail!("WASM plugin runtime not enabled in this build");
     }
 
-    let executable = resolve_executable_plugin(name)?;
-    let mut child = Command::new(executable)
+    let stdout = run_executable(name, &req, opts, Output::Buffered).await?;
+    check_protocol_version(&stdout)?;
+    let resp: PluginResponse =
+        serde_json::from_slice(&stdout).context("plugin produced invalid JSON")?;
+    Ok(resp)
+}
+
+/// Like `dispatch`, but reads the plugin's stdout as newline-delimited
+/// `PluginResponse` objects and hands each to `on_response` as it arrives.
+/// WASM plugins produce a single response.
+pub async fn dispatch_stream(
+    name: &str,
+    payload: serde_json::Value,
+    opts: &DispatchOptions,
+    on_response: &mut dyn FnMut(PluginResponse) -> Result<()>,
+) -> Result<()> {
+    if name.ends_with(".wasm") {
+        return on_response(dispatch(name, payload, opts).await?);
+    }
+
+    let req = PluginRequest {
+        command: name.to_string(),
+        payload,
+        context: opts.context.clone(),
+        protocol_version: Some(PROTOCOL_VERSION.to_string()),
+    };
+    run_executable(name, &req, opts, Output::Lines(on_response)).await?;
+    Ok(())
+}
+
+/// Longest stdout line accepted from a streaming plugin.
+pub const MAX_STREAM_LINE_BYTES: usize = 1024 * 1024;
+
+enum Output<'a> {
+    /// Collect all of stdout and return it.
+    Buffered,
+    /// Parse each stdout line as a response as soon as it is complete.
+    Lines(&'a mut dyn FnMut(PluginResponse) -> Result<()>),
+}
+
+/// Runs an executable plugin with `req` on stdin, enforcing `opts.timeout`.
+/// Returns stdout for `Output::Buffered` (empty for `Output::Lines`).
+async fn run_executable(
+    name: &str,
+    req: &PluginRequest,
+    opts: &DispatchOptions,
+    output: Output<'_>,
+) -> Result<Vec<u8>> {
+    let executable = resolve_executable_plugin(name, &opts.root, &opts.plugin_paths)?;
+    tracing::debug!("running plugin {} from {}", name, executable.display());
+    let mut child = Command::new(&executable)
+        .envs(opts.env.iter().map(|(k, v)| (k, v)))
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
         .spawn()
         .context("failed to launch plugin")?;
 
-    if let Some(stdin) = child.stdin.take() {
-        let bytes = serde_json::to_vec(&req)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let bytes = serde_json::to_vec(req)?;
         tokio::spawn(async move {
-            use tokio::io::AsyncWriteExt;
-            let mut s = stdin;
-            let _ = s.write_all(&bytes).await;
+            let _ = stdin.write_all(&bytes).await;
         });
     }
+    let stderr = tokio::spawn(read_all(child.stderr.take()));
+    let stdout = child
+        .stdout
+        .take()
+        .context("plugin stdout was not captured")?;
 
-    let output = child.wait_with_output().await?;
-    if !output.status.success() {
-        bail!("plugin exited with status {}", output.status);
+    let work = async {
+        let collected = match output {
+            Output::Buffered => read_all(Some(stdout)).await,
+            Output::Lines(on_response) => {
+                read_responses(stdout, on_response).await?;
+                Vec::new()
+            }
+        };
+        Ok::<_, anyhow::Error>((child.wait().await?, collected))
+    };
+    let (status, collected) = match timeout(opts.timeout, work).await {
+        Ok(Ok(done)) => done,
+        Ok(Err(err)) => {
+            let _ = child.kill().await;
+            return Err(err);
+        }
+        Err(_) => {
+            // `kill` also waits for the child, so it is reaped here.
+            child.kill().await?;
+            bail!(
+                "plugin {} timed out after {}s and was killed",
+                executable.display(),
+                opts.timeout.as_secs_f64()
+            );
+        }
+    };
+    let stderr = stderr.await?;
+
+    if !status.success() {
+        let stderr = stderr_tail(&stderr);
+        if stderr.is_empty() {
+            bail!("plugin exited with status {}", status);
+        }
+        bail!("plugin exited with status {}: {}", status, stderr);
     }
+    // Successful plugins may still log to stderr; pass that through.
+    std::io::Write::write_all(&mut std::io::stderr(), &stderr)?;
+    Ok(collected)
+}
 
-    let resp: PluginResponse =
-        serde_json::from_slice(&output.stdout).context("plugin produced invalid JSON")?;
-    Ok(resp)
+/// Reads newline-delimited responses, rejecting lines longer than
+/// `MAX_STREAM_LINE_BYTES` instead of buffering them. Blank lines are skipped.
+async fn read_responses<R: AsyncRead + Unpin>(
+    reader: R,
+    on_response: &mut dyn FnMut(PluginResponse) -> Result<()>,
+) -> Result<()> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    for line_no in 1.. {
+        line.clear();
+        let limit = MAX_STREAM_LINE_BYTES as u64 + 1;
+        if (&mut reader)
+            .take(limit)
+            .read_until(b'\n', &mut line)
+            .await?
+            == 0
+        {
+            break;
+        }
+        if line.len() > MAX_STREAM_LINE_BYTES {
+            bail!(
+                "plugin output line {} exceeds {} bytes",
+                line_no,
+                MAX_STREAM_LINE_BYTES
+            );
+        }
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        check_protocol_version(&line)?;
+        let resp: PluginResponse = serde_json::from_slice(&line)
+            .with_context(|| format!("plugin produced invalid JSON on line {line_no}"))?;
+        on_response(resp)?;
+    }
+    Ok(())
+}
+
+async fn read_all<R: AsyncRead + Unpin>(reader: Option<R>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if let Some(mut reader) = reader {
+        let _ = reader.read_to_end(&mut buf).await;
+    }
+    buf
 }
 
-fn resolve_executable_plugin(name: &str) -> Result<PathBuf> {
-    let prefixed = if name.starts_with("devflow-plugin-") {
+/// The trimmed end of a plugin's stderr, for error messages.
+fn stderr_tail(stderr: &[u8]) -> String {
+    let start = stderr.len().saturating_sub(STDERR_TAIL_BYTES);
+    String::from_utf8_lossy(&stderr[start..]).trim().to_string()
+}
+
+/// Finds `devflow-plugin-<name>` in `plugin_paths`, then on `PATH`, then in
+/// `<root>/plugins/`.
+fn resolve_executable_plugin(name: &str, root: &Path, plugin_paths: &[PathBuf]) -> Result<PathBuf> {
+    validate_plugin_name(name)?;
+    let prefixed = if name.starts_with(PLUGIN_PREFIX) {
         name.to_string()
     } else {
-        format!("devflow-plugin-{name}")
+        format!("{PLUGIN_PREFIX}{name}")
     };
 
+    for dir in plugin_paths {
+        let candidate = dir.join(&prefixed);
+        if candidate.is_file() && is_executable(&candidate) {
+            return Ok(candidate);
+        }
+        tracing::debug!("no plugin {} in {}", prefixed, dir.display());
+    }
+
     if let Ok(path) = which::which(&prefixed) {
         return Ok(path);
     }
 
-    let local = PathBuf::from("plugins").join(&prefixed);
+    let local = root.join("plugins").join(&prefixed);
     if local.exists() {
         return Ok(local);
     }
 
     bail!("plugin not found: {prefixed}")
 }
+
+/// Rejects names that aren't plain `[A-Za-z0-9_-]` words, so a name can never
+/// be joined onto a directory and land outside it.
+fn validate_plugin_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        bail!("plugin name is empty");
+    }
+    if name.contains(['/', '\\']) || name.contains("..") || Path::new(name).is_absolute() {
+        bail!("invalid plugin name `{name}`: plugin names can't contain path separators or `..`");
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        bail!("invalid plugin name `{name}`: {c:?} is not allowed; use only letters, digits, `-` and `_`");
+    }
+    Ok(())
+}
+
+/// Self-description a plugin prints when run as `devflow-plugin-<name> --manifest`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub commands: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginSource {
+    /// A directory from `DEVFLOW_PLUGIN_PATH` or `plugin_paths`.
+    PluginPath,
+    Path,
+    Local,
+}
+
+/// A plugin executable found by `discover_plugins`.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledPlugin {
+    /// Name to pass to `devflow plugin`, i.e. the file name without the prefix.
+    pub name: String,
+    pub path: PathBuf,
+    pub source: PluginSource,
+    /// The plugin that `dispatch` would run instead of this one, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shadowed_by: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<PluginManifest>,
+}
+
+/// Lists `devflow-plugin-*` executables in `plugin_paths`, on `PATH` and in
+/// `<root>/plugins/`, in resolution order. Entries hidden by an earlier one
+/// with the same name are marked with `shadowed_by`.
+pub fn discover_plugins(root: &Path, plugin_paths: &[PathBuf]) -> Vec<InstalledPlugin> {
+    let mut found = Vec::new();
+    for dir in plugin_paths {
+        found.extend(scan_plugin_dir(dir, PluginSource::PluginPath));
+    }
+    if let Some(path) = env::var_os("PATH") {
+        for dir in env::split_paths(&path) {
+            found.extend(scan_plugin_dir(&dir, PluginSource::Path));
+        }
+    }
+    found.extend(scan_plugin_dir(&root.join("plugins"), PluginSource::Local));
+
+    let mut winners: HashMap<String, PathBuf> = HashMap::new();
+    for plugin in &mut found {
+        match winners.get(&plugin.name) {
+            Some(winner) => plugin.shadowed_by = Some(winner.clone()),
+            None => {
+                winners.insert(plugin.name.clone(), plugin.path.clone());
+            }
+        }
+    }
+    found
+}
+
+fn scan_plugin_dir(dir: &Path, source: PluginSource) -> Vec<InstalledPlugin> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut plugins = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let name = file_name.strip_prefix(PLUGIN_PREFIX)?.to_string();
+            let path = entry.path();
+            // `which` only resolves executables on PATH; local plugins are
+            // listed as long as they are files.
+            let usable = path.is_file() && (source == PluginSource::Local || is_executable(&path));
+            usable.then_some(InstalledPlugin {
+                name,
+                path,
+                source,
+                shadowed_by: None,
+                manifest: None,
+            })
+        })
+        .collect::<Vec<_>>();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+/// Runs `path --manifest` and parses its output. Plugins that don't support
+/// the flag, fail, or take longer than a couple of seconds yield `None`.
+pub async fn read_manifest(path: &Path) -> Option<PluginManifest> {
+    let child = Command::new(path)
+        .arg("--manifest")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .ok()?;
+    let output = timeout(Duration::from_secs(2), child.wait_with_output())
+        .await
+        .ok()?
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// Resolves a `.wasm` plugin: the name as a path first, then `<root>/plugins/`.
+#[cfg(feature = "wasm")]
+fn resolve_wasm_plugin(name: &str, root: &Path) -> Result<PathBuf> {
+    let direct = PathBuf::from(name);
+    if direct.exists() {
+        return Ok(direct);
+    }
+    let local = root.join("plugins").join(name);
+    if local.exists() {
+        return Ok(local);
+    }
+    bail!("plugin not found: {name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_config_drops_secrets() {
+        let cfg: DevflowConfig = serde_yaml::from_str(
+            "env:\n  API_TOKEN: string default=abc123\n  PORT: int default=3000\n\
+             test_command: curl -H token=abc123 localhost\n\
+             profiles:\n  ci:\n    desired_ports: [9000]\n",
+        )
+        .expect("parse");
+        let cleaned = without_secrets(cfg);
+        assert_eq!(cleaned.env["API_TOKEN"], "string");
+        assert_eq!(cleaned.env["PORT"], "int default=3000");
+        assert!(!cleaned
+            .test_command
+            .expect("test command")
+            .contains("abc123"));
+        assert!(cleaned.profiles.is_empty());
+    }
+
+    #[test]
+    fn plugin_path_is_searched_before_path() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let (first, second) = (td.path().join("first"), td.path().join("second"));
+        for dir in [&first, &second] {
+            fs::create_dir(dir).expect("mkdir");
+            let plugin = dir.join("devflow-plugin-sh");
+            fs::write(&plugin, "#!/bin/sh\n").expect("write");
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).expect("chmod");
+            }
+        }
+        let search = [td.path().join("missing"), first.clone(), second];
+        assert_eq!(
+            resolve_executable_plugin("sh", td.path(), &search).expect("resolve"),
+            first.join("devflow-plugin-sh")
+        );
+        let found = discover_plugins(td.path(), &search);
+        assert_eq!(found[0].source, PluginSource::PluginPath);
+        assert_eq!(found[1].shadowed_by, Some(first.join("devflow-plugin-sh")));
+    }
+
+    #[test]
+    fn traversal_and_odd_plugin_names_are_refused() {
+        for name in [
+            "../../bin/evil",
+            "..",
+            "/usr/bin/evil",
+            "sub/dir",
+            "back\\slash",
+            "C:evil",
+            "infra-check.py",
+            "",
+        ] {
+            let err = resolve_executable_plugin(name, Path::new(""), &[]).expect_err(name);
+            assert!(err.to_string().contains("plugin name"), "{name}: {err}");
+        }
+        let err = validate_plugin_name("../x").unwrap_err().to_string();
+        assert!(err.contains("path separators"), "{err}");
+
+        for name in ["lint", "infra_check", "devflow-plugin-Deploy2"] {
+            validate_plugin_name(name).expect(name);
+        }
+    }
+
+    #[test]
+    fn config_plugin_paths_resolve_against_the_root() {
+        let cfg = DevflowConfig {
+            plugin_paths: vec!["tools/plugins".into(), "/opt/devflow".into()],
+            ..DevflowConfig::default()
+        };
+        let search = plugin_search_path(Path::new("/work/app"), Some(&cfg));
+        assert!(search.ends_with(&[
+            PathBuf::from("/work/app/tools/plugins"),
+            PathBuf::from("/opt/devflow")
+        ]));
+    }
+
+    #[test]
+    fn request_without_context_still_parses() {
+        let req: PluginRequest =
+            serde_json::from_str(r#"{"command":"x","payload":{}}"#).expect("parse");
+        assert_eq!(req.context.language, Language::Unknown);
+        assert!(req.context.config.is_none());
+        assert!(req.protocol_version.is_none());
+    }
+
+    #[test]
+    fn responses_with_another_major_protocol_are_refused() {
+        for ok in [
+            r#"{"ok":true,"message":"","data":null}"#,
+            r#"{"ok":true,"message":"","data":null,"protocol_version":"1.0"}"#,
+            r#"{"ok":true,"message":"","data":null,"protocol_version":"1.7"}"#,
+            r#"{"ok":true,"message":"","data":null,"protocol_version":1}"#,
+            "not json",
+        ] {
+            check_protocol_version(ok.as_bytes()).expect(ok);
+        }
+
+        let newer = check_protocol_version(br#"{"protocol_version":"2.0","result":[]}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(newer.contains("upgrade devflow"), "{newer}");
+        let older = check_protocol_version(br#"{"protocol_version":"0.9"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(older.contains("update the plugin"), "{older}");
+        assert!(check_protocol_version(br#"{"protocol_version":"one"}"#).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Sandboxed WebAssembly plugin host, built with the `wasm` feature.
+//!
+//! A plugin is a core wasm module that exports `memory` and a `run` function
+//! taking no arguments. It talks to devflow through these imports from the
+//! `devflow` module:
+//!
+//! - `request_len() -> i32`: byte length of the `PluginRequest` JSON
+//! - `request_read(ptr: i32)`: copies the request JSON into memory at `ptr`
+//! - `response_write(ptr: i32, len: i32)`: hands back the `PluginResponse` JSON
+//! - `read_file(path_ptr: i32, path_len: i32, out_ptr: i32, out_cap: i32) -> i32`:
+//!   copies up to `out_cap` bytes of a file under a directory granted with
+//!   `--allow-read` and returns the file's full length, or -1 if access is
+//!   denied or the read fails
+//!
+//! Nothing else is linked (in particular no WASI), so a module has no
+//! filesystem, network, clock or environment access beyond the above.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use wasmtime::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    Trap,
+};
+
+use super::{check_protocol_version, PluginRequest, PluginResponse};
+
+/// Linear memory a plugin may allocate when no limit is given.
+pub const DEFAULT_MAX_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+/// Fuel (roughly, wasm instructions) a plugin may burn when no limit is given.
+pub const DEFAULT_FUEL: u64 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WasmLimits {
+    pub max_memory_bytes: usize,
+    pub fuel: u64,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            max_memory_bytes: DEFAULT_MAX_MEMORY_BYTES,
+            fuel: DEFAULT_FUEL,
+        }
+    }
+}
+
+struct HostState {
+    request: Vec<u8>,
+    response: Option<Vec<u8>>,
+    allow_read: Vec<PathBuf>,
+    limits: StoreLimits,
+}
+
+/// Runs the wasm module at `path` with `request`, granting read access to the
+/// `allow_read` directories only.
+pub fn run(
+    path: &Path,
+    request: &PluginRequest,
+    allow_read: &[PathBuf],
+    limits: WasmLimits,
+) -> Result<PluginResponse> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::from_file(&engine, path)
+        .map_err(anyhow::Error::from)
+        .with_context(|| format!("failed to load wasm plugin {}", path.display()))?;
+
+    let allow_read = allow_read
+        .iter()
+        .map(|dir| {
+            dir.canonicalize()
+                .with_context(|| format!("cannot grant read access to {}", dir.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let state = HostState {
+        request: serde_json::to_vec(request)?,
+        response: None,
+        allow_read,
+        limits: StoreLimitsBuilder::new()
+            .memory_size(limits.max_memory_bytes)
+            .instances(1)
+            .build(),
+    };
+    let mut store = Store::new(&engine, state);
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(limits.fuel)?;
+
+    let instance = host_linker(&engine)?
+        .instantiate(&mut store, &module)
+        .map_err(anyhow::Error::from)
+        .context("failed to instantiate wasm plugin (only devflow.* imports are available)")?;
+    let entry = instance
+        .get_typed_func::<(), ()>(&mut store, "run")
+        .map_err(anyhow::Error::from)
+        .context("wasm plugin must export `run: () -> ()`")?;
+
+    if let Err(err) = entry.call(&mut store, ()) {
+        if err.downcast_ref::<Trap>() == Some(&Trap::OutOfFuel) {
+            bail!(
+                "wasm plugin exceeded its instruction limit ({} fuel)",
+                limits.fuel
+            );
+        }
+        return Err(anyhow::Error::from(err).context("wasm plugin trapped"));
+    }
+
+    let Some(response) = store.into_data().response else {
+        bail!("wasm plugin returned without calling devflow.response_write");
+    };
+    check_protocol_version(&response)?;
+    serde_json::from_slice(&response).context("plugin produced invalid JSON")
+}
+
+fn host_linker(engine: &Engine) -> Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(
+        "devflow",
+        "request_len",
+        |caller: Caller<'_, HostState>| -> i32 { caller.data().request.len() as i32 },
+    )?;
+    linker.func_wrap(
+        "devflow",
+        "request_read",
+        |mut caller: Caller<'_, HostState>, ptr: i32| -> wasmtime::Result<()> {
+            let memory = guest_memory(&mut caller)?;
+            let request = std::mem::take(&mut caller.data_mut().request);
+            let written = memory.write(&mut caller, ptr as u32 as usize, &request);
+            caller.data_mut().request = request;
+            Ok(written?)
+        },
+    )?;
+    linker.func_wrap(
+        "devflow",
+        "response_write",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+            let memory = guest_memory(&mut caller)?;
+            let mut response = vec![0; len as u32 as usize];
+            memory.read(&caller, ptr as u32 as usize, &mut response)?;
+            caller.data_mut().response = Some(response);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "devflow",
+        "read_file",
+        |mut caller: Caller<'_, HostState>,
+         path_ptr: i32,
+         path_len: i32,
+         out_ptr: i32,
+         out_cap: i32|
+         -> wasmtime::Result<i32> {
+            let memory = guest_memory(&mut caller)?;
+            let mut raw = vec![0; path_len as u32 as usize];
+            memory.read(&caller, path_ptr as u32 as usize, &mut raw)?;
+            let Some(contents) = read_granted(&caller.data().allow_read, &raw) else {
+                return Ok(-1);
+            };
+            let n = contents.len().min(out_cap.max(0) as usize);
+            memory.write(&mut caller, out_ptr as u32 as usize, &contents[..n])?;
+            Ok(i32::try_from(contents.len()).unwrap_or(i32::MAX))
+        },
+    )?;
+    Ok(linker)
+}
+
+fn guest_memory(caller: &mut Caller<'_, HostState>) -> wasmtime::Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => wasmtime::bail!("wasm plugin must export its `memory`"),
+    }
+}
+
+/// Reads the file at `raw_path` if it resolves inside one of `allowed`.
+fn read_granted(allowed: &[PathBuf], raw_path: &[u8]) -> Option<Vec<u8>> {
+    let path = Path::new(std::str::from_utf8(raw_path).ok()?)
+        .canonicalize()
+        .ok()?;
+    if !allowed.iter().any(|dir| path.starts_with(dir)) {
+        return None;
+    }
+    fs::read(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> PluginRequest {
+        PluginRequest {
+            command: "test".into(),
+            payload: serde_json::json!({}),
+            context: Default::default(),
+            protocol_version: Some(crate::plugin::PROTOCOL_VERSION.into()),
+        }
+    }
+
+    fn write_module(dir: &Path, wat: &str) -> PathBuf {
+        let path = dir.join("plugin.wat");
+        fs::write(&path, wat).expect("write module");
+        path
+    }
+
+    #[test]
+    fn stops_runaway_plugin_at_fuel_limit() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let path = write_module(
+            td.path(),
+            r#"(module
+                 (memory (export "memory") 1)
+                 (func (export "run") (loop (br 0))))"#,
+        );
+        let limits = WasmLimits {
+            fuel: 10_000,
+            ..WasmLimits::default()
+        };
+        let err = run(&path, &request(), &[], limits).expect_err("out of fuel");
+        assert!(err.to_string().contains("instruction limit"), "{err:#}");
+    }
+
+    #[test]
+    fn rejects_memory_above_limit() {
+        let td = tempfile::tempdir().expect("tempdir");
+        // 32 pages = 2 MiB, above the 1 MiB limit below.
+        let path = write_module(
+            td.path(),
+            r#"(module
+                 (memory (export "memory") 32)
+                 (func (export "run")))"#,
+        );
+        let limits = WasmLimits {
+            max_memory_bytes: 1024 * 1024,
+            ..WasmLimits::default()
+        };
+        assert!(run(&path, &request(), &[], limits).is_err());
+    }
+
+    #[test]
+    fn rejects_wasi_imports() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let path = write_module(
+            td.path(),
+            r#"(module
+                 (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+                 (memory (export "memory") 1)
+                 (func (export "run")))"#,
+        );
+        let err = run(&path, &request(), &[], WasmLimits::default()).expect_err("no wasi");
+        assert!(
+            err.to_string().contains("only devflow.* imports"),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn read_file_is_limited_to_granted_dirs() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let granted = td.path().join("granted");
+        fs::create_dir(&granted).expect("mkdir");
+        fs::write(granted.join("a.txt"), "hi").expect("write");
+        fs::write(td.path().join("secret.txt"), "no").expect("write");
+        let allowed = vec![granted.canonicalize().expect("canonicalize")];
+
+        let inside = granted.join("a.txt");
+        assert_eq!(
+            read_granted(&allowed, inside.to_str().expect("utf8").as_bytes()),
+            Some(b"hi".to_vec())
+        );
+        let escape = granted.join("../secret.txt");
+        assert_eq!(
+            read_granted(&allowed, escape.to_str().expect("utf8").as_bytes()),
+            None
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use clap::ValueEnum;
+use std::{env, io::IsTerminal};
+
+/// Whether human-readable output may use color, from `--color`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal, unless `NO_COLOR` is set or
+    /// `TERM=dumb`
+    #[default]
+    Auto,
+    /// Always color, even into files and pipes
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output written to `stream` should be colored.
+    pub fn enabled(self, stream: &impl IsTerminal) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => stream.is_terminal() && auto_allows_color(),
+        }
+    }
+}
+
+/// <https://no-color.org>: a non-empty `NO_COLOR` turns color off, and a
+/// dumb terminal can't show it.
+fn auto_allows_color() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let dumb = env::var_os("TERM").is_some_and(|term| term == "dumb");
+    !no_color && !dumb
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_choices_ignore_the_stream() {
+        let file = tempfile::tempfile().expect("tempfile");
+        assert!(ColorChoice::Always.enabled(&file));
+        assert!(!ColorChoice::Never.enabled(&file));
+        assert!(!ColorChoice::Auto.enabled(&file));
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+use super::ports::Proto;
+
+/// Compose file names, in the order `docker compose` looks for them.
+pub const COMPOSE_FILES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yml",
+    "docker-compose.yaml",
+];
+
+/// Widest published port range expanded into individual ports.
+const MAX_PORT_RANGE: u16 = 1000;
+
+/// A service declared in a compose file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComposeService {
+    pub name: String,
+    /// `None` for services that are only built from a Dockerfile.
+    pub image: Option<String>,
+    /// Ports published on the host. Container-only ports, which Docker maps
+    /// to a random host port, are left out.
+    pub ports: Vec<PublishedPort>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PublishedPort {
+    pub host: u16,
+    pub protocol: Proto,
+}
+
+pub fn find_compose_file(root: &Path) -> Option<PathBuf> {
+    COMPOSE_FILES
+        .iter()
+        .map(|name| root.join(name))
+        .find(|path| path.is_file())
+}
+
+pub fn load_compose(path: &Path) -> Result<Vec<ComposeService>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    parse_compose(&content).with_context(|| format!("invalid compose file {}", path.display()))
+}
+
+/// Services in file order. Reads the compose spec and the `version: "2"`/`"3"`
+/// files that put services under `services:`, as well as version 1 files
+/// that list them at the top level.
+pub fn parse_compose(content: &str) -> Result<Vec<ComposeService>> {
+    let doc: Value = serde_yaml::from_str(content)?;
+    let Some(top) = doc.as_mapping() else {
+        bail!("expected a mapping at the top level");
+    };
+    let services = match top.get("services") {
+        Some(Value::Mapping(services)) => services.clone(),
+        Some(Value::Null) => Mapping::new(),
+        Some(_) => bail!("`services` must be a mapping"),
+        // Version 1: every top-level entry with an image or build is a service.
+        None => top
+            .iter()
+            .filter(|(_, def)| def.get("image").is_some() || def.get("build").is_some())
+            .map(|(name, def)| (name.clone(), def.clone()))
+            .collect(),
+    };
+
+    services
+        .iter()
+        .map(|(name, def)| {
+            let name = name
+                .as_str()
+                .context("service names must be strings")?
+                .to_string();
+            let ports = match def.get("ports") {
+                Some(Value::Sequence(ports)) => ports.iter().flat_map(published_ports).collect(),
+                _ => Vec::new(),
+            };
+            Ok(ComposeService {
+                image: def.get("image").and_then(Value::as_str).map(interpolate),
+                name,
+                ports,
+            })
+        })
+        .collect()
+}
+
+/// Host ports of one `ports:` entry, short (`"127.0.0.1:8080:80/udp"`) or
+/// long (`{published: 8080, target: 80}`) syntax.
+fn published_ports(entry: &Value) -> Vec<PublishedPort> {
+    let (published, protocol) = match entry {
+        Value::String(short) => {
+            let short = interpolate(short);
+            let (mapping, protocol) = match short.rsplit_once('/') {
+                Some((mapping, protocol)) => (mapping.to_string(), protocol.to_string()),
+                None => (short, "tcp".to_string()),
+            };
+            // HOST:CONTAINER or IP:HOST:CONTAINER, where IP may be `[::1]`.
+            let Some((rest, _container)) = mapping.rsplit_once(':') else {
+                return Vec::new();
+            };
+            let host = rest.rsplit_once(':').map_or(rest, |(_, host)| host);
+            (host.to_string(), protocol)
+        }
+        Value::Mapping(long) => {
+            let published = match long.get("published") {
+                Some(Value::Number(n)) => n.to_string(),
+                Some(Value::String(s)) => interpolate(s),
+                _ => return Vec::new(),
+            };
+            let protocol = long
+                .get("protocol")
+                .and_then(Value::as_str)
+                .unwrap_or("tcp")
+                .to_string();
+            (published, protocol)
+        }
+        // A bare number is a container port.
+        _ => return Vec::new(),
+    };
+
+    let protocol = match protocol.to_ascii_lowercase().as_str() {
+        "tcp" => Proto::Tcp,
+        "udp" => Proto::Udp,
+        _ => return Vec::new(),
+    };
+    let (start, end) = published
+        .split_once('-')
+        .unwrap_or((&published, &published));
+    let (Ok(start), Ok(end)) = (start.trim().parse::<u16>(), end.trim().parse::<u16>()) else {
+        return Vec::new();
+    };
+    if start == 0 || end < start || end - start >= MAX_PORT_RANGE {
+        return Vec::new();
+    }
+    (start..=end)
+        .map(|host| PublishedPort { host, protocol })
+        .collect()
+}
+
+/// Expands `${VAR}`, `${VAR:-default}` and `${VAR-default}` from the process
+/// environment, as compose does; unset variables without a default become
+/// empty. `$$` is a literal `$`.
+fn interpolate(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(at) = rest.find('$') {
+        out.push_str(&rest[..at]);
+        rest = &rest[at + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+            continue;
+        }
+        let Some(body) = rest.strip_prefix('{') else {
+            out.push('$');
+            continue;
+        };
+        let Some(close) = body.find('}') else {
+            out.push('$');
+            continue;
+        };
+        let expr = &body[..close];
+        rest = &body[close + 1..];
+        let (name, default, empty_counts) = if let Some((name, default)) = expr.split_once(":-") {
+            (name, default, true)
+        } else if let Some((name, default)) = expr.split_once('-') {
+            (name, default, false)
+        } else {
+            (expr, "", false)
+        };
+        match std::env::var(name) {
+            Ok(v) if !(empty_counts && v.is_empty()) => out.push_str(&v),
+            _ => out.push_str(default),
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(service: &ComposeService) -> Vec<(u16, Proto)> {
+        service.ports.iter().map(|p| (p.host, p.protocol)).collect()
+    }
+
+    #[test]
+    fn reads_services_images_and_published_ports() {
+        let services = parse_compose(
+            r#"
+services:
+  web:
+    build: .
+    ports:
+      - "8080:80"
+      - "127.0.0.1:9229:9229"
+      - "[::1]:6001:6001/udp"
+      - "3000"
+      - 4000
+      - "${DEVFLOW_TEST_UNSET_PORT:-5050}:5000"
+  db:
+    image: postgres:16
+    ports:
+      - target: 5432
+        published: "5432"
+      - target: 53
+        published: 1053
+        protocol: udp
+      - target: 80
+  cache:
+    image: redis
+    ports:
+      - "7000-7002:7000-7002"
+"#,
+        )
+        .expect("parse");
+
+        let names: Vec<_> = services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["web", "db", "cache"]);
+        assert_eq!(services[0].image, None);
+        assert_eq!(services[1].image.as_deref(), Some("postgres:16"));
+        assert_eq!(
+            ports(&services[0]),
+            [
+                (8080, Proto::Tcp),
+                (9229, Proto::Tcp),
+                (6001, Proto::Udp),
+                (5050, Proto::Tcp)
+            ]
+        );
+        assert_eq!(
+            ports(&services[1]),
+            [(5432, Proto::Tcp), (1053, Proto::Udp)]
+        );
+        assert_eq!(
+            ports(&services[2]),
+            [(7000, Proto::Tcp), (7001, Proto::Tcp), (7002, Proto::Tcp)]
+        );
+    }
+
+    #[test]
+    fn reads_legacy_layouts() {
+        let v2 = parse_compose(
+            "version: '2'\nservices:\n  db:\n    image: mysql\n    ports: ['3306:3306']\n",
+        )
+        .expect("v2");
+        assert_eq!(v2[0].name, "db");
+        assert_eq!(ports(&v2[0]), [(3306, Proto::Tcp)]);
+
+        let v1 = parse_compose("web:\n  build: .\n  ports: ['80:80']\nredis:\n  image: redis\n")
+            .expect("v1");
+        let names: Vec<_> = v1.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["web", "redis"]);
+
+        assert!(parse_compose("services:\n").expect("empty").is_empty());
+        assert!(parse_compose("- not a mapping\n").is_err());
+    }
+
+    #[test]
+    fn compose_yaml_takes_precedence() {
+        let td = tempfile::tempdir().expect("tempdir");
+        assert!(find_compose_file(td.path()).is_none());
+        std::fs::write(td.path().join("docker-compose.yml"), "services: {}\n").expect("write");
+        std::fs::write(td.path().join("compose.yaml"), "services: {}\n").expect("write");
+        assert_eq!(
+            find_compose_file(td.path()),
+            Some(td.path().join("compose.yaml"))
+        );
+    }
+}
@@ -1,52 +1,676 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::Path};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+use super::language::{detect_node_package_manager, Language};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub const ALL: [ConfigFormat; 3] = [ConfigFormat::Yaml, ConfigFormat::Toml, ConfigFormat::Json];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => ".devflow.yaml",
+            ConfigFormat::Toml => ".devflow.toml",
+            ConfigFormat::Json => ".devflow.json",
+        }
+    }
+
+    /// The format of a config at `path`, by extension (`.yaml`/`.yml`,
+    /// `.toml` or `.json`).
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "toml" => Some(ConfigFormat::Toml),
+            "json" => Some(ConfigFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// devflow project configuration (`.devflow.yaml`, `.devflow.toml` or
+/// `.devflow.json`).
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct DevflowConfig {
+    /// Expected env vars and their types, e.g. `PORT: int` or
+    /// `MODE: enum:dev|prod`; a trailing `?` makes one optional and
+    /// `default=VALUE` gives `env fix` a value to write.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Services `devflow up` starts in dependency order.
+    #[serde(default)]
+    pub services: Vec<ServiceDef>,
+    /// Shell commands `devflow up` runs to completion before starting services.
+    #[serde(default)]
+    pub start_commands: Vec<String>,
+    /// Command `devflow watch` runs on change, instead of the language default.
+    #[serde(default)]
+    pub test_command: Option<String>,
+    /// Glob patterns of paths `devflow watch` ignores.
+    #[serde(default)]
+    pub ignore_globs: Vec<String>,
+    /// Ports the project needs free; `up` reports the ones in use.
+    #[serde(default)]
+    pub desired_ports: Vec<u16>,
+    /// Notify on failed `devflow watch` test runs, as if `--notify` were given.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub watch_notify: bool,
+    /// Command `devflow watch` keeps running and restarts on change, as if
+    /// `--restart` were given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_restart: Option<String>,
+    /// Directories `devflow watch` watches instead of the whole project,
+    /// relative to the project root, as if given with `--path`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_paths: Vec<PathBuf>,
+    /// Log `devflow logs` reads when no `--file` is given, relative to the
+    /// project root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<String>,
+    /// Extra regexes for secrets `logs` and `snap` redact, on top of the
+    /// built-in ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact_patterns: Vec<String>,
+    /// Directories searched for `devflow-plugin-*` executables before
+    /// `PATH`, relative to the project root; `~` is the home directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugin_paths: Vec<PathBuf>,
+    /// Named overrides selected with `--profile NAME`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, ProfileOverlay>,
+}
+
+/// Per-profile overrides layered over the base config by `--profile`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileOverlay {
+    /// Env schema entries merged over the base `env`.
     #[serde(default)]
     pub env: HashMap<String, String>,
+    /// Services replacing base services of the same name, or added.
     #[serde(default)]
     pub services: Vec<ServiceDef>,
+    /// Start commands appended after the base ones.
     #[serde(default)]
     pub start_commands: Vec<String>,
+    /// Replaces the base `test_command`.
     #[serde(default)]
     pub test_command: Option<String>,
+    /// Ignore globs appended after the base ones.
     #[serde(default)]
     pub ignore_globs: Vec<String>,
+    /// Ports appended after the base `desired_ports`.
     #[serde(default)]
     pub desired_ports: Vec<u16>,
+    /// Replaces the base `watch_notify`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_notify: Option<bool>,
+    /// Replaces the base `watch_restart`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watch_restart: Option<String>,
+    /// Watch paths appended after the base ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watch_paths: Vec<PathBuf>,
+    /// Replaces the base `log_file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<String>,
+    /// Redact patterns appended after the base ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact_patterns: Vec<String>,
+    /// Plugin directories appended after the base ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugin_paths: Vec<PathBuf>,
+}
+
+impl DevflowConfig {
+    /// Merges `overlay` over this config. Precedence, field by field:
+    ///
+    /// - `env`: merged key by key; the overlay's type wins on conflicts.
+    /// - `services`: an overlay service replaces the base service of the same
+    ///   name; new names are appended.
+    /// - `start_commands`, `ignore_globs`, `desired_ports`, `watch_paths`,
+    ///   `redact_patterns`, `plugin_paths`: overlay entries are appended after
+    ///   the base entries, skipping duplicates.
+    /// - `test_command`, `watch_notify`, `watch_restart`, `log_file`: replaced
+    ///   when the overlay sets them.
+    pub fn merge_profile(&mut self, overlay: &ProfileOverlay) {
+        self.env
+            .extend(overlay.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        for svc in &overlay.services {
+            match self.services.iter_mut().find(|s| s.name == svc.name) {
+                Some(existing) => *existing = svc.clone(),
+                None => self.services.push(svc.clone()),
+            }
+        }
+
+        append_unique(&mut self.start_commands, &overlay.start_commands);
+        append_unique(&mut self.ignore_globs, &overlay.ignore_globs);
+        append_unique(&mut self.desired_ports, &overlay.desired_ports);
+        append_unique(&mut self.watch_paths, &overlay.watch_paths);
+        append_unique(&mut self.redact_patterns, &overlay.redact_patterns);
+        append_unique(&mut self.plugin_paths, &overlay.plugin_paths);
+
+        if overlay.test_command.is_some() {
+            self.test_command = overlay.test_command.clone();
+        }
+        if let Some(notify) = overlay.watch_notify {
+            self.watch_notify = notify;
+        }
+        if overlay.watch_restart.is_some() {
+            self.watch_restart = overlay.watch_restart.clone();
+        }
+        if overlay.log_file.is_some() {
+            self.log_file = overlay.log_file.clone();
+        }
+    }
+
+    /// Applies the named profile, erroring if the config does not define it.
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let Some(overlay) = self.profiles.get(name).cloned() else {
+            let mut known = self.profiles.keys().cloned().collect::<Vec<_>>();
+            known.sort();
+            if known.is_empty() {
+                bail!("unknown profile {name}: config defines no profiles");
+            }
+            bail!("unknown profile {name} (available: {})", known.join(", "));
+        };
+        self.merge_profile(&overlay);
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn append_unique<T: Clone + PartialEq>(base: &mut Vec<T>, extra: &[T]) {
+    for item in extra {
+        if !base.contains(item) {
+            base.push(item.clone());
+        }
+    }
+}
+
+/// Checks a `--config` path up front: it must exist and its format must be
+/// clear from its extension.
+pub fn check_config_path(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        bail!("--config {}: no such file", path.display());
+    }
+    if ConfigFormat::from_path(path).is_none() {
+        bail!(
+            "--config {}: unknown format; use a .yaml, .yml, .toml or .json file",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// A named development service.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct ServiceDef {
+    /// Identifier used in `depends_on`, `devflow run` and output prefixes.
     pub name: String,
+    /// Shell command that starts the service.
     pub command: String,
+    /// Services that must be started, and ready, first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+    /// How `devflow up` decides the service is ready.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "serde_yaml::with::singleton_map"
+    )]
+    #[schemars(with = "Option<ReadyCheck>")]
+    pub ready_when: Option<ReadyCheck>,
+    /// Seconds to wait for `ready_when` (default 30).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready_timeout_secs: Option<u64>,
+}
+
+/// How `devflow up` decides a service is ready before starting dependents.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadyCheck {
+    /// A TCP connection to `127.0.0.1:<port>` succeeds.
+    Tcp(u16),
+    /// An HTTP GET to the URL returns status 200. Only `http://` is supported.
+    Http(String),
+}
+
+impl fmt::Display for ReadyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadyCheck::Tcp(port) => write!(f, "tcp port {port}"),
+            ReadyCheck::Http(url) => write!(f, "http 200 from {url}"),
+        }
+    }
 }
 
-pub fn load_config(root: &Path) -> Result<DevflowConfig> {
-    let path = root.join(".devflow.yaml");
+/// JSON Schema (draft-07, which YAML language servers understand best) of
+/// [`DevflowConfig`], generated from the structs and their doc comments.
+pub fn config_schema() -> schemars::Schema {
+    schemars::generate::SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<DevflowConfig>()
+}
+
+/// Locates the project config file: `config` (the `--config` file) if
+/// given, otherwise the one in `root`, erroring if more than one format is
+/// present so it is never ambiguous which one applies.
+pub fn find_config(root: &Path, config: Option<&Path>) -> Result<Option<(PathBuf, ConfigFormat)>> {
+    if let Some(path) = config {
+        let format = ConfigFormat::from_path(path)
+            .with_context(|| format!("unknown config format: {}", path.display()))?;
+        return Ok(Some((path.to_path_buf(), format)));
+    }
+    let found = ConfigFormat::ALL
+        .into_iter()
+        .map(|f| (root.join(f.file_name()), f))
+        .filter(|(path, _)| path.exists())
+        .collect::<Vec<_>>();
+    if found.len() > 1 {
+        let names = found
+            .iter()
+            .map(|(_, f)| f.file_name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        bail!("multiple devflow config files found ({names}); keep only one");
+    }
+    Ok(found.into_iter().next())
+}
+
+pub fn config_exists(root: &Path, config: Option<&Path>) -> bool {
+    if let Some(path) = config {
+        return path.exists();
+    }
+    ConfigFormat::ALL
+        .iter()
+        .any(|f| root.join(f.file_name()).exists())
+}
+
+/// Loads the project config with `profile` (the `--profile`, if any)
+/// merged in.
+pub fn load_config(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+) -> Result<DevflowConfig> {
+    let mut cfg = load_base_config(root, config)?;
+    if let Some(name) = profile {
+        cfg.apply_profile(name)?;
+    }
+    Ok(cfg)
+}
+
+/// Loads the project config exactly as written, without applying a profile.
+pub fn load_base_config(root: &Path, config: Option<&Path>) -> Result<DevflowConfig> {
+    let Some((path, format)) = find_config(root, config)? else {
+        bail!(
+            "could not find .devflow.yaml, .devflow.toml or .devflow.json in {}",
+            root.display()
+        );
+    };
     let content =
         fs::read_to_string(&path).with_context(|| format!("could not read {}", path.display()))?;
-    serde_yaml::from_str(&content).context("invalid .devflow.yaml")
+    let invalid = || {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        format!("invalid {}", name.to_string_lossy())
+    };
+    match format {
+        ConfigFormat::Yaml => serde_yaml::from_str(&content).with_context(invalid),
+        ConfigFormat::Toml => toml::from_str(&content).with_context(invalid),
+        ConfigFormat::Json => serde_json::from_str(&content).with_context(invalid),
+    }
 }
 
-pub fn write_default_config(root: &Path) -> Result<()> {
-    let cfg = DevflowConfig {
+/// The config `devflow init` writes for a `language` project: its usual dev
+/// server as the `app` service, test command, build output to ignore and
+/// ports. `Unknown` gets no service or test command.
+pub fn template_config(root: &Path, language: Language) -> DevflowConfig {
+    let (command, test_command, ignore_globs, desired_ports): (String, String, &[&str], _) =
+        match language {
+            Language::Rust => (
+                "cargo run".into(),
+                "cargo test".into(),
+                &["target/**"],
+                vec![3000, 5432],
+            ),
+            Language::Node => {
+                let manager = detect_node_package_manager(root);
+                let (exec, exec_args) = manager.exec();
+                let mut jest = vec![exec];
+                jest.extend(exec_args);
+                jest.push("jest");
+                (
+                    format!("{manager} run dev"),
+                    jest.join(" "),
+                    &["node_modules/**", "dist/**", "coverage/**"],
+                    vec![3000, 5432],
+                )
+            }
+            Language::Python => (
+                "uvicorn main:app --reload".into(),
+                "pytest".into(),
+                &[".venv/**", "**/__pycache__/**", ".pytest_cache/**"],
+                vec![8000, 5432],
+            ),
+            Language::Go => (
+                "go run .".into(),
+                "go test ./...".into(),
+                &["bin/**", "vendor/**"],
+                vec![8080, 5432],
+            ),
+            Language::Java if root.join("pom.xml").exists() => (
+                "mvn spring-boot:run".into(),
+                "mvn test".into(),
+                &["target/**"],
+                vec![8080, 5432],
+            ),
+            Language::Java => {
+                let gradle = if root.join("gradlew").exists() {
+                    "./gradlew"
+                } else {
+                    "gradle"
+                };
+                (
+                    format!("{gradle} bootRun"),
+                    format!("{gradle} test"),
+                    &["build/**", ".gradle/**"],
+                    vec![8080, 5432],
+                )
+            }
+            Language::Ruby => (
+                "bin/rails server".into(),
+                "bundle exec rspec".into(),
+                &["tmp/**", "log/**", "vendor/bundle/**"],
+                vec![3000, 5432],
+            ),
+            Language::Php => (
+                "php -S localhost:8000 -t public".into(),
+                "vendor/bin/phpunit".into(),
+                &["vendor/**"],
+                vec![8000, 3306],
+            ),
+            Language::Unknown => {
+                return DevflowConfig {
+                    env: HashMap::from([("PORT".into(), "int".into())]),
+                    desired_ports: vec![3000],
+                    ..Default::default()
+                }
+            }
+        };
+    DevflowConfig {
         env: HashMap::from([
             ("DATABASE_URL".into(), "string".into()),
             ("PORT".into(), "int".into()),
         ]),
         services: vec![ServiceDef {
             name: "app".into(),
-            command: "cargo run".into(),
+            command,
+            depends_on: Vec::new(),
+            ready_when: None,
+            ready_timeout_secs: None,
         }],
         start_commands: vec!["docker compose up -d".into()],
-        test_command: Some("cargo test".into()),
-        ignore_globs: vec!["target/**".into(), "node_modules/**".into()],
-        desired_ports: vec![3000, 5432],
+        test_command: Some(test_command),
+        ignore_globs: ignore_globs.iter().map(|g| g.to_string()).collect(),
+        desired_ports,
+        ..Default::default()
+    }
+}
+
+/// Writes [`template_config`] for `language` as `format`'s config file,
+/// replacing any file already there.
+pub fn write_default_config(root: &Path, format: ConfigFormat, language: Language) -> Result<()> {
+    write_config(root, format, &template_config(root, language))
+}
+
+/// Writes `cfg` as `format`'s config file, replacing any file already there.
+pub fn write_config(root: &Path, format: ConfigFormat, cfg: &DevflowConfig) -> Result<()> {
+    let content = match format {
+        ConfigFormat::Yaml => serde_yaml::to_string(cfg)?,
+        ConfigFormat::Toml => toml::to_string_pretty(cfg)?,
+        ConfigFormat::Json => serde_json::to_string_pretty(cfg)?,
     };
-    let content = serde_yaml::to_string(&cfg)?;
-    fs::write(root.join(".devflow.yaml"), content)?;
+    fs::write(root.join(format.file_name()), content)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn config_format_comes_from_the_extension() {
+        let format = |p: &str| ConfigFormat::from_path(Path::new(p));
+        assert_eq!(format("ci/devflow.yml"), Some(ConfigFormat::Yaml));
+        assert_eq!(format(".devflow.YAML"), Some(ConfigFormat::Yaml));
+        assert_eq!(format("devflow.toml"), Some(ConfigFormat::Toml));
+        assert_eq!(format("fixtures/app.json"), Some(ConfigFormat::Json));
+        assert_eq!(format("devflow.conf"), None);
+        assert_eq!(format("devflow"), None);
+    }
+
+    #[test]
+    fn default_config_round_trips_in_every_format() {
+        for format in ConfigFormat::ALL {
+            let dir = tempdir().expect("tempdir");
+            write_default_config(dir.path(), format, Language::Rust).expect("write");
+            let cfg = load_config(dir.path(), None, None).expect("load");
+            assert_eq!(cfg.desired_ports, vec![3000, 5432]);
+            assert_eq!(cfg.services[0].name, "app");
+        }
+    }
+
+    fn svc(name: &str, command: &str) -> ServiceDef {
+        ServiceDef {
+            name: name.into(),
+            command: command.into(),
+            depends_on: Vec::new(),
+            ready_when: None,
+            ready_timeout_secs: None,
+        }
+    }
+
+    fn base() -> DevflowConfig {
+        DevflowConfig {
+            env: HashMap::from([
+                ("PORT".into(), "int".into()),
+                ("DEBUG".into(), "bool".into()),
+            ]),
+            services: vec![svc("app", "cargo run"), svc("db", "postgres")],
+            start_commands: vec!["docker compose up -d".into()],
+            test_command: Some("cargo test".into()),
+            ignore_globs: vec!["target/**".into()],
+            desired_ports: vec![3000],
+            watch_notify: false,
+            watch_restart: None,
+            watch_paths: Vec::new(),
+            log_file: None,
+            redact_patterns: Vec::new(),
+            plugin_paths: Vec::new(),
+            profiles: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn merge_profile_merges_env_key_by_key() {
+        let mut cfg = base();
+        cfg.merge_profile(&ProfileOverlay {
+            env: HashMap::from([("PORT".into(), "port".into()), ("CI".into(), "bool".into())]),
+            ..Default::default()
+        });
+        assert_eq!(cfg.env.len(), 3);
+        assert_eq!(cfg.env["PORT"], "port");
+        assert_eq!(cfg.env["DEBUG"], "bool");
+        assert_eq!(cfg.env["CI"], "bool");
+    }
+
+    #[test]
+    fn merge_profile_appends_lists_without_duplicates() {
+        let mut cfg = base();
+        cfg.merge_profile(&ProfileOverlay {
+            ignore_globs: vec!["target/**".into(), "dist/**".into()],
+            desired_ports: vec![3000, 8080],
+            start_commands: vec!["make seed".into()],
+            ..Default::default()
+        });
+        assert_eq!(cfg.ignore_globs, vec!["target/**", "dist/**"]);
+        assert_eq!(cfg.desired_ports, vec![3000, 8080]);
+        assert_eq!(
+            cfg.start_commands,
+            vec!["docker compose up -d", "make seed"]
+        );
+    }
+
+    #[test]
+    fn merge_profile_overrides_scalars_and_services_by_name() {
+        let mut cfg = base();
+        cfg.merge_profile(&ProfileOverlay {
+            services: vec![
+                svc("app", "cargo run --release"),
+                svc("worker", "cargo run --bin worker"),
+            ],
+            test_command: Some("cargo nextest run".into()),
+            ..Default::default()
+        });
+        assert_eq!(cfg.test_command.as_deref(), Some("cargo nextest run"));
+        assert_eq!(
+            cfg.services,
+            vec![
+                svc("app", "cargo run --release"),
+                svc("db", "postgres"),
+                svc("worker", "cargo run --bin worker")
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_profile_covers_watch_log_and_plugin_settings() {
+        let mut cfg = base();
+        cfg.watch_paths = vec!["src".into()];
+        cfg.merge_profile(&ProfileOverlay {
+            watch_notify: Some(true),
+            watch_restart: Some("cargo run".into()),
+            watch_paths: vec!["src".into(), "tests".into()],
+            log_file: Some("logs/ci.log".into()),
+            redact_patterns: vec!["ci_[a-z0-9]+".into()],
+            plugin_paths: vec!["ci/plugins".into()],
+            ..Default::default()
+        });
+        assert!(cfg.watch_notify);
+        assert_eq!(cfg.watch_restart.as_deref(), Some("cargo run"));
+        assert_eq!(cfg.watch_paths, vec![PathBuf::from("src"), "tests".into()]);
+        assert_eq!(cfg.log_file.as_deref(), Some("logs/ci.log"));
+        assert_eq!(cfg.redact_patterns, vec!["ci_[a-z0-9]+"]);
+        assert_eq!(cfg.plugin_paths, vec![PathBuf::from("ci/plugins")]);
+    }
+
+    #[test]
+    fn profile_with_unknown_field_is_rejected() {
+        let err = serde_yaml::from_str::<DevflowConfig>(
+            "profiles:\n  ci:\n    tset_command: cargo test\n",
+        )
+        .expect_err("typo");
+        assert!(err.to_string().contains("tset_command"), "{err}");
+    }
+
+    #[test]
+    fn empty_overlay_leaves_config_unchanged() {
+        let mut cfg = base();
+        cfg.merge_profile(&ProfileOverlay::default());
+        assert_eq!(cfg.test_command.as_deref(), Some("cargo test"));
+        assert_eq!(cfg.services.len(), 2);
+        assert_eq!(cfg.desired_ports, vec![3000]);
+    }
+
+    #[test]
+    fn apply_profile_rejects_unknown_name() {
+        let mut cfg = base();
+        cfg.profiles.insert("ci".into(), ProfileOverlay::default());
+        let err = cfg.apply_profile("staging").expect_err("unknown");
+        assert!(err.to_string().contains("available: ci"));
+    }
+
+    #[test]
+    fn schema_describes_every_config_field() {
+        let cfg = DevflowConfig {
+            watch_notify: true,
+            watch_restart: Some("cargo run".into()),
+            watch_paths: vec!["src".into()],
+            log_file: Some("app.log".into()),
+            redact_patterns: vec!["sk_[a-z]+".into()],
+            plugin_paths: vec!["~/.devflow/plugins".into()],
+            profiles: HashMap::from([("ci".into(), ProfileOverlay::default())]),
+            ..DevflowConfig::default()
+        };
+        let serialized = serde_json::to_value(&cfg).expect("serialize");
+        let schema = serde_json::to_value(config_schema()).expect("schema");
+        let properties = schema["properties"].as_object().expect("properties");
+
+        let mut fields: Vec<_> = serialized.as_object().expect("object").keys().collect();
+        let mut described: Vec<_> = properties.keys().collect();
+        fields.sort();
+        described.sort();
+        assert_eq!(fields, described);
+        for (field, property) in properties {
+            assert!(
+                property["description"].is_string(),
+                "{field} has no description"
+            );
+        }
+    }
+
+    #[test]
+    fn profiles_parse_from_yaml() {
+        let cfg: DevflowConfig = serde_yaml::from_str(
+            "env:\n  PORT: int\nprofiles:\n  ci:\n    env:\n      CI: bool\n    test_command: cargo test --locked\n",
+        )
+        .expect("yaml");
+        let mut merged = cfg.clone();
+        merged.apply_profile("ci").expect("profile");
+        assert_eq!(merged.env.len(), 2);
+        assert_eq!(merged.test_command.as_deref(), Some("cargo test --locked"));
+    }
+
+    #[test]
+    fn templates_match_the_language() {
+        let dir = tempdir().expect("tempdir");
+        let node = template_config(dir.path(), Language::Node);
+        assert_eq!(node.services[0].command, "npm run dev");
+        assert_eq!(node.test_command.as_deref(), Some("npx jest"));
+        assert!(node.ignore_globs.contains(&"node_modules/**".to_string()));
+        assert_eq!(node.desired_ports, vec![3000, 5432]);
+
+        fs::write(dir.path().join("pnpm-lock.yaml"), "").expect("write");
+        let pnpm = template_config(dir.path(), Language::Node);
+        assert_eq!(pnpm.services[0].command, "pnpm run dev");
+        assert_eq!(pnpm.test_command.as_deref(), Some("pnpm exec jest"));
+
+        let go = template_config(dir.path(), Language::Go);
+        assert_eq!(go.test_command.as_deref(), Some("go test ./..."));
+
+        let unknown = template_config(dir.path(), Language::Unknown);
+        assert!(unknown.services.is_empty());
+        assert!(unknown.test_command.is_none());
+    }
+
+    #[test]
+    fn rejects_multiple_config_files() {
+        let dir = tempdir().expect("tempdir");
+        write_default_config(dir.path(), ConfigFormat::Yaml, Language::Rust).expect("write");
+        write_default_config(dir.path(), ConfigFormat::Toml, Language::Rust).expect("write");
+        assert!(load_config(dir.path(), None, None).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    fmt,
+    process::{Command, Stdio},
+    sync::LazyLock,
+};
+
+/// A container engine that can run compose files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerRuntime {
+    Docker,
+    Podman,
+    Nerdctl,
+}
+
+impl ContainerRuntime {
+    /// Probed in this order; the first one whose daemon answers wins.
+    pub const ALL: [ContainerRuntime; 3] = [
+        ContainerRuntime::Docker,
+        ContainerRuntime::Podman,
+        ContainerRuntime::Nerdctl,
+    ];
+
+    pub fn program(self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+            ContainerRuntime::Nerdctl => "nerdctl",
+        }
+    }
+
+    /// The command that stands in for `docker compose`.
+    pub fn compose(self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker compose",
+            ContainerRuntime::Podman => "podman compose",
+            ContainerRuntime::Nerdctl => "nerdctl compose",
+        }
+    }
+}
+
+impl fmt::Display for ContainerRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.program())
+    }
+}
+
+/// The runtime `up` will use, and whether it can actually run containers.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeStatus {
+    pub runtime: ContainerRuntime,
+    pub path: String,
+    /// Whether `<runtime> info` succeeded, i.e. the daemon (or, for podman,
+    /// the machine) is up.
+    pub reachable: bool,
+}
+
+/// Finds the container runtime to use: the first of docker, podman and
+/// nerdctl on `PATH` whose daemon is reachable, otherwise the first one
+/// installed. `None` when none of them is installed.
+pub fn detect_container_runtime() -> Option<RuntimeStatus> {
+    pick_runtime(|runtime| {
+        let path = which::which(runtime.program()).ok()?;
+        Some((path.display().to_string(), daemon_reachable(runtime)))
+    })
+}
+
+/// `probe` returns the runtime's path and whether its daemon answers, or
+/// `None` when it isn't installed.
+fn pick_runtime(
+    mut probe: impl FnMut(ContainerRuntime) -> Option<(String, bool)>,
+) -> Option<RuntimeStatus> {
+    let mut installed = None;
+    for runtime in ContainerRuntime::ALL {
+        let Some((path, reachable)) = probe(runtime) else {
+            continue;
+        };
+        let status = RuntimeStatus {
+            runtime,
+            path,
+            reachable,
+        };
+        if reachable {
+            return Some(status);
+        }
+        installed.get_or_insert(status);
+    }
+    installed
+}
+
+fn daemon_reachable(runtime: ContainerRuntime) -> bool {
+    Command::new(runtime.program())
+        .arg("info")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// `docker compose` as a command word: at the start of the command or after
+/// whitespace or a shell operator, not inside e.g. `mydocker compose`.
+static DOCKER_COMPOSE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(^|[\s;&|(])docker compose\b").expect("valid regex"));
+
+pub fn uses_docker_compose(command: &str) -> bool {
+    DOCKER_COMPOSE.is_match(command)
+}
+
+/// `command` with every `docker compose` replaced by `runtime`'s compose
+/// invocation.
+pub fn rewrite_compose(command: &str, runtime: ContainerRuntime) -> String {
+    DOCKER_COMPOSE
+        .replace_all(command, format!("${{1}}{}", runtime.compose()))
+        .into_owned()
+}
+
+/// `command` ready to run: unchanged unless it uses `docker compose`, in
+/// which case that becomes `runtime`'s compose. Fails when it needs compose
+/// and no runtime can serve it.
+pub fn compose_command(command: &str, runtime: Option<&RuntimeStatus>) -> Result<String> {
+    if !uses_docker_compose(command) {
+        return Ok(command.to_string());
+    }
+    match runtime {
+        Some(rt) if rt.reachable => Ok(rewrite_compose(command, rt.runtime)),
+        Some(rt) => bail!(
+            "`{}` needs a container runtime, but `{} info` failed; start the {} daemon and retry",
+            command,
+            rt.runtime,
+            rt.runtime
+        ),
+        None => bail!(
+            "`{}` needs a container runtime, but none of docker, podman or nerdctl is in PATH",
+            command
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_a_reachable_runtime_over_an_earlier_installed_one() {
+        let picked = pick_runtime(|runtime| match runtime {
+            ContainerRuntime::Docker => Some(("/usr/bin/docker".into(), false)),
+            ContainerRuntime::Podman => Some(("/usr/bin/podman".into(), true)),
+            ContainerRuntime::Nerdctl => None,
+        })
+        .expect("runtime");
+        assert_eq!(picked.runtime, ContainerRuntime::Podman);
+        assert!(picked.reachable);
+
+        let picked = pick_runtime(|runtime| match runtime {
+            ContainerRuntime::Podman => None,
+            other => Some((format!("/bin/{other}"), false)),
+        })
+        .expect("runtime");
+        assert_eq!(picked.runtime, ContainerRuntime::Docker);
+        assert!(!picked.reachable);
+
+        assert!(pick_runtime(|_| None).is_none());
+    }
+
+    #[test]
+    fn rewrites_docker_compose_as_a_command_word_only() {
+        assert_eq!(
+            rewrite_compose("docker compose up -d", ContainerRuntime::Podman),
+            "podman compose up -d"
+        );
+        assert_eq!(
+            rewrite_compose(
+                "make build && docker compose up db",
+                ContainerRuntime::Nerdctl
+            ),
+            "make build && nerdctl compose up db"
+        );
+        assert!(!uses_docker_compose("mydocker compose up"));
+        assert!(!uses_docker_compose("docker composer"));
+        assert!(!uses_docker_compose("docker-compose up"));
+        assert!(uses_docker_compose("(docker compose up)"));
+    }
+}
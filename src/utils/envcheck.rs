@@ -1,73 +1,595 @@
 use anyhow::Result;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, env, fs, path::Path};
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, fmt, fs,
+    path::Path,
+};
+use url::Url;
+
+use super::{config::DevflowConfig, language::Language};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnvIssue {
     pub key: String,
     pub reason: String,
+    /// Env file that supplied the offending value, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+impl fmt::Display for EnvIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.reason)?;
+        if let Some(source) = &self.source {
+            write!(f, " (from {})", source)?;
+        }
+        Ok(())
+    }
 }
 
 pub fn parse_dotenv(root: &Path) -> Result<HashMap<String, String>> {
-    let path = root.join(".env");
-    let mut vars = HashMap::new();
+    parse_dotenv_file(&root.join(".env"))
+}
+
+/// Parses a dotenv-formatted file. A missing file yields no variables.
+pub fn parse_dotenv_file(path: &Path) -> Result<HashMap<String, String>> {
     if !path.exists() {
-        return Ok(vars);
+        return Ok(HashMap::new());
     }
     let content = fs::read_to_string(path)?;
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') || !line.contains('=') {
+    Ok(parse_dotenv_str(&content))
+}
+
+pub fn parse_dotenv_str(content: &str) -> HashMap<String, String> {
+    content.lines().filter_map(parse_dotenv_line).collect()
+}
+
+/// Problems `parse_dotenv_str` glosses over: keys defined more than once
+/// (the last one wins), keys with empty values, and lines that aren't
+/// `KEY=value` at all. Each issue is tagged with `source`.
+pub fn parse_dotenv_issues(content: &str, source: &str) -> Vec<EnvIssue> {
+    let issue = |key: String, reason: String| EnvIssue {
+        key,
+        reason,
+        source: Some(source.to_string()),
+    };
+    let mut issues = Vec::new();
+    let mut seen: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
             continue;
         }
-        let mut parts = line.splitn(2, '=');
-        if let (Some(k), Some(v)) = (parts.next(), parts.next()) {
-            vars.insert(k.trim().to_string(), v.trim().to_string());
+        match parse_dotenv_line(line) {
+            Some((key, value)) => {
+                if value.is_empty() {
+                    issues.push(issue(key.clone(), format!("empty value (line {number})")));
+                }
+                seen.entry(key).or_default().push(number);
+            }
+            None => issues.push(issue(
+                format!("line {number}"),
+                format!("not a KEY=value line: `{trimmed}`"),
+            )),
+        }
+    }
+    for (key, lines) in seen.into_iter().filter(|(_, lines)| lines.len() > 1) {
+        let lines = lines.iter().map(usize::to_string).collect::<Vec<_>>();
+        issues.push(issue(
+            key,
+            format!(
+                "defined {} times (lines {}); the last one wins",
+                lines.len(),
+                lines.join(", ")
+            ),
+        ));
+    }
+    issues.sort_by(|a, b| a.key.cmp(&b.key));
+    issues
+}
+
+/// Env files merged by `load_layered_env`, lowest precedence first.
+pub fn env_layers(profile: Option<&str>) -> Vec<String> {
+    let mut layers = vec![".env".to_string(), ".env.local".to_string()];
+    if let Some(profile) = profile {
+        layers.push(format!(".env.{profile}"));
+    }
+    layers
+}
+
+/// Variables merged from several env files, with the file that supplied each
+/// value.
+#[derive(Debug, Clone, Default)]
+pub struct LayeredEnv {
+    pub vars: HashMap<String, String>,
+    pub sources: HashMap<String, String>,
+    /// Layers that exist on disk, lowest precedence first.
+    pub files: Vec<String>,
+    /// Problems within each file (see [`parse_dotenv_issues`]), in layer
+    /// order.
+    pub issues: Vec<EnvIssue>,
+}
+
+impl LayeredEnv {
+    pub fn source(&self, key: &str) -> Option<&str> {
+        self.sources.get(key).map(String::as_str)
+    }
+
+    /// Validates the merged values, tagging each issue with its source file.
+    pub fn validate(&self, schema: &HashMap<String, SchemaSpec>) -> Vec<EnvIssue> {
+        schema_issues(schema, &self.vars, &self.sources)
+    }
+
+    /// Short description of the merged files, e.g. `.env + .env.local`.
+    pub fn describe(&self) -> String {
+        if self.files.is_empty() {
+            ".env".to_string()
+        } else {
+            self.files.join(" + ")
+        }
+    }
+}
+
+/// Merges `.env`, `.env.local` and `.env.<profile>` from `root`. Later files
+/// override earlier ones, including with empty values.
+pub fn load_layered_env(root: &Path, profile: Option<&str>) -> Result<LayeredEnv> {
+    let mut merged = LayeredEnv::default();
+    for name in env_layers(profile) {
+        let path = root.join(&name);
+        if !path.exists() {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        for (key, value) in parse_dotenv_str(&content) {
+            merged.sources.insert(key.clone(), name.clone());
+            merged.vars.insert(key, value);
+        }
+        merged.issues.extend(parse_dotenv_issues(&content, &name));
+        merged.files.push(name);
+    }
+    Ok(merged)
+}
+
+/// Parses one `KEY=value` line. Handles an optional `export ` prefix,
+/// single-quoted (literal) and double-quoted (escaped) values, and `#`
+/// comments after unquoted values.
+pub fn parse_dotenv_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let line = line
+        .strip_prefix("export")
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .map_or(line, str::trim_start);
+
+    let (key, raw) = line.split_once('=')?;
+    let key = key.trim();
+    if key.is_empty() {
+        return None;
+    }
+
+    let trimmed = raw.trim_start();
+    let value = if let Some(rest) = trimmed.strip_prefix('"') {
+        unescape_double_quoted(rest)
+    } else if let Some(rest) = trimmed.strip_prefix('\'') {
+        rest.split_once('\'').map_or(rest, |(v, _)| v).to_string()
+    } else {
+        strip_inline_comment(raw).trim().to_string()
+    };
+    Some((key.to_string(), value))
+}
+
+/// Reads a double-quoted value up to the closing quote, expanding `\n`,
+/// `\r`, `\t`, `\"` and `\\`. Unknown escapes are kept verbatim.
+fn unescape_double_quoted(rest: &str) -> String {
+    let mut out = String::with_capacity(rest.len());
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('"') => out.push('"'),
+                Some('\\') => out.push('\\'),
+                Some(other) => {
+                    out.push('\\');
+                    out.push(other);
+                }
+                None => out.push('\\'),
+            },
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Formats `KEY=value` so that [`parse_dotenv_line`] reads `value` back
+/// exactly: plain words stay bare, anything else is double-quoted with `\`,
+/// `"`, tabs and line breaks escaped.
+pub fn format_dotenv_line(key: &str, value: &str) -> String {
+    let bare = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.,:/@+%=".contains(c));
+    if bare {
+        return format!("{key}={value}");
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
         }
     }
-    Ok(vars)
+    format!("{key}=\"{quoted}\"")
+}
+
+/// Cuts an unquoted value at a `#` that follows whitespace, so `a#b` and
+/// `#fff` are kept but `a # note` becomes `a`.
+fn strip_inline_comment(raw: &str) -> &str {
+    raw.char_indices()
+        .find(|&(i, c)| c == '#' && raw[..i].ends_with(char::is_whitespace))
+        .map_or(raw, |(i, _)| &raw[..i])
+}
+
+/// A parsed env schema entry such as `int`, `int?` or `port default=8080`.
+///
+/// A trailing `?` on the type marks the key optional; a `default=<value>`
+/// annotation (everything after it, to the end of the string) supplies the
+/// value `env fix` writes when the key is missing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaSpec {
+    pub typ: String,
+    pub optional: bool,
+    pub default: Option<String>,
+}
+
+impl SchemaSpec {
+    pub fn parse(raw: &str) -> Self {
+        let (typ, default) = match raw.split_once(" default=") {
+            Some((typ, default)) => (typ, Some(default.trim().to_string())),
+            None => (raw, None),
+        };
+        let typ = typ.trim();
+        let (typ, optional) = match typ.strip_suffix('?') {
+            Some(t) => (t.trim_end(), true),
+            None => (typ, false),
+        };
+        Self {
+            typ: typ.to_string(),
+            optional,
+            default,
+        }
+    }
+
+    /// Value `env fix` writes for a missing key: the declared default, or a
+    /// placeholder for the type.
+    pub fn fill_value(&self) -> String {
+        self.default
+            .clone()
+            .unwrap_or_else(|| placeholder_for(&self.typ))
+    }
+}
+
+/// Parses the raw `env` map from the config into schema specs.
+pub fn parse_schema(raw: &HashMap<String, String>) -> HashMap<String, SchemaSpec> {
+    raw.iter()
+        .map(|(k, v)| (k.clone(), SchemaSpec::parse(v)))
+        .collect()
 }
 
 pub fn validate_env_schema(
-    schema: &HashMap<String, String>,
+    schema: &HashMap<String, SchemaSpec>,
     actual: &HashMap<String, String>,
+) -> Vec<EnvIssue> {
+    schema_issues(schema, actual, &HashMap::new())
+}
+
+fn schema_issues(
+    schema: &HashMap<String, SchemaSpec>,
+    actual: &HashMap<String, String>,
+    sources: &HashMap<String, String>,
 ) -> Vec<EnvIssue> {
     let mut issues = Vec::new();
-    for (key, typ) in schema {
+    for (key, spec) in schema {
+        if let Some(default) = &spec.default {
+            if let Err(reason) = check_value(&spec.typ, default) {
+                issues.push(EnvIssue {
+                    key: key.clone(),
+                    reason: format!("invalid schema default: {reason}"),
+                    source: None,
+                });
+            }
+        }
         match actual.get(key) {
+            None if spec.optional => {}
             None => issues.push(EnvIssue {
                 key: key.clone(),
-                reason: "missing".into(),
+                reason: match &spec.default {
+                    Some(default) => format!("missing (default {default})"),
+                    None => "missing".into(),
+                },
+                source: None,
             }),
             Some(value) => {
-                if typ == "int" && value.parse::<i64>().is_err() {
-                    issues.push(EnvIssue {
-                        key: key.clone(),
-                        reason: "expected int".into(),
-                    });
-                }
-                if typ == "bool" && value.parse::<bool>().is_err() {
+                if let Err(reason) = check_value(&spec.typ, value) {
                     issues.push(EnvIssue {
                         key: key.clone(),
-                        reason: "expected bool".into(),
+                        reason,
+                        source: sources.get(key).cloned(),
                     });
                 }
             }
         }
     }
+    issues.sort_by(|a, b| a.key.cmp(&b.key));
     issues
 }
 
-pub fn doctor_path_issues() -> Vec<String> {
+/// Checks `value` against a schema type, returning a human-readable reason
+/// on mismatch. Supported types: `string`, `int`, `float`, `bool`, `url`,
+/// `port`, `enum:a|b|c` and `regex:<pattern>`.
+pub fn check_value(typ: &str, value: &str) -> Result<(), String> {
+    let typ = typ.trim();
+    if let Some(variants) = typ.strip_prefix("enum:") {
+        let variants = variants
+            .split('|')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect::<Vec<_>>();
+        if variants.is_empty() {
+            return Err(format!("invalid schema `{typ}`: enum has no variants"));
+        }
+        if !variants.contains(&value) {
+            return Err(format!(
+                "expected one of {}, got {}",
+                variants.join("|"),
+                value
+            ));
+        }
+        return Ok(());
+    }
+    if let Some(pattern) = typ.strip_prefix("regex:") {
+        let re =
+            Regex::new(pattern).map_err(|e| format!("invalid schema `{typ}`: bad regex: {e}"))?;
+        if !re.is_match(value) {
+            return Err(format!("expected match for /{pattern}/, got {value}"));
+        }
+        return Ok(());
+    }
+
+    let ok = match typ {
+        "" | "string" => true,
+        "int" => value.parse::<i64>().is_ok(),
+        "float" => value.parse::<f64>().is_ok_and(f64::is_finite),
+        "bool" => value.parse::<bool>().is_ok(),
+        "port" => value.parse::<u16>().is_ok_and(|p| p != 0),
+        "url" => Url::parse(value).is_ok_and(|u| u.has_host()),
+        other => return Err(format!("invalid schema: unknown type `{other}`")),
+    };
+    if ok {
+        Ok(())
+    } else {
+        let expected = match typ {
+            "port" => "port (1-65535)",
+            "url" => "url with scheme and host",
+            t => t,
+        };
+        Err(format!("expected {expected}, got {value}"))
+    }
+}
+
+/// Result of comparing a `.env` against a template such as `.env.example`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateDiff {
+    /// Keys in the template that `.env` does not define.
+    pub missing: Vec<String>,
+    /// Keys in `.env` that the template does not mention.
+    pub extra: Vec<String>,
+    /// Keys whose `.env` value is still the template's placeholder.
+    pub unchanged: Vec<String>,
+}
+
+pub fn diff_against_template(
+    actual: &HashMap<String, String>,
+    template: &HashMap<String, String>,
+) -> TemplateDiff {
+    let mut diff = TemplateDiff::default();
+    for (key, example) in template {
+        match actual.get(key) {
+            None => diff.missing.push(key.clone()),
+            Some(value) if value == example => diff.unchanged.push(key.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.extra = actual
+        .keys()
+        .filter(|k| !template.contains_key(*k))
+        .cloned()
+        .collect();
+    diff.missing.sort();
+    diff.extra.sort();
+    diff.unchanged.sort();
+    diff
+}
+
+/// Value written by `env fix` for a missing key of the given schema type.
+pub fn placeholder_for(typ: &str) -> String {
+    let typ = typ.trim();
+    if let Some(variants) = typ.strip_prefix("enum:") {
+        return variants
+            .split('|')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+    }
+    match typ {
+        "int" => "0",
+        "float" => "0.0",
+        "bool" => "false",
+        _ => "",
+    }
+    .to_string()
+}
+
+/// How much a `PathIssue` matters: an `Error` stops the project from
+/// running, a `Warning` only loses a convenience.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A problem with `PATH` or a tool missing from it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathIssue {
+    pub severity: Severity,
+    /// The executable looked for (the first choice when several would do),
+    /// or `PATH` itself.
+    pub tool: String,
+    pub reason: String,
+}
+
+impl fmt::Display for PathIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.reason)
+    }
+}
+
+/// Executables a `language` project needs, each a list of alternatives of
+/// which one is enough.
+fn required_tools(language: Language) -> &'static [&'static [&'static str]] {
+    match language {
+        Language::Python => &[&["python3", "python"]],
+        Language::Node => &[&["node"]],
+        Language::Go => &[&["go"]],
+        Language::Rust => &[&["cargo"], &["rustc"]],
+        Language::Java => &[&["java"]],
+        Language::Ruby => &[&["ruby"]],
+        Language::Php => &[&["php"]],
+        Language::Unknown => &[],
+    }
+}
+
+/// Package managers and test runners a `language` project usually wants but
+/// can run without.
+fn optional_tools(language: Language) -> &'static [&'static [&'static str]] {
+    match language {
+        Language::Python => &[&["pip3", "pip"], &["pytest"]],
+        Language::Node => &[&["npm"]],
+        Language::Java => &[&["mvn", "gradle"]],
+        Language::Ruby => &[&["bundle"]],
+        Language::Php => &[&["composer"]],
+        Language::Go | Language::Rust | Language::Unknown => &[],
+    }
+}
+
+/// Shell builtins that can start a command but aren't looked up on `PATH`.
+const SHELL_BUILTINS: &[&str] = &["cd", "export", "source", ".", "set", "exec", "eval"];
+
+/// The program a shell `command` runs first, skipping leading `VAR=value`
+/// assignments. `None` for builtins, paths (relative ones depend on the
+/// directory the command runs in) and commands that don't parse.
+fn command_program(command: &str) -> Option<String> {
+    let words = shell_words::split(command).ok()?;
+    let program = words.into_iter().find(|word| {
+        !word
+            .split_once('=')
+            .is_some_and(|(name, _)| !name.is_empty() && !name.contains('/'))
+    })?;
+    (!SHELL_BUILTINS.contains(&program.as_str()) && !program.contains('/')).then_some(program)
+}
+
+/// Checks `PATH` for the toolchain of `language` (errors when missing), its
+/// usual helpers (warnings) and the first program of every configured
+/// `start_commands` entry and service command (errors, since `up` can't
+/// start them).
+pub fn doctor_path_issues(language: Language, cfg: Option<&DevflowConfig>) -> Vec<PathIssue> {
+    path_issues(language, cfg, env::var_os("PATH").is_some(), |tool| {
+        which::which(tool).is_ok()
+    })
+}
+
+fn path_issues(
+    language: Language,
+    cfg: Option<&DevflowConfig>,
+    path_set: bool,
+    found: impl Fn(&str) -> bool,
+) -> Vec<PathIssue> {
     let mut issues = Vec::new();
-    if env::var_os("PATH").is_none() {
-        issues.push("PATH is unset".into());
+    if !path_set {
+        issues.push(PathIssue {
+            severity: Severity::Error,
+            tool: "PATH".into(),
+            reason: "PATH is unset".into(),
+        });
     }
-    if which::which("python").is_err() && which::which("python3").is_err() {
-        issues.push("Python not found in PATH".into());
+    // Each tool is reported once, for the first reason it is needed.
+    let mut checked = BTreeSet::new();
+    let mut check = |alternatives: &[&str], severity: Severity, why: String| {
+        if !checked.insert(alternatives[0].to_string()) || alternatives.iter().any(|t| found(t)) {
+            return;
+        }
+        issues.push(PathIssue {
+            severity,
+            tool: alternatives[0].to_string(),
+            reason: format!("{} not found in PATH ({})", alternatives.join(" or "), why),
+        });
+    };
+
+    for tools in required_tools(language) {
+        check(
+            tools,
+            Severity::Error,
+            format!("needed for {} projects", language),
+        );
     }
-    if which::which("node").is_err() {
-        issues.push("Node not found in PATH".into());
+    for tools in optional_tools(language) {
+        check(
+            tools,
+            Severity::Warning,
+            format!("optional for {} projects", language),
+        );
+    }
+    if let Some(cfg) = cfg {
+        for command in &cfg.start_commands {
+            if let Some(program) = command_program(command) {
+                check(
+                    &[&program],
+                    Severity::Error,
+                    "used by start_commands".into(),
+                );
+            }
+        }
+        for svc in &cfg.services {
+            if let Some(program) = command_program(&svc.command) {
+                check(
+                    &[&program],
+                    Severity::Error,
+                    format!("used by service {}", svc.name),
+                );
+            }
+        }
     }
     issues
 }
@@ -77,13 +599,269 @@ mod tests {
     use super::*;
     use std::collections::HashMap;
 
+    #[test]
+    fn parses_quoted_values_containing_equals_hash_and_spaces() {
+        let vars = parse_dotenv_str(
+            "export FOO=\"a=b\"\nBAR='# not a comment'\nBAZ=\"  padded  \"\nQUX = plain value # trailing\n",
+        );
+        assert_eq!(vars["FOO"], "a=b");
+        assert_eq!(vars["BAR"], "# not a comment");
+        assert_eq!(vars["BAZ"], "  padded  ");
+        assert_eq!(vars["QUX"], "plain value");
+    }
+
+    #[test]
+    fn formatted_lines_parse_back_to_the_same_value() {
+        for value in [
+            "plain",
+            "",
+            "postgres://u:p@h:5432/db",
+            "two words # not a comment",
+            "'single' \"double\" \\n",
+            "line\nnext\ttab",
+            "#fff",
+        ] {
+            let line = format_dotenv_line("KEY", value);
+            assert_eq!(
+                parse_dotenv_line(&line),
+                Some(("KEY".to_string(), value.to_string())),
+                "{line}"
+            );
+        }
+        assert_eq!(format_dotenv_line("PORT", "3000"), "PORT=3000");
+    }
+
+    #[test]
+    fn expands_escapes_only_in_double_quotes() {
+        let vars = parse_dotenv_str("A=\"line\\nnext \\\"q\\\"\"\nB='raw\\n'\n");
+        assert_eq!(vars["A"], "line\nnext \"q\"");
+        assert_eq!(vars["B"], "raw\\n");
+    }
+
+    #[test]
+    fn keeps_hash_inside_unquoted_word_and_skips_comments() {
+        let vars = parse_dotenv_str("# comment\nCOLOR=#fff\nURL=http://x/#anchor\nEMPTY= # none\n");
+        assert_eq!(vars["COLOR"], "#fff");
+        assert_eq!(vars["EMPTY"], "");
+        assert_eq!(vars["URL"], "http://x/#anchor");
+        assert_eq!(vars.len(), 3);
+    }
+
+    #[test]
+    fn reports_duplicate_keys_with_their_lines() {
+        let issues = parse_dotenv_issues(
+            "PORT=3000\nNAME=app\n# PORT=1\nexport PORT=4000\nNAME=app\n",
+            ".env",
+        );
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].key, "NAME");
+        assert_eq!(
+            issues[0].reason,
+            "defined 2 times (lines 2, 5); the last one wins"
+        );
+        assert_eq!(issues[1].key, "PORT");
+        assert_eq!(
+            issues[1].reason,
+            "defined 2 times (lines 1, 4); the last one wins"
+        );
+        assert_eq!(issues[1].source.as_deref(), Some(".env"));
+    }
+
+    #[test]
+    fn reports_empty_values_and_malformed_lines() {
+        let issues = parse_dotenv_issues("TOKEN=\nQUOTED=\"\"\noops\n=value\nOK=1\n", ".env");
+        let found = issues.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                "QUOTED: empty value (line 2) (from .env)",
+                "TOKEN: empty value (line 1) (from .env)",
+                "line 3: not a KEY=value line: `oops` (from .env)",
+                "line 4: not a KEY=value line: `=value` (from .env)",
+            ]
+        );
+        assert!(parse_dotenv_issues("# only\n\nA=1\n", ".env").is_empty());
+    }
+
+    #[test]
+    fn layered_env_prefers_later_files_and_keeps_empty_overrides() {
+        let td = tempfile::tempdir().expect("tempdir");
+        fs::write(td.path().join(".env"), "PORT=3000\nDEBUG=true\nNAME=app\n").expect("write");
+        fs::write(td.path().join(".env.local"), "PORT=4000\n").expect("write");
+        fs::write(td.path().join(".env.staging"), "DEBUG=\n").expect("write");
+
+        let env = load_layered_env(td.path(), Some("staging")).expect("load");
+        assert_eq!(env.vars.get("PORT").map(String::as_str), Some("4000"));
+        assert_eq!(env.source("PORT"), Some(".env.local"));
+        assert_eq!(env.vars.get("DEBUG").map(String::as_str), Some(""));
+        assert_eq!(env.source("DEBUG"), Some(".env.staging"));
+        assert_eq!(env.source("NAME"), Some(".env"));
+        assert_eq!(env.describe(), ".env + .env.local + .env.staging");
+
+        let base = load_layered_env(td.path(), None).expect("load");
+        assert_eq!(base.vars.get("DEBUG").map(String::as_str), Some("true"));
+    }
+
+    #[test]
+    fn diffs_against_template() {
+        let template = HashMap::from([
+            ("PORT".to_string(), "3000".to_string()),
+            ("API_KEY".to_string(), "changeme".to_string()),
+            ("DB_URL".to_string(), String::new()),
+        ]);
+        let actual = HashMap::from([
+            ("PORT".to_string(), "8080".to_string()),
+            ("API_KEY".to_string(), "changeme".to_string()),
+            ("LOCAL_ONLY".to_string(), "1".to_string()),
+        ]);
+        let diff = diff_against_template(&actual, &template);
+        assert_eq!(diff.missing, vec!["DB_URL"]);
+        assert_eq!(diff.extra, vec!["LOCAL_ONLY"]);
+        assert_eq!(diff.unchanged, vec!["API_KEY"]);
+    }
+
+    #[test]
+    fn validates_float_and_port() {
+        assert!(check_value("float", "1.5").is_ok());
+        assert!(check_value("float", "NaN").is_err());
+        assert!(check_value("port", "8080").is_ok());
+        assert!(check_value("port", "0").is_err());
+        assert!(check_value("port", "70000").is_err());
+    }
+
+    #[test]
+    fn validates_url_requires_scheme_and_host() {
+        assert!(check_value("url", "postgres://localhost:5432/db").is_ok());
+        assert!(check_value("url", "localhost:5432").is_err());
+        assert!(check_value("url", "mailto:me@example.com").is_err());
+    }
+
+    #[test]
+    fn validates_enum_with_descriptive_reason() {
+        assert!(check_value("enum:dev|prod", "dev").is_ok());
+        let reason = check_value("enum:dev|prod", "qa").expect_err("not a variant");
+        assert_eq!(reason, "expected one of dev|prod, got qa");
+        assert!(check_value("enum:", "dev")
+            .expect_err("malformed")
+            .contains("no variants"));
+    }
+
+    #[test]
+    fn validates_regex_and_rejects_bad_patterns() {
+        assert!(check_value("regex:^sk_[a-z]+$", "sk_live").is_ok());
+        assert!(check_value("regex:^sk_[a-z]+$", "pk_live").is_err());
+        assert!(check_value("regex:(", "x")
+            .expect_err("malformed")
+            .contains("bad regex"));
+    }
+
+    #[test]
+    fn rejects_unknown_schema_type() {
+        assert!(check_value("strnig", "x")
+            .expect_err("unknown")
+            .contains("unknown type"));
+    }
+
+    #[test]
+    fn parses_schema_spec_suffixes() {
+        assert_eq!(
+            SchemaSpec::parse("int"),
+            SchemaSpec {
+                typ: "int".into(),
+                optional: false,
+                default: None
+            }
+        );
+        let spec = SchemaSpec::parse("port? default=8080");
+        assert_eq!(spec.typ, "port");
+        assert!(spec.optional);
+        assert_eq!(spec.fill_value(), "8080");
+        let spec = SchemaSpec::parse("string default=hello world");
+        assert_eq!(spec.default.as_deref(), Some("hello world"));
+        assert_eq!(SchemaSpec::parse("bool").fill_value(), "false");
+    }
+
+    #[test]
+    fn optional_absent_keys_are_not_flagged() {
+        let schema = parse_schema(&HashMap::from([
+            ("DEBUG".to_string(), "bool?".to_string()),
+            ("PORT".to_string(), "port default=8080".to_string()),
+            ("RATE".to_string(), "float? default=abc".to_string()),
+        ]));
+        let issues = validate_env_schema(&schema, &HashMap::new());
+        let reasons = issues
+            .iter()
+            .map(|i| format!("{}: {}", i.key, i.reason))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![
+                "PORT: missing (default 8080)",
+                "RATE: invalid schema default: expected float, got abc"
+            ]
+        );
+    }
+
     #[test]
     fn validates_int_type() {
         let mut schema = HashMap::new();
-        schema.insert("PORT".to_string(), "int".to_string());
+        schema.insert("PORT".to_string(), SchemaSpec::parse("int"));
         let mut actual = HashMap::new();
         actual.insert("PORT".to_string(), "abc".to_string());
         let issues = validate_env_schema(&schema, &actual);
         assert_eq!(issues.len(), 1);
     }
+
+    #[test]
+    fn command_program_skips_assignments_builtins_and_paths() {
+        assert_eq!(
+            command_program("docker compose up -d").as_deref(),
+            Some("docker")
+        );
+        assert_eq!(
+            command_program("PORT=3000 NODE_ENV=dev npm start").as_deref(),
+            Some("npm")
+        );
+        assert_eq!(command_program("cd web && npm start"), None);
+        assert_eq!(command_program("./scripts/dev.sh"), None);
+        assert_eq!(command_program("'unterminated"), None);
+    }
+
+    #[test]
+    fn path_issues_follow_the_language_and_config() {
+        let cfg: DevflowConfig = serde_yaml::from_str(
+            "start_commands: [\"docker compose up -d\"]\nservices:\n  - name: api\n    command: cargo run\n  - name: db\n    command: postgres -D data\n",
+        )
+        .unwrap();
+        let installed = ["cargo", "postgres"];
+        let issues = path_issues(Language::Rust, Some(&cfg), true, |t| installed.contains(&t));
+        let summary: Vec<_> = issues
+            .iter()
+            .map(|i| (i.severity, i.tool.as_str()))
+            .collect();
+        // No Python or Node checks in a Rust project; cargo is found.
+        assert_eq!(
+            summary,
+            [(Severity::Error, "rustc"), (Severity::Error, "docker")]
+        );
+        assert_eq!(
+            issues[1].reason,
+            "docker not found in PATH (used by start_commands)"
+        );
+
+        let issues = path_issues(Language::Python, None, false, |t| t == "python");
+        let summary: Vec<_> = issues
+            .iter()
+            .map(|i| (i.severity, i.tool.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (Severity::Error, "PATH"),
+                (Severity::Warning, "pip3"),
+                (Severity::Warning, "pytest")
+            ]
+        );
+        assert!(path_issues(Language::Unknown, None, true, |_| false).is_empty());
+    }
 }
@@ -0,0 +1,244 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use std::{collections::HashMap, env, fs, path::Path};
+
+use super::envcheck::parse_dotenv_line;
+
+/// Environment variable holding the base64 key for `env encrypt`/`decrypt`.
+pub const ENV_KEY_VAR: &str = "DEVFLOW_ENV_KEY";
+
+/// Marks an encrypted value; the version is bumped if the format changes.
+const PREFIX: &str = "enc:v1:";
+
+const NONCE_LEN: usize = 12;
+
+/// A 256-bit ChaCha20-Poly1305 key.
+pub struct EnvKey(ChaCha20Poly1305);
+
+impl EnvKey {
+    /// Reads the key from `key_file` if given, otherwise from
+    /// `DEVFLOW_ENV_KEY`. Either holds 32 bytes in base64, e.g. the output
+    /// of `openssl rand -base64 32`.
+    pub fn load(key_file: Option<&Path>) -> Result<Self> {
+        let (encoded, source) = match key_file {
+            Some(path) => (
+                fs::read_to_string(path)
+                    .with_context(|| format!("could not read key file {}", path.display()))?,
+                path.display().to_string(),
+            ),
+            None => match env::var(ENV_KEY_VAR) {
+                Ok(key) => (key, ENV_KEY_VAR.to_string()),
+                Err(_) => bail!(
+                    "no encryption key: set {ENV_KEY_VAR} or pass --key-file PATH \
+                     (create one with `openssl rand -base64 32`)"
+                ),
+            },
+        };
+        Self::parse(encoded.trim()).with_context(|| format!("invalid key in {source}"))
+    }
+
+    fn parse(encoded: &str) -> Result<Self> {
+        let bytes = STANDARD.decode(encoded).context("not valid base64")?;
+        if bytes.len() != 32 {
+            bail!("expected 32 bytes, got {}", bytes.len());
+        }
+        Ok(Self(ChaCha20Poly1305::new(Key::from_slice(&bytes))))
+    }
+
+    /// `enc:v1:<base64 of nonce + ciphertext>`. The key name is
+    /// authenticated too, so a value moved to another key won't decrypt.
+    fn seal(&self, key: &str, value: &str) -> String {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = self
+            .0
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: key.as_bytes(),
+                },
+            )
+            .expect("encrypting into a Vec can't fail");
+        let mut bytes = nonce.to_vec();
+        bytes.extend(sealed);
+        format!("{PREFIX}{}", STANDARD.encode(bytes))
+    }
+
+    /// The plaintext of a `seal`ed value, or `None` when it was sealed with
+    /// another key, for another key name, or has been tampered with.
+    fn open(&self, key: &str, sealed: &str) -> Option<String> {
+        let bytes = STANDARD.decode(sealed.strip_prefix(PREFIX)?).ok()?;
+        if bytes.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let plain = self
+            .0
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: key.as_bytes(),
+                },
+            )
+            .ok()?;
+        String::from_utf8(plain).ok()
+    }
+}
+
+/// Splits a dotenv line into everything up to and including the first `=`,
+/// the key, and the raw text after the `=`. `None` for comments, blank and
+/// malformed lines.
+fn split_assignment(line: &str) -> Option<(&str, String, &str)> {
+    let (key, _) = parse_dotenv_line(line)?;
+    let eq = line.find('=')?;
+    Some((&line[..=eq], key, &line[eq + 1..]))
+}
+
+/// The result of encrypting an env file.
+pub struct Encrypted {
+    pub content: String,
+    /// Keys whose value was (re-)encrypted.
+    pub changed: Vec<String>,
+    /// Keys whose value matched `previous`, so its ciphertext was kept.
+    pub unchanged: Vec<String>,
+}
+
+/// Encrypts every value in the dotenv `plain`, keeping keys, comments and
+/// blank lines as they are so the encrypted file diffs by key. The raw text
+/// after each `=` (quotes and inline comments included) is what gets
+/// encrypted, so decrypting restores the file exactly.
+///
+/// Values that `previous` (the current encrypted file, if any) already holds
+/// under this key are kept byte for byte, so re-encrypting only changes the
+/// lines whose values changed.
+pub fn encrypt_env(plain: &str, key: &EnvKey, previous: Option<&str>) -> Encrypted {
+    let previous = previous
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(split_assignment)
+                .map(|(_, name, sealed)| (name, sealed.to_string()))
+                .collect::<HashMap<_, _>>()
+        })
+        .unwrap_or_default();
+
+    let mut out = Encrypted {
+        content: String::with_capacity(plain.len() * 2),
+        changed: Vec::new(),
+        unchanged: Vec::new(),
+    };
+    for line in plain.lines() {
+        match split_assignment(line) {
+            Some((head, name, raw)) => {
+                let kept = previous
+                    .get(&name)
+                    .filter(|sealed| key.open(&name, sealed).as_deref() == Some(raw));
+                let sealed = match kept {
+                    Some(sealed) => {
+                        out.unchanged.push(name);
+                        sealed.clone()
+                    }
+                    None => {
+                        let sealed = key.seal(&name, raw);
+                        out.changed.push(name);
+                        sealed
+                    }
+                };
+                out.content.push_str(head);
+                out.content.push_str(&sealed);
+            }
+            None => out.content.push_str(line),
+        }
+        out.content.push('\n');
+    }
+    out
+}
+
+/// Reverses [`encrypt_env`], returning the plain file and its keys. Fails
+/// without partial output if any value doesn't decrypt with `key`.
+pub fn decrypt_env(encrypted: &str, key: &EnvKey) -> Result<(String, Vec<String>)> {
+    let mut plain = String::with_capacity(encrypted.len());
+    let mut keys = Vec::new();
+    for (index, line) in encrypted.lines().enumerate() {
+        match split_assignment(line) {
+            Some((head, name, sealed)) => {
+                if !sealed.starts_with(PREFIX) {
+                    bail!(
+                        "line {}: {name} is not encrypted (expected a `{PREFIX}` value)",
+                        index + 1
+                    );
+                }
+                let Some(value) = key.open(&name, sealed) else {
+                    bail!(
+                        "could not decrypt {name} (line {}): wrong key, or the value was \
+                         edited or moved from another key",
+                        index + 1
+                    );
+                };
+                plain.push_str(head);
+                plain.push_str(&value);
+                keys.push(name);
+            }
+            None => plain.push_str(line),
+        }
+        plain.push('\n');
+    }
+    Ok((plain, keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(byte: u8) -> EnvKey {
+        EnvKey::parse(&STANDARD.encode([byte; 32])).expect("key")
+    }
+
+    #[test]
+    fn round_trips_and_keeps_keys_and_comments_readable() {
+        let plain = "# db\nexport DATABASE_URL=\"postgres://u:p@h/db\" # local\n\nPORT=3000\n";
+        let encrypted = encrypt_env(plain, &key(1), None);
+        assert_eq!(encrypted.changed, ["DATABASE_URL", "PORT"]);
+        assert!(encrypted
+            .content
+            .starts_with("# db\nexport DATABASE_URL=enc:v1:"));
+        assert!(encrypted.content.contains("\n\nPORT=enc:v1:"));
+        assert!(!encrypted.content.contains("3000"));
+
+        let (decrypted, keys) = decrypt_env(&encrypted.content, &key(1)).expect("decrypt");
+        assert_eq!(decrypted, plain);
+        assert_eq!(keys, ["DATABASE_URL", "PORT"]);
+    }
+
+    #[test]
+    fn refuses_the_wrong_key_and_swapped_values() {
+        let encrypted = encrypt_env("A=1\nB=2\n", &key(1), None).content;
+        let err = decrypt_env(&encrypted, &key(2)).unwrap_err();
+        assert!(err.to_string().contains("could not decrypt A"), "{err}");
+
+        let (a, b) = encrypted.split_once('\n').expect("two lines");
+        let swapped = format!("A={}\nB={}\n", &b[2..], &a[2..]);
+        assert!(decrypt_env(&swapped, &key(1)).is_err());
+    }
+
+    #[test]
+    fn reencrypting_keeps_ciphertext_of_unchanged_values() {
+        let first = encrypt_env("A=1\nB=2\n", &key(1), None).content;
+        let second = encrypt_env("A=1\nB=3\n", &key(1), Some(&first));
+        assert_eq!(second.unchanged, ["A"]);
+        assert_eq!(second.changed, ["B"]);
+        assert_eq!(first.lines().next(), second.content.lines().next());
+        assert_ne!(first.lines().nth(1), second.content.lines().nth(1));
+    }
+
+    #[test]
+    fn rejects_keys_of_the_wrong_size() {
+        assert!(EnvKey::parse(&STANDARD.encode([0u8; 16])).is_err());
+        assert!(EnvKey::parse("not base64!").is_err());
+    }
+}
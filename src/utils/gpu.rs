@@ -0,0 +1,110 @@
+use serde::Serialize;
+
+/// One GPU's utilization and memory at the time it was read.
+#[derive(Debug, Clone, Serialize)]
+pub struct GpuReading {
+    pub name: String,
+    pub utilization_percent: u32,
+    pub memory_used_bytes: u64,
+    pub memory_total_bytes: u64,
+}
+
+impl GpuReading {
+    pub fn memory_pct(&self) -> f64 {
+        if self.memory_total_bytes > 0 {
+            self.memory_used_bytes as f64 / self.memory_total_bytes as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A vendor library that can report on the GPUs it drives. Each vendor is
+/// one implementation, found by [`detect`].
+pub trait GpuSource {
+    /// One reading per GPU; GPUs that can't be read right now are left out.
+    fn read(&self) -> Vec<GpuReading>;
+}
+
+/// The GPU sources compiled into this build whose driver library loads on
+/// this machine. Empty without the `gpu` feature or without a GPU.
+pub fn detect() -> Vec<Box<dyn GpuSource>> {
+    nvidia().into_iter().collect()
+}
+
+pub fn read_all(sources: &[Box<dyn GpuSource>]) -> Vec<GpuReading> {
+    sources.iter().flat_map(|source| source.read()).collect()
+}
+
+#[cfg(feature = "gpu")]
+fn nvidia() -> Option<Box<dyn GpuSource>> {
+    let nvml = nvml_wrapper::Nvml::init().ok()?;
+    Some(Box::new(Nvidia(nvml)))
+}
+
+#[cfg(not(feature = "gpu"))]
+fn nvidia() -> Option<Box<dyn GpuSource>> {
+    None
+}
+
+/// NVIDIA GPUs through NVML, which ships with the driver.
+#[cfg(feature = "gpu")]
+struct Nvidia(nvml_wrapper::Nvml);
+
+#[cfg(feature = "gpu")]
+impl GpuSource for Nvidia {
+    fn read(&self) -> Vec<GpuReading> {
+        let count = self.0.device_count().unwrap_or(0);
+        (0..count)
+            .filter_map(|index| {
+                let device = self.0.device_by_index(index).ok()?;
+                let memory = device.memory_info().ok()?;
+                Some(GpuReading {
+                    name: device.name().unwrap_or_else(|_| format!("GPU {index}")),
+                    utilization_percent: device.utilization_rates().ok()?.gpu,
+                    memory_used_bytes: memory.used,
+                    memory_total_bytes: memory.total,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(Vec<GpuReading>);
+
+    impl GpuSource for Fixed {
+        fn read(&self) -> Vec<GpuReading> {
+            self.0.clone()
+        }
+    }
+
+    fn reading(name: &str, used: u64, total: u64) -> GpuReading {
+        GpuReading {
+            name: name.into(),
+            utilization_percent: 40,
+            memory_used_bytes: used,
+            memory_total_bytes: total,
+        }
+    }
+
+    #[test]
+    fn readings_from_every_source_are_combined() {
+        let sources: Vec<Box<dyn GpuSource>> = vec![
+            Box::new(Fixed(vec![reading("a", 1, 4), reading("b", 2, 4)])),
+            Box::new(Fixed(Vec::new())),
+            Box::new(Fixed(vec![reading("c", 3, 4)])),
+        ];
+        let names: Vec<_> = read_all(&sources).into_iter().map(|gpu| gpu.name).collect();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn memory_is_a_percentage_of_the_total() {
+        assert_eq!(reading("a", 1, 4).memory_pct(), 25.0);
+        assert_eq!(reading("a", 0, 0).memory_pct(), 0.0);
+    }
+}
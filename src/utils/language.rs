@@ -1,40 +1,322 @@
-use std::{fs, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum Language {
     Python,
     Node,
     Go,
     Rust,
+    Java,
+    Ruby,
+    Php,
+    #[default]
+    #[value(skip)]
     Unknown,
 }
 
-pub fn detect_project_language(root: &Path) -> Language {
-    if root.join("pyproject.toml").exists() || root.join("requirements.txt").exists() {
-        return Language::Python;
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Language::Python => "python",
+            Language::Node => "node",
+            Language::Go => "go",
+            Language::Rust => "rust",
+            Language::Java => "java",
+            Language::Ruby => "ruby",
+            Language::Php => "php",
+            Language::Unknown => "unknown",
+        })
+    }
+}
+
+/// JavaScript package manager of a Node project.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodePackageManager {
+    #[default]
+    Npm,
+    Yarn,
+    Pnpm,
+    Bun,
+}
+
+impl NodePackageManager {
+    const ALL: [NodePackageManager; 4] = [
+        NodePackageManager::Npm,
+        NodePackageManager::Yarn,
+        NodePackageManager::Pnpm,
+        NodePackageManager::Bun,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NodePackageManager::Npm => "npm",
+            NodePackageManager::Yarn => "yarn",
+            NodePackageManager::Pnpm => "pnpm",
+            NodePackageManager::Bun => "bun",
+        }
+    }
+
+    /// Lock files the manager writes, newest format first.
+    pub fn lockfiles(self) -> &'static [&'static str] {
+        match self {
+            NodePackageManager::Npm => &["package-lock.json"],
+            NodePackageManager::Yarn => &["yarn.lock"],
+            NodePackageManager::Pnpm => &["pnpm-lock.yaml"],
+            NodePackageManager::Bun => &["bun.lock", "bun.lockb"],
+        }
+    }
+
+    /// Program and leading arguments that run a package binary, like `npx`.
+    pub fn exec(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            NodePackageManager::Npm => ("npx", &[]),
+            NodePackageManager::Yarn => ("yarn", &[]),
+            NodePackageManager::Pnpm => ("pnpm", &["exec"]),
+            NodePackageManager::Bun => ("bunx", &[]),
+        }
     }
-    if root.join("package.json").exists() {
-        return Language::Node;
+}
+
+impl fmt::Display for NodePackageManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
     }
-    if root.join("go.mod").exists() {
-        return Language::Go;
+}
+
+/// The package manager a Node project uses: the one named by the
+/// `packageManager` field of `package.json` (e.g. `pnpm@8.15.0`), else the
+/// one whose lock file is present. npm when there is no lock file, or lock
+/// files of several managers.
+pub fn detect_node_package_manager(root: &Path) -> NodePackageManager {
+    let declared = fs::read_to_string(root.join("package.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+        .and_then(|pkg| {
+            let field = pkg["packageManager"].as_str()?.to_string();
+            let name = field.split('@').next().unwrap_or_default().to_string();
+            NodePackageManager::ALL
+                .into_iter()
+                .find(|manager| manager.name() == name)
+        });
+    if let Some(manager) = declared {
+        return manager;
     }
-    if root.join("Cargo.toml").exists() {
-        return Language::Rust;
+    let locked = NodePackageManager::ALL
+        .into_iter()
+        .filter(|manager| {
+            manager
+                .lockfiles()
+                .iter()
+                .any(|file| root.join(file).exists())
+        })
+        .collect::<Vec<_>>();
+    match locked.as_slice() {
+        [manager] => *manager,
+        _ => NodePackageManager::Npm,
     }
-    Language::Unknown
 }
 
-pub fn expected_toolchain_hint(root: &Path) -> Option<String> {
-    for file in [".nvmrc", "rust-toolchain", "go.mod", "pyproject.toml"] {
-        let path = root.join(file);
-        if path.exists() {
-            if let Ok(c) = fs::read_to_string(path) {
-                return Some(c.lines().next().unwrap_or_default().trim().to_string());
+/// Marker files for each language, in detection priority order.
+const MARKERS: [(&str, Language); 10] = [
+    ("pyproject.toml", Language::Python),
+    ("requirements.txt", Language::Python),
+    ("package.json", Language::Node),
+    ("go.mod", Language::Go),
+    ("Cargo.toml", Language::Rust),
+    ("pom.xml", Language::Java),
+    ("build.gradle", Language::Java),
+    ("build.gradle.kts", Language::Java),
+    ("Gemfile", Language::Ruby),
+    ("composer.json", Language::Php),
+];
+
+/// Subdirectories that hold dependencies or build output, not projects.
+const SKIPPED_DIRS: [&str; 3] = ["node_modules", "target", "vendor"];
+
+/// The project's main language: the first marker found in `root` itself.
+pub fn detect_project_language(root: &Path) -> Language {
+    detect_project_languages(root)
+        .into_iter()
+        .find(|(_, marker)| marker.parent() == Some(root))
+        .map_or(Language::Unknown, |(language, _)| language)
+}
+
+/// Every language with a marker in `root` or one of its immediate
+/// subdirectories (for monorepos), with the marker file that gave it away.
+/// `root` comes first, then subdirectories by name; hidden and dependency
+/// directories are skipped. Each language is listed once per directory.
+pub fn detect_project_languages(root: &Path) -> Vec<(Language, PathBuf)> {
+    let mut subdirs = fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref())
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    subdirs.sort();
+
+    let mut found = Vec::new();
+    for dir in std::iter::once(root.to_path_buf()).chain(subdirs) {
+        let mut seen = Vec::new();
+        for (file, language) in MARKERS {
+            let marker = dir.join(file);
+            if !seen.contains(&language) && marker.is_file() {
+                seen.push(language);
+                found.push((language, marker));
             }
         }
     }
-    None
+    found
+}
+
+/// A toolchain version the project asks for, e.g. `node` `18.17.0` from
+/// `.nvmrc`. `version` is as written: a version, a range such as `>=3.10`,
+/// or a channel such as `stable`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolchainHint {
+    /// Binary the version applies to: `node`, `rustc`, `go` or `python`.
+    pub tool: &'static str,
+    pub version: String,
+    /// File the version was read from.
+    pub source: &'static str,
+}
+
+impl ToolchainHint {
+    /// Whether `installed` (e.g. `1.78.0`) satisfies the hint, or `None`
+    /// when either isn't a comparable version (`stable`, `lts/iron`). A
+    /// bare version pins the parts it names (`18` is any 18.x), except for
+    /// Go, whose `go` directive is a minimum. Ranges use semver rules, with
+    /// PEP 440's `==` and `~=` translated.
+    pub fn satisfied_by(&self, installed: &str) -> Option<bool> {
+        let installed = semver::Version::parse(&full_version(installed)?).ok()?;
+        let requirement = version_requirement(self.tool, &self.version)?;
+        Some(requirement.matches(&installed))
+    }
+}
+
+/// `1.22` as `1.22.0`, so that semver can parse it.
+fn full_version(version: &str) -> Option<String> {
+    let mut parts = version.split('.').collect::<Vec<_>>();
+    if parts.len() > 3 || parts.iter().any(|p| p.parse::<u64>().is_err()) {
+        return None;
+    }
+    parts.resize(3, "0");
+    Some(parts.join("."))
+}
+
+fn version_requirement(tool: &str, version: &str) -> Option<semver::VersionReq> {
+    let version = version.trim();
+    if version.starts_with(|c: char| c.is_ascii_digit()) {
+        full_version(version)?;
+        let op = if tool == "go" { ">=" } else { "=" };
+        return semver::VersionReq::parse(&format!("{op}{version}")).ok();
+    }
+    let clauses = version
+        .split(',')
+        .map(str::trim)
+        // semver has no exclusions; `!=3.11.2` rarely decides the outcome.
+        .filter(|clause| !clause.is_empty() && !clause.starts_with("!="))
+        .map(|clause| {
+            let clause = clause.trim_end_matches(".*");
+            if let Some(rest) = clause.strip_prefix("~=") {
+                // `~=3.10` allows any 3.x from 3.10; `~=3.10.2` any 3.10.x.
+                let op = if rest.split('.').count() == 2 {
+                    "^"
+                } else {
+                    "~"
+                };
+                format!("{op}{rest}")
+            } else if let Some(rest) = clause.strip_prefix("==") {
+                format!("={rest}")
+            } else {
+                clause.to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+    semver::VersionReq::parse(&clauses.join(", ")).ok()
+}
+
+impl fmt::Display for ToolchainHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ({})", self.tool, self.version, self.source)
+    }
+}
+
+/// The first toolchain version found in `.nvmrc`, `rust-toolchain.toml`,
+/// `rust-toolchain`, `go.mod` or `pyproject.toml`. Files that exist but
+/// don't name a version are skipped.
+pub fn expected_toolchain_hint(root: &Path) -> Option<ToolchainHint> {
+    type Parser = fn(&str) -> Option<String>;
+    let files: [(&str, &str, Parser); 5] = [
+        (".nvmrc", "node", nvmrc_version),
+        ("rust-toolchain.toml", "rustc", rust_toolchain_channel),
+        ("rust-toolchain", "rustc", rust_toolchain_channel),
+        ("go.mod", "go", go_directive),
+        ("pyproject.toml", "python", requires_python),
+    ];
+    files.into_iter().find_map(|(source, tool, parse)| {
+        let content = fs::read_to_string(root.join(source)).ok()?;
+        Some(ToolchainHint {
+            tool,
+            version: parse(&content)?,
+            source,
+        })
+    })
+}
+
+/// First non-comment line, without a leading `v` (`v18.17.0`, `lts/iron`).
+fn nvmrc_version(content: &str) -> Option<String> {
+    let line = content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .find(|line| !line.is_empty())?;
+    Some(line.strip_prefix('v').unwrap_or(line).to_string())
+}
+
+/// `[toolchain] channel` of a TOML toolchain file, or the whole of a legacy
+/// one-line `rust-toolchain` file.
+fn rust_toolchain_channel(content: &str) -> Option<String> {
+    if let Ok(toml) = content.parse::<toml::Table>() {
+        return toml
+            .get("toolchain")?
+            .get("channel")?
+            .as_str()
+            .map(str::to_string);
+    }
+    let line = content.trim();
+    (!line.is_empty() && !line.contains(char::is_whitespace)).then(|| line.to_string())
+}
+
+/// Version of the `go` directive in `go.mod`.
+fn go_directive(content: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let version = line.trim().strip_prefix("go ")?;
+        Some(version.split("//").next()?.trim().to_string())
+    })
+}
+
+/// `[project] requires-python`, or Poetry's `python` dependency.
+fn requires_python(content: &str) -> Option<String> {
+    let toml = content.parse::<toml::Table>().ok()?;
+    let project = toml.get("project").and_then(|p| p.get("requires-python"));
+    let poetry = || {
+        toml.get("tool")?
+            .get("poetry")?
+            .get("dependencies")?
+            .get("python")
+    };
+    project.or_else(poetry)?.as_str().map(str::to_string)
 }
 
 #[cfg(test)]
@@ -48,4 +330,181 @@ mod tests {
         std::fs::write(dir.path().join("Cargo.toml"), "[package]\nname='a'\n").expect("write");
         assert_eq!(detect_project_language(dir.path()), Language::Rust);
     }
+
+    #[test]
+    fn detects_java() {
+        let dir = tempdir().expect("tempdir");
+        std::fs::write(dir.path().join("build.gradle.kts"), "plugins {}\n").expect("write");
+        assert_eq!(detect_project_language(dir.path()), Language::Java);
+    }
+
+    #[test]
+    fn detects_ruby() {
+        let dir = tempdir().expect("tempdir");
+        std::fs::write(
+            dir.path().join("Gemfile"),
+            "source 'https://rubygems.org'\n",
+        )
+        .expect("write");
+        assert_eq!(detect_project_language(dir.path()), Language::Ruby);
+    }
+
+    #[test]
+    fn detects_php() {
+        let dir = tempdir().expect("tempdir");
+        std::fs::write(dir.path().join("composer.json"), "{}").expect("write");
+        assert_eq!(detect_project_language(dir.path()), Language::Php);
+    }
+
+    #[test]
+    fn detects_unknown_without_markers() {
+        let dir = tempdir().expect("tempdir");
+        std::fs::write(dir.path().join("README.md"), "# app\n").expect("write");
+        assert_eq!(detect_project_language(dir.path()), Language::Unknown);
+    }
+
+    #[test]
+    fn detects_node_package_manager() {
+        let dir = tempdir().expect("tempdir");
+        let root = dir.path();
+        std::fs::write(root.join("package.json"), "{}").expect("write");
+        assert_eq!(detect_node_package_manager(root), NodePackageManager::Npm);
+
+        std::fs::write(root.join("pnpm-lock.yaml"), "").expect("write");
+        assert_eq!(detect_node_package_manager(root), NodePackageManager::Pnpm);
+
+        // Lock files of two managers: ambiguous.
+        std::fs::write(root.join("yarn.lock"), "").expect("write");
+        assert_eq!(detect_node_package_manager(root), NodePackageManager::Npm);
+
+        std::fs::write(
+            root.join("package.json"),
+            r#"{"packageManager": "yarn@4.1.0"}"#,
+        )
+        .expect("write");
+        assert_eq!(detect_node_package_manager(root), NodePackageManager::Yarn);
+    }
+
+    #[test]
+    fn reads_toolchain_versions_from_each_format() {
+        assert_eq!(
+            nvmrc_version("# pinned\nv18.17.0\n").as_deref(),
+            Some("18.17.0")
+        );
+        assert_eq!(nvmrc_version("lts/iron").as_deref(), Some("lts/iron"));
+        assert_eq!(
+            rust_toolchain_channel(
+                "[toolchain]\nchannel = \"1.78.0\"\ncomponents = [\"clippy\"]\n"
+            )
+            .as_deref(),
+            Some("1.78.0")
+        );
+        assert_eq!(
+            rust_toolchain_channel("nightly-2024-05-01\n").as_deref(),
+            Some("nightly-2024-05-01")
+        );
+        assert_eq!(
+            go_directive("module example.com/app\n\ngo 1.22.1 // minimum\n").as_deref(),
+            Some("1.22.1")
+        );
+        assert_eq!(
+            requires_python("[build-system]\nrequires = [\"hatchling\"]\n\n[project]\nname = \"app\"\nrequires-python = \">=3.10\"\n")
+                .as_deref(),
+            Some(">=3.10")
+        );
+        assert_eq!(
+            requires_python("[tool.poetry.dependencies]\npython = \"^3.11\"\n").as_deref(),
+            Some("^3.11")
+        );
+        assert_eq!(requires_python("[build-system]\nrequires = []\n"), None);
+    }
+
+    #[test]
+    fn compares_installed_version_with_hint() {
+        let hint = |tool, version: &str| ToolchainHint {
+            tool,
+            version: version.into(),
+            source: "test",
+        };
+        assert_eq!(hint("rustc", "1.78.0").satisfied_by("1.78.0"), Some(true));
+        assert_eq!(hint("rustc", "1.78").satisfied_by("1.70.0"), Some(false));
+        assert_eq!(hint("node", "18").satisfied_by("18.17.1"), Some(true));
+        assert_eq!(hint("node", "18").satisfied_by("20.11.0"), Some(false));
+        assert_eq!(hint("go", "1.22").satisfied_by("1.23.0"), Some(true));
+        assert_eq!(hint("go", "1.22").satisfied_by("1.21.5"), Some(false));
+        assert_eq!(
+            hint("python", ">=3.10,<4").satisfied_by("3.12.1"),
+            Some(true)
+        );
+        assert_eq!(hint("python", "^3.11").satisfied_by("3.10.4"), Some(false));
+        assert_eq!(hint("python", "~=3.10").satisfied_by("3.12.0"), Some(true));
+        assert_eq!(
+            hint("python", "==3.11.*").satisfied_by("3.11.7"),
+            Some(true)
+        );
+        assert_eq!(hint("rustc", "stable").satisfied_by("1.78.0"), None);
+        assert_eq!(hint("node", "lts/iron").satisfied_by("20.11.0"), None);
+    }
+
+    #[test]
+    fn toolchain_hint_skips_files_without_a_version() {
+        let dir = tempdir().expect("tempdir");
+        std::fs::write(dir.path().join("go.mod"), "module example.com/app\n").expect("write");
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            "[project]\nrequires-python = \">=3.12\"\n",
+        )
+        .expect("write");
+        let hint = expected_toolchain_hint(dir.path()).expect("hint");
+        assert_eq!(
+            hint,
+            ToolchainHint {
+                tool: "python",
+                version: ">=3.12".into(),
+                source: "pyproject.toml",
+            }
+        );
+        assert_eq!(hint.to_string(), "python >=3.12 (pyproject.toml)");
+    }
+
+    #[test]
+    fn detects_every_language_in_a_monorepo() {
+        let dir = tempdir().expect("tempdir");
+        let write = |path: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).expect("mkdir");
+            std::fs::write(path, "").expect("write");
+        };
+        write("package.json");
+        write("backend/Cargo.toml");
+        write("ml/pyproject.toml");
+        write("ml/requirements.txt");
+        write("node_modules/left-pad/package.json");
+        write(".cache/go.mod");
+
+        let found = detect_project_languages(dir.path())
+            .into_iter()
+            .map(|(language, marker)| {
+                let marker = marker.strip_prefix(dir.path()).unwrap().to_path_buf();
+                (language, marker)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                (Language::Node, PathBuf::from("package.json")),
+                (Language::Rust, PathBuf::from("backend/Cargo.toml")),
+                (Language::Python, PathBuf::from("ml/pyproject.toml")),
+            ]
+        );
+        assert_eq!(detect_project_language(dir.path()), Language::Node);
+    }
+
+    #[test]
+    fn subdirectory_markers_do_not_change_the_main_language() {
+        let dir = tempdir().expect("tempdir");
+        std::fs::create_dir(dir.path().join("tools")).expect("mkdir");
+        std::fs::write(dir.path().join("tools/go.mod"), "").expect("write");
+        assert_eq!(detect_project_language(dir.path()), Language::Unknown);
+    }
 }
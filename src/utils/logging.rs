@@ -0,0 +1,211 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tracing::{level_filters::LevelFilter, Metadata};
+
+use super::color::ColorChoice;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{
+    filter::{filter_fn, EnvFilter},
+    fmt,
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    Layer,
+};
+
+/// Target of the event that records how a command ended. It only goes to the
+/// log file: on the console the error is already printed by `main`.
+pub const RESULT_TARGET: &str = "devflow::result";
+
+/// What the log file records when `RUST_LOG` isn't set.
+const DEFAULT_FILE_FILTER: &str = "devflow=info";
+
+/// The console level for `-q` and `-v`/`-vv`/`-vvv`, or `None` without
+/// either so `RUST_LOG` alone decides.
+pub fn verbosity_level(verbose: u8, quiet: bool) -> Option<LevelFilter> {
+    match (quiet, verbose) {
+        (true, _) => Some(LevelFilter::ERROR),
+        (false, 0) => None,
+        (false, 1) => Some(LevelFilter::INFO),
+        (false, 2) => Some(LevelFilter::DEBUG),
+        (false, _) => Some(LevelFilter::TRACE),
+    }
+}
+
+/// `RUST_LOG`, `warn` when it is unset, with devflow's own events set to
+/// `level` if one is given. More specific `RUST_LOG` directives, such as
+/// `devflow::plugin=trace`, still win.
+fn console_filter(level: Option<LevelFilter>) -> EnvFilter {
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::WARN.into())
+        .from_env_lossy();
+    match level {
+        Some(level) => {
+            filter.add_directive(format!("devflow={level}").parse().expect("valid directive"))
+        }
+        None => filter,
+    }
+}
+
+/// Installs the global subscriber: devflow's diagnostics on stderr, filtered
+/// by `RUST_LOG` and `level` (see [`console_filter`]) and colored per
+/// `color`, and, when `log_file`
+/// is given, every `info` and above also appended to it in the
+/// `<timestamp> <LEVEL> <target>: <message>` form `devflow logs` reads. The
+/// file is rotated daily.
+///
+/// Keep the returned guard alive until exit; dropping it flushes the file.
+pub fn init(
+    log_file: Option<&Path>,
+    level: Option<LevelFilter>,
+    color: ColorChoice,
+) -> Result<Option<WorkerGuard>> {
+    let console = fmt::layer()
+        .with_writer(io::stderr)
+        .with_ansi(color.enabled(&io::stderr()))
+        .without_time()
+        .with_filter(console_filter(level))
+        .with_filter(filter_fn(|meta: &Metadata<'_>| {
+            meta.target() != RESULT_TARGET
+        }));
+
+    let (file, guard) = match log_file {
+        Some(path) => {
+            let (writer, guard) = tracing_appender::non_blocking(DailyFile::open(path)?);
+            let filter = EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILE_FILTER));
+            let layer = fmt::layer()
+                .with_writer(writer)
+                .with_ansi(false)
+                .with_filter(filter);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(console)
+        .with(file)
+        .init();
+    Ok(guard)
+}
+
+/// An append-only log file that, on the first write of a new day, is renamed
+/// to `<name>.<YYYY-MM-DD>` of the day it covered and started afresh. The
+/// live file keeps its name, so `devflow logs --follow` sees an ordinary
+/// rotation.
+struct DailyFile {
+    path: PathBuf,
+    file: File,
+    day: NaiveDate,
+}
+
+impl DailyFile {
+    fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("could not create {}", dir.display()))?;
+        }
+        let today = Local::now().date_naive();
+        // A file left over from an earlier day is rotated before we append.
+        let last_written = fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .map(|at| DateTime::<Local>::from(at).date_naive());
+        if let Some(day) = last_written.ok().filter(|day| *day < today) {
+            rotate(path, day)
+                .with_context(|| format!("could not rotate log file {}", path.display()))?;
+        }
+        Ok(Self {
+            file: append(path)
+                .with_context(|| format!("could not open log file {}", path.display()))?,
+            path: path.to_path_buf(),
+            day: today,
+        })
+    }
+}
+
+impl Write for DailyFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let today = Local::now().date_naive();
+        if today != self.day {
+            self.file.flush()?;
+            rotate(&self.path, self.day)?;
+            self.file = append(&self.path)?;
+            self.day = today;
+        }
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+    File::options().create(true).append(true).open(path)
+}
+
+/// Moves `path` aside as `<path>.<day>`, appending if another devflow run
+/// already rotated that day.
+fn rotate(path: &Path, day: NaiveDate) -> io::Result<()> {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(format!(".{}", day.format("%Y-%m-%d")));
+    let rotated = PathBuf::from(rotated);
+    if rotated.exists() {
+        let mut old = append(&rotated)?;
+        io::copy(&mut File::open(path)?, &mut old)?;
+        fs::remove_file(path)
+    } else {
+        fs::rename(path, rotated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_flags_map_to_levels() {
+        assert_eq!(verbosity_level(0, false), None);
+        assert_eq!(verbosity_level(1, false), Some(LevelFilter::INFO));
+        assert_eq!(verbosity_level(2, false), Some(LevelFilter::DEBUG));
+        assert_eq!(verbosity_level(5, false), Some(LevelFilter::TRACE));
+        assert_eq!(verbosity_level(0, true), Some(LevelFilter::ERROR));
+    }
+
+    #[test]
+    fn rotation_moves_the_file_aside_and_merges_same_day() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let path = td.path().join("devflow.log");
+        let day = NaiveDate::from_ymd_opt(2026, 2, 24).expect("date");
+
+        fs::write(&path, "first\n").expect("write");
+        rotate(&path, day).expect("rotate");
+        assert!(!path.exists());
+        fs::write(&path, "second\n").expect("write");
+        rotate(&path, day).expect("rotate");
+
+        assert_eq!(
+            fs::read_to_string(td.path().join("devflow.log.2026-02-24")).expect("read"),
+            "first\nsecond\n"
+        );
+    }
+
+    #[test]
+    fn writes_append_to_the_live_file() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let path = td.path().join("logs/devflow.log");
+        let mut file = DailyFile::open(&path).expect("open");
+        file.write_all(b"2026-02-24T10:30:00Z ERROR devflow: boom\n")
+            .expect("write");
+        file.flush().expect("flush");
+        drop(file);
+        let mut file = DailyFile::open(&path).expect("reopen");
+        file.write_all(b"more\n").expect("write");
+        assert_eq!(fs::read_to_string(&path).expect("read").lines().count(), 2);
+    }
+}
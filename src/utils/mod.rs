@@ -1,6 +1,15 @@
+pub mod color;
+pub mod compose;
 pub mod config;
+pub mod containers;
 pub mod envcheck;
+pub mod envcrypt;
+pub mod gpu;
 pub mod language;
+pub mod logging;
 pub mod ports;
+pub mod registry;
 pub mod sanitize;
+pub mod services;
+pub mod shutdown;
 pub mod snapshot;
@@ -1,10 +1,35 @@
-use serde::Serialize;
-use std::net::TcpListener;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+use std::{
+    fmt, io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpListener, UdpSocket},
+    str::FromStr,
+    sync::OnceLock,
+};
 use sysinfo::{Pid, ProcessesToUpdate, System};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Proto {
+    Tcp,
+    Udp,
+}
+
+impl fmt::Display for Proto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Proto::Tcp => f.write_str("tcp"),
+            Proto::Udp => f.write_str("udp"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortOwner {
     pub port: u16,
+    pub protocol: Proto,
     pub pid: u32,
     pub parent_pid: Option<u32>,
     pub cmd: String,
@@ -12,42 +37,336 @@ pub struct PortOwner {
     pub uptime_secs: u64,
 }
 
-pub fn common_free_ports() -> Vec<u16> {
-    [3000, 3001, 5173, 8000, 8080, 5432, 6379]
-        .into_iter()
-        .filter(|port| TcpListener::bind(("127.0.0.1", *port)).is_ok())
+/// Widest range accepted by [`PortRange`].
+pub const MAX_RANGE_WIDTH: u32 = 65535;
+
+/// An inclusive `START-END` port range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn ports(&self) -> impl Iterator<Item = u16> {
+        self.start..=self.end
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("expected START-END, got `{s}`"))?;
+        let start = start
+            .trim()
+            .parse::<u16>()
+            .map_err(|e| format!("invalid start port `{start}`: {e}"))?;
+        let end = end
+            .trim()
+            .parse::<u16>()
+            .map_err(|e| format!("invalid end port `{end}`: {e}"))?;
+        if start > end {
+            return Err(format!("start port {start} is greater than end port {end}"));
+        }
+        if u32::from(end - start) + 1 > MAX_RANGE_WIDTH {
+            return Err(format!("range is wider than {MAX_RANGE_WIDTH} ports"));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// Ports commonly used by local dev servers and databases.
+pub const DEFAULT_DEV_PORTS: [u16; 7] = [3000, 3001, 5173, 8000, 8080, 5432, 6379];
+
+/// Addresses a port must be bindable on to count as free unless the caller
+/// names its own: a dev server on either loopback would clash with ours.
+pub const LOOPBACK_ADDRS: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::LOCALHOST),
+    IpAddr::V6(Ipv6Addr::LOCALHOST),
+];
+
+fn try_bind(addr: IpAddr, port: u16, protocol: Proto) -> io::Result<()> {
+    match protocol {
+        Proto::Tcp => TcpListener::bind((addr, port)).map(drop),
+        Proto::Udp => UdpSocket::bind((addr, port)).map(drop),
+    }
+}
+
+/// Fails when nothing can be bound on `addr` at all, e.g. an address that
+/// isn't assigned to this host.
+pub fn check_bind_addr(addr: IpAddr, protocol: Proto) -> Result<()> {
+    try_bind(addr, 0, protocol).with_context(|| format!("cannot bind {protocol} sockets to {addr}"))
+}
+
+/// Whether `port` can be bound on every address in `addrs`. An address this
+/// host can't bind at all (`::1` with IPv6 disabled) doesn't count against
+/// the port; use [`check_bind_addr`] to reject those up front.
+pub fn is_port_free(port: u16, protocol: Proto, addrs: &[IpAddr]) -> bool {
+    addrs
+        .iter()
+        .all(|&addr| try_bind(addr, port, protocol).is_ok() || try_bind(addr, 0, protocol).is_err())
+}
+
+/// The `candidates` that are free on every address in `addrs`.
+pub fn free_ports(candidates: &[u16], protocol: Proto, addrs: &[IpAddr]) -> Vec<u16> {
+    candidates
+        .iter()
+        .copied()
+        .filter(|&port| is_port_free(port, protocol, addrs))
         .collect()
 }
 
-pub fn find_owner_by_port(port: u16) -> Option<PortOwner> {
-    let mut sys = System::new_all();
-    sys.refresh_processes(ProcessesToUpdate::All, true);
-    for (pid, proc_) in sys.processes() {
-        if proc_.name().to_string_lossy().contains(&port.to_string())
-            || proc_
-                .cmd()
-                .iter()
-                .any(|c| c.to_string_lossy().contains(&port.to_string()))
-        {
-            let parent_pid = proc_.parent().map(|p| p.as_u32());
+/// The `candidates` that can be bound over TCP on both loopbacks.
+pub fn common_free_ports(candidates: &[u16]) -> Vec<u16> {
+    free_ports(candidates, Proto::Tcp, &LOOPBACK_ADDRS)
+}
+
+pub fn common_free_udp_ports(candidates: &[u16]) -> Vec<u16> {
+    free_ports(candidates, Proto::Udp, &LOOPBACK_ADDRS)
+}
+
+/// Returns the first port at or above `start` that can be bound over TCP on
+/// both loopbacks.
+pub fn next_free_port(start: u16) -> Option<u16> {
+    (start..=u16::MAX).find(|&port| is_port_free(port, Proto::Tcp, &LOOPBACK_ADDRS))
+}
+
+/// Looks up port owners against one snapshot of the process table, so
+/// repeated lookups (a range, or every port of a `port --watch` tick) scan
+/// the processes once instead of once per port.
+///
+/// The table is read on the first lookup that finds a bound port and kept
+/// until [`PortScanner::refresh`]; ports nobody owns never trigger a scan.
+/// On Linux the socket tables are read on the first lookup, and the
+/// socket-to-pid map from `/proc/*/fd` on the first that finds a bound port;
+/// both are kept until `refresh` too, so a range scan walks `/proc` once.
+#[derive(Default)]
+pub struct PortScanner {
+    sys: OnceLock<System>,
+    #[cfg(target_os = "linux")]
+    listeners: OnceLock<HashMap<(Proto, u16), Vec<u64>>>,
+    #[cfg(target_os = "linux")]
+    socket_pids: OnceLock<HashMap<u64, u32>>,
+}
+
+impl PortScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drops the cached tables; the next lookup reads fresh ones.
+    pub fn refresh(&mut self) {
+        *self = Self::default();
+    }
+
+    fn system(&self) -> &System {
+        self.sys.get_or_init(|| {
+            let mut sys = System::new();
+            sys.refresh_processes(ProcessesToUpdate::All, true);
+            sys
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn listener_pid(&self, port: u16, protocol: Proto) -> Option<u32> {
+        let inodes = self
+            .listeners
+            .get_or_init(read_proc_net_listeners)
+            .get(&(protocol, port))?;
+        let socket_pids = self.socket_pids.get_or_init(read_socket_pids);
+        inodes
+            .iter()
+            .find_map(|inode| socket_pids.get(inode).copied())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn listener_pid(&self, port: u16, protocol: Proto) -> Option<u32> {
+        listener_pid(port, protocol)
+    }
+
+    pub fn owner(&self, port: u16, protocol: Proto) -> Option<PortOwner> {
+        let pid = self.listener_pid(port, protocol)?;
+
+        // The socket table is authoritative; if the process vanished, started
+        // after the table was read, or is not visible to us we still report
+        // the pid rather than guessing.
+        let Some(proc_) = self.system().process(Pid::from_u32(pid)) else {
             return Some(PortOwner {
                 port,
-                pid: pid.as_u32(),
-                parent_pid,
-                cmd: proc_
-                    .cmd()
-                    .iter()
-                    .map(|s| s.to_string_lossy())
-                    .collect::<Vec<_>>()
-                    .join(" "),
-                memory_kb: proc_.memory(),
-                uptime_secs: proc_.run_time(),
+                protocol,
+                pid,
+                parent_pid: None,
+                cmd: String::new(),
+                memory_kb: 0,
+                uptime_secs: 0,
             });
+        };
+
+        Some(PortOwner {
+            port,
+            protocol,
+            pid,
+            parent_pid: proc_.parent().map(|p| p.as_u32()),
+            cmd: proc_
+                .cmd()
+                .iter()
+                .map(|s| s.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" "),
+            memory_kb: proc_.memory(),
+            uptime_secs: proc_.run_time(),
+        })
+    }
+
+    pub fn process_name(&self, pid: u32) -> Option<String> {
+        self.system()
+            .process(Pid::from_u32(pid))
+            .map(|p| p.name().to_string_lossy().to_string())
+    }
+}
+
+/// One-off lookup; use a [`PortScanner`] when checking several ports.
+pub fn find_owner_by_port(port: u16, protocol: Proto) -> Option<PortOwner> {
+    PortScanner::new().owner(port, protocol)
+}
+
+/// The listening socket inodes of every port, from `/proc/net/{tcp,udp}{,6}`.
+#[cfg(target_os = "linux")]
+fn read_proc_net_listeners() -> HashMap<(Proto, u16), Vec<u64>> {
+    let tables = [
+        ("/proc/net/tcp", Proto::Tcp),
+        ("/proc/net/tcp6", Proto::Tcp),
+        ("/proc/net/udp", Proto::Udp),
+        ("/proc/net/udp6", Proto::Udp),
+    ];
+    let mut listeners = HashMap::<_, Vec<u64>>::new();
+    for (path, protocol) in tables {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        for (port, inode) in parse_proc_net_table(&content, protocol) {
+            listeners.entry((protocol, port)).or_default().push(inode);
         }
     }
+    listeners
+}
+
+/// Which pid holds each socket inode, from one walk of `/proc/*/fd`. A
+/// socket shared by several processes (e.g. after a fork) maps to the
+/// lowest pid.
+#[cfg(target_os = "linux")]
+fn read_socket_pids() -> HashMap<u64, u32> {
+    use std::fs;
+
+    let mut socket_pids = HashMap::new();
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return socket_pids;
+    };
+    let mut pids = procs
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_string_lossy().parse::<u32>().ok())
+        .collect::<Vec<_>>();
+    pids.sort_unstable();
+    for pid in pids {
+        let Ok(fds) = fs::read_dir(format!("/proc/{pid}/fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(target) = fs::read_link(fd.path()) else {
+                continue;
+            };
+            if let Some(inode) = parse_socket_inode(&target.to_string_lossy()) {
+                socket_pids.entry(inode).or_insert(pid);
+            }
+        }
+    }
+    socket_pids
+}
+
+#[cfg(target_os = "macos")]
+fn listener_pid(port: u16, protocol: Proto) -> Option<u32> {
+    let mut cmd = std::process::Command::new("lsof");
+    match protocol {
+        Proto::Tcp => cmd.args(["-nP", &format!("-iTCP:{port}"), "-sTCP:LISTEN", "-t"]),
+        Proto::Udp => cmd.args(["-nP", &format!("-iUDP:{port}"), "-t"]),
+    };
+    let output = cmd.output().ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|l| l.trim().parse().ok())
+}
+
+#[cfg(windows)]
+fn listener_pid(port: u16, protocol: Proto) -> Option<u32> {
+    let proto_arg = match protocol {
+        Proto::Tcp => "TCP",
+        Proto::Udp => "UDP",
+    };
+    let output = std::process::Command::new("netstat")
+        .args(["-ano", "-p", proto_arg])
+        .output()
+        .ok()?;
+    let suffix = format!(":{port}");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            let cols = line.split_whitespace().collect::<Vec<_>>();
+            // TCP: Proto  Local Address  Foreign Address  State  PID
+            // UDP: Proto  Local Address  Foreign Address  PID
+            let matches = match protocol {
+                Proto::Tcp => cols.len() == 5 && cols[3] == "LISTENING",
+                Proto::Udp => cols.len() == 4,
+            };
+            if matches && cols[1].ends_with(&suffix) {
+                cols.last()?.parse().ok()
+            } else {
+                None
+            }
+        })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn listener_pid(_port: u16, _protocol: Proto) -> Option<u32> {
     None
 }
 
+/// Returns the socket inodes of entries in a `/proc/net/{tcp,udp}{,6}` table
+/// bound to `port`. TCP entries must be in the `LISTEN` state; any bound UDP
+/// socket counts as an owner.
+pub fn parse_proc_net_listeners(content: &str, port: u16, protocol: Proto) -> Vec<u64> {
+    parse_proc_net_table(content, protocol)
+        .filter(|&(local_port, _)| local_port == port)
+        .map(|(_, inode)| inode)
+        .collect()
+}
+
+/// `(port, inode)` of every listening entry in a `/proc/net` table, with
+/// the same rules as [`parse_proc_net_listeners`].
+fn parse_proc_net_table(content: &str, protocol: Proto) -> impl Iterator<Item = (u16, u64)> + '_ {
+    const TCP_LISTEN: &str = "0A";
+    content.lines().skip(1).filter_map(move |line| {
+        let cols = line.split_whitespace().collect::<Vec<_>>();
+        let local = cols.get(1)?;
+        let state = cols.get(3)?;
+        let inode = cols.get(9)?.parse::<u64>().ok()?;
+        let (_, hex_port) = local.rsplit_once(':')?;
+        let local_port = u16::from_str_radix(hex_port, 16).ok()?;
+        let listening = protocol == Proto::Udp || *state == TCP_LISTEN;
+        (listening && inode != 0).then_some((local_port, inode))
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn parse_socket_inode(link: &str) -> Option<u64> {
+    link.strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
 pub fn safe_kill_suggestion(pid: u32) -> Vec<String> {
     vec![
         format!("Try graceful stop first: kill {}", pid),
@@ -57,10 +376,150 @@ pub fn safe_kill_suggestion(pid: u32) -> Vec<String> {
     ]
 }
 
+/// Sends a graceful termination request to `pid`, or a forced kill when
+/// `force` is set.
+#[cfg(unix)]
+pub fn terminate_process(pid: u32, force: bool) -> Result<()> {
+    use nix::{
+        sys::signal::{kill, Signal},
+        unistd::Pid as UnixPid,
+    };
+
+    let signal = if force {
+        Signal::SIGKILL
+    } else {
+        Signal::SIGTERM
+    };
+    let raw = i32::try_from(pid).context("pid out of range")?;
+    kill(UnixPid::from_raw(raw), signal)
+        .with_context(|| format!("failed to send {signal} to pid {pid}"))
+}
+
+#[cfg(windows)]
+pub fn terminate_process(pid: u32, force: bool) -> Result<()> {
+    let mut cmd = std::process::Command::new("taskkill");
+    if force {
+        cmd.arg("/F");
+    }
+    let status = cmd
+        .args(["/PID", &pid.to_string()])
+        .status()
+        .context("failed to run taskkill")?;
+    if !status.success() {
+        anyhow::bail!("taskkill exited with status {status}");
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn terminate_process(pid: u32, _force: bool) -> Result<()> {
+    anyhow::bail!("killing pid {pid} is not supported on this platform")
+}
+
 pub fn process_name(pid: u32) -> Option<String> {
-    let mut sys = System::new_all();
-    sys.refresh_processes(ProcessesToUpdate::All, true);
-    let pid = Pid::from_u32(pid);
-    sys.process(pid)
-        .map(|p| p.name().to_string_lossy().to_string())
+    PortScanner::new().process_name(pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_listening_inode_from_proc_net_tcp() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:0BB8 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:0BB8 0100007F:D431 01 00000000:00000000 00:00000000 00000000  1000        0 4343 1 0000000000000000 20 4 30 10 -1
+   2: 00000000:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 5555 1 0000000000000000 100 0 0 10 0";
+        assert_eq!(
+            parse_proc_net_listeners(table, 3000, Proto::Tcp),
+            vec![4242]
+        );
+        assert_eq!(
+            parse_proc_net_listeners(table, 8080, Proto::Tcp),
+            vec![5555]
+        );
+        assert!(parse_proc_net_listeners(table, 5432, Proto::Tcp).is_empty());
+    }
+
+    #[test]
+    fn next_free_port_skips_bound_port() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        assert!(common_free_ports(&[port]).is_empty());
+        let next = next_free_port(port).expect("free port");
+        assert!(next > port);
+    }
+
+    #[test]
+    fn port_bound_on_one_loopback_is_not_free() {
+        let Ok(listener) = TcpListener::bind((Ipv6Addr::LOCALHOST, 0)) else {
+            return; // no IPv6 on this host
+        };
+        let port = listener.local_addr().expect("addr").port();
+        let v4 = [IpAddr::V4(Ipv4Addr::LOCALHOST)];
+        assert_eq!(free_ports(&[port], Proto::Tcp, &v4), vec![port]);
+        assert!(common_free_ports(&[port]).is_empty());
+        assert!(!is_port_free(
+            port,
+            Proto::Tcp,
+            &[IpAddr::V6(Ipv6Addr::UNSPECIFIED)]
+        ));
+    }
+
+    #[test]
+    fn unusable_addresses_are_rejected_up_front() {
+        // TEST-NET-1 is never assigned to a local interface.
+        let addr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        assert!(check_bind_addr(addr, Proto::Tcp).is_err());
+        assert!(check_bind_addr(IpAddr::V4(Ipv4Addr::LOCALHOST), Proto::Udp).is_ok());
+    }
+
+    #[test]
+    fn finds_owner_of_bound_listener() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        if cfg!(target_os = "linux") {
+            let owner = find_owner_by_port(port, Proto::Tcp).expect("owner");
+            assert_eq!(owner.pid, std::process::id());
+            assert_eq!(owner.protocol, Proto::Tcp);
+        }
+    }
+
+    #[test]
+    fn finds_owner_of_bound_udp_socket() {
+        let socket = UdpSocket::bind(("127.0.0.1", 0)).expect("bind");
+        let port = socket.local_addr().expect("addr").port();
+        if cfg!(target_os = "linux") {
+            let owner = find_owner_by_port(port, Proto::Udp).expect("owner");
+            assert_eq!(owner.pid, std::process::id());
+            assert_eq!(owner.protocol, Proto::Udp);
+        }
+    }
+
+    #[test]
+    fn scanner_reads_processes_only_for_owned_ports() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("bind");
+        let free = listener.local_addr().expect("addr").port();
+        drop(listener);
+        // Bound before the scanner's first lookup, which snapshots the
+        // socket table until `refresh`.
+        let listener = TcpListener::bind(("127.0.0.1", 0)).expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        let mut scanner = PortScanner::new();
+        assert!(scanner.owner(free, Proto::Tcp).is_none());
+        assert!(scanner.sys.get().is_none());
+
+        if cfg!(target_os = "linux") {
+            let owner = scanner.owner(port, Proto::Tcp).expect("owner");
+            assert_eq!(owner.pid, std::process::id());
+            assert!(scanner.sys.get().is_some());
+            assert!(scanner.process_name(owner.pid).is_some());
+            #[cfg(target_os = "linux")]
+            assert!(scanner.socket_pids.get().is_some());
+            scanner.refresh();
+            assert!(scanner.sys.get().is_none());
+            #[cfg(target_os = "linux")]
+            assert!(scanner.socket_pids.get().is_none());
+        }
+    }
 }
@@ -0,0 +1,199 @@
+//! Latest-version lookups against package registries, for `deps --online`.
+//!
+//! Answers are cached in `.devflow/registry_cache.json` so repeated runs
+//! don't query the registries again for an hour. Failed lookups are not
+//! cached and come back as `None`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+    time::Duration,
+};
+
+/// Registry answers cached by `latest_versions`, relative to the project root.
+pub const CACHE_PATH: &str = ".devflow/registry_cache.json";
+/// How long a cached answer is used before asking the registry again.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+/// Per-request timeout, connecting included.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Requests in flight at once.
+const CONCURRENCY: usize = 8;
+const USER_AGENT: &str = concat!("devflow/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registry {
+    Npm,
+    CratesIo,
+    PyPi,
+}
+
+impl Registry {
+    pub fn name(self) -> &'static str {
+        match self {
+            Registry::Npm => "npm",
+            Registry::CratesIo => "crates.io",
+            Registry::PyPi => "pypi",
+        }
+    }
+
+    fn url(self, package: &str) -> String {
+        match self {
+            // Also works for scoped packages, unlike `/<name>/latest`.
+            Registry::Npm => format!("https://registry.npmjs.org/-/package/{package}/dist-tags"),
+            Registry::CratesIo => format!("https://crates.io/api/v1/crates/{package}"),
+            Registry::PyPi => format!("https://pypi.org/pypi/{package}/json"),
+        }
+    }
+
+    /// Picks the latest stable version out of a registry response.
+    fn latest(self, body: &serde_json::Value) -> Option<String> {
+        let version = match self {
+            Registry::Npm => &body["latest"],
+            Registry::CratesIo => {
+                let krate = &body["crate"];
+                // `max_stable_version` is null when only pre-releases exist.
+                if krate["max_stable_version"].is_string() {
+                    &krate["max_stable_version"]
+                } else {
+                    &krate["max_version"]
+                }
+            }
+            Registry::PyPi => &body["info"]["version"],
+        };
+        version.as_str().map(str::to_string)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    latest: String,
+    fetched_at: DateTime<Utc>,
+}
+
+/// The latest published version of each package in `names`, or `None`
+/// where the registry couldn't be reached or didn't know the package.
+/// Blocks; call it from `spawn_blocking` in async code.
+pub fn latest_versions(
+    root: &Path,
+    registry: Registry,
+    names: &[String],
+) -> HashMap<String, Option<String>> {
+    let cache_path = root.join(CACHE_PATH);
+    let mut cache: BTreeMap<String, CacheEntry> = fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let key = |name: &str| format!("{}/{}", registry.name(), name);
+    let now = Utc::now();
+    let fresh = |entry: &CacheEntry| {
+        (now - entry.fetched_at)
+            .to_std()
+            .is_ok_and(|age| age < CACHE_TTL)
+    };
+
+    let mut latest = HashMap::new();
+    let mut missing = Vec::new();
+    for name in names {
+        match cache.get(&key(name)).filter(|entry| fresh(entry)) {
+            Some(entry) => {
+                latest.insert(name.clone(), Some(entry.latest.clone()));
+            }
+            None => missing.push(name.as_str()),
+        }
+    }
+    if missing.is_empty() {
+        return latest;
+    }
+
+    let agent = ureq::AgentBuilder::new()
+        .timeout(HTTP_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build();
+    for chunk in missing.chunks(CONCURRENCY) {
+        let fetched = std::thread::scope(|scope| {
+            let handles = chunk
+                .iter()
+                .map(|name| {
+                    let agent = &agent;
+                    scope.spawn(move || fetch_latest(agent, registry, name))
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().ok().flatten())
+                .collect::<Vec<_>>()
+        });
+        for (name, version) in chunk.iter().zip(fetched) {
+            if let Some(version) = &version {
+                cache.insert(
+                    key(name),
+                    CacheEntry {
+                        latest: version.clone(),
+                        fetched_at: now,
+                    },
+                );
+            }
+            latest.insert(name.to_string(), version);
+        }
+    }
+
+    // A cache that can't be written only costs a refetch next time.
+    if let Some(parent) = cache_path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(body) = serde_json::to_string_pretty(&cache) {
+        let _ = fs::write(&cache_path, body);
+    }
+    latest
+}
+
+fn fetch_latest(agent: &ureq::Agent, registry: Registry, name: &str) -> Option<String> {
+    let body = agent
+        .get(&registry.url(name))
+        .call()
+        .ok()?
+        .into_json::<serde_json::Value>()
+        .ok()?;
+    registry.latest(&body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_latest_version_from_each_registry() {
+        let npm = serde_json::json!({"latest": "18.3.1", "next": "19.0.0-rc.1"});
+        assert_eq!(Registry::Npm.latest(&npm).as_deref(), Some("18.3.1"));
+        let crates = serde_json::json!({"crate": {"max_version": "2.0.0-beta.1", "max_stable_version": "1.0.210"}});
+        assert_eq!(
+            Registry::CratesIo.latest(&crates).as_deref(),
+            Some("1.0.210")
+        );
+        let prerelease_only = serde_json::json!({"crate": {"max_version": "0.1.0-alpha", "max_stable_version": null}});
+        assert_eq!(
+            Registry::CratesIo.latest(&prerelease_only).as_deref(),
+            Some("0.1.0-alpha")
+        );
+        let pypi = serde_json::json!({"info": {"version": "5.0.6"}});
+        assert_eq!(Registry::PyPi.latest(&pypi).as_deref(), Some("5.0.6"));
+        assert_eq!(Registry::PyPi.latest(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn fresh_cache_entries_answer_without_network() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let path = td.path().join(CACHE_PATH);
+        fs::create_dir_all(path.parent().unwrap()).expect("mkdir");
+        let cache = serde_json::json!({
+            "npm/react": {"latest": "18.3.1", "fetched_at": Utc::now()},
+        });
+        fs::write(&path, cache.to_string()).expect("write cache");
+
+        let latest = latest_versions(td.path(), Registry::Npm, &["react".to_string()]);
+        assert_eq!(latest["react"].as_deref(), Some("18.3.1"));
+    }
+}
@@ -0,0 +1,406 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    future::Future,
+    path::Path,
+    process::{ExitStatus, Stdio},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    process::{Child, Command},
+    time::{sleep, timeout, Duration, Instant},
+};
+
+use crate::utils::{
+    config::{ReadyCheck, ServiceDef},
+    sanitize::redact,
+};
+
+/// Readiness timeout used when a service sets no `ready_timeout_secs`.
+pub const DEFAULT_READY_TIMEOUT_SECS: u64 = 30;
+
+/// How long a stopped service gets to exit after SIGTERM before SIGKILL.
+pub const STOP_GRACE: Duration = Duration::from_secs(5);
+
+/// Builds a command that runs `command` through the platform shell.
+pub fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut c = Command::new("cmd");
+        c.args(["/C", command]);
+        c
+    }
+    #[cfg(not(windows))]
+    {
+        let mut c = Command::new("sh");
+        c.args(["-c", command]);
+        c
+    }
+}
+
+/// Spawns `command` in `root`, echoing its stdout and stderr line by line
+/// prefixed with `[name]`.
+pub fn spawn_streaming(name: &str, command: &str, root: &Path) -> Result<Child> {
+    spawn_streaming_with(name, shell_command(command), command, root)
+}
+
+/// Like [`spawn_streaming`], but starts `command` in its own process group so
+/// [`stop_group`] can take down everything it launched.
+pub fn spawn_streaming_group(name: &str, command: &str, root: &Path) -> Result<Child> {
+    let mut cmd = shell_command(command);
+    #[cfg(unix)]
+    cmd.process_group(0);
+    spawn_streaming_with(name, cmd, command, root)
+}
+
+fn spawn_streaming_with(name: &str, mut cmd: Command, command: &str, root: &Path) -> Result<Child> {
+    let mut child = cmd
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start {name}: {command}"))?;
+    tracing::info!(
+        "started {} (pid {}): {}",
+        name,
+        child.id().unwrap_or_default(),
+        redact(command)
+    );
+
+    if let Some(out) = child.stdout.take() {
+        tokio::spawn(forward_lines(name.to_string(), out, false));
+    }
+    if let Some(err) = child.stderr.take() {
+        tokio::spawn(forward_lines(name.to_string(), err, true));
+    }
+    Ok(child)
+}
+
+/// Waits for `child`, started with [`spawn_streaming_group`], to exit. If
+/// `stop` completes first, its whole process group is stopped with
+/// [`stop_group`], so the server behind `cargo run` or `npm run dev` goes
+/// too, not just the shell.
+pub async fn wait_or_stop(child: &mut Child, stop: impl Future<Output = ()>) -> Result<ExitStatus> {
+    tokio::select! {
+        status = child.wait() => Ok(status?),
+        () = stop => {
+            stop_group(child, STOP_GRACE).await?;
+            Ok(child.wait().await?)
+        }
+    }
+}
+
+/// Stops a child started with [`spawn_streaming_group`]: SIGTERM to its
+/// process group, SIGKILL if the leader is still running after `grace`, then
+/// SIGKILL to whatever is left of the group so nothing is orphaned. Other
+/// platforms kill the child directly.
+pub async fn stop_group(child: &mut Child, grace: Duration) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use nix::{
+            sys::signal::{killpg, Signal},
+            unistd::Pid,
+        };
+
+        let Some(pid) = child.id() else {
+            return Ok(());
+        };
+        let group = Pid::from_raw(i32::try_from(pid).context("pid out of range")?);
+        let _ = killpg(group, Signal::SIGTERM);
+        if timeout(grace, child.wait()).await.is_err() {
+            let _ = killpg(group, Signal::SIGKILL);
+            child.wait().await?;
+        }
+        let _ = killpg(group, Signal::SIGKILL);
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = grace;
+        child.kill().await?;
+        Ok(())
+    }
+}
+
+/// Where detached services write their output, one `<name>.log` each.
+pub const SERVICE_LOG_DIR: &str = ".devflow/logs";
+
+/// Spawns `command` in the background with its output appended to
+/// `.devflow/logs/<name>.log`. Returns the child's pid.
+pub fn spawn_detached(name: &str, command: &str, root: &Path) -> Result<u32> {
+    let mut cmd = shell_command(command);
+    cmd.current_dir(root);
+    detach(name, cmd, command, root)
+}
+
+/// Like [`spawn_detached`], but runs `argv` directly (no shell) in `cwd`.
+/// Output still goes to `root/.devflow/logs/<name>.log`.
+pub fn spawn_detached_argv(name: &str, argv: &[String], cwd: &Path, root: &Path) -> Result<u32> {
+    let Some((program, args)) = argv.split_first() else {
+        bail!("cannot start {name}: empty command line");
+    };
+    let mut cmd = Command::new(program);
+    cmd.args(args).current_dir(cwd);
+    detach(name, cmd, &argv.join(" "), root)
+}
+
+fn detach(name: &str, mut cmd: Command, command: &str, root: &Path) -> Result<u32> {
+    let log_dir = root.join(SERVICE_LOG_DIR);
+    fs::create_dir_all(&log_dir)?;
+    let log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_dir.join(format!("{name}.log")))?;
+
+    cmd.stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // Keep the service out of our process group so a later Ctrl+C in this
+    // terminal does not reach it.
+    #[cfg(unix)]
+    cmd.process_group(0);
+
+    let child = cmd
+        .spawn()
+        .with_context(|| format!("failed to start {name}: {command}"))?;
+    let pid = child
+        .id()
+        .with_context(|| format!("{name} exited before its pid could be read"))?;
+    tracing::info!(
+        "started {} in the background (pid {}): {}",
+        name,
+        pid,
+        redact(command)
+    );
+    Ok(pid)
+}
+
+async fn forward_lines<R: AsyncRead + Unpin>(name: String, reader: R, stderr: bool) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if stderr {
+            eprintln!("[{name}] {line}");
+        } else {
+            println!("[{name}] {line}");
+        }
+    }
+}
+
+/// Orders services so that every service comes after its `depends_on`
+/// entries, keeping the configured order where dependencies allow.
+pub fn order_services(services: &[ServiceDef]) -> Result<Vec<&ServiceDef>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Visiting,
+        Done,
+    }
+
+    fn visit<'a>(
+        svc: &'a ServiceDef,
+        by_name: &HashMap<&str, &'a ServiceDef>,
+        marks: &mut HashMap<&'a str, Mark>,
+        stack: &mut Vec<&'a str>,
+        ordered: &mut Vec<&'a ServiceDef>,
+    ) -> Result<()> {
+        match marks.get(svc.name.as_str()) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                let start = stack.iter().position(|n| *n == svc.name).unwrap_or(0);
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(&svc.name);
+                bail!("service dependency cycle: {}", cycle.join(" -> "));
+            }
+            None => {}
+        }
+
+        marks.insert(&svc.name, Mark::Visiting);
+        stack.push(&svc.name);
+        for dep in &svc.depends_on {
+            let Some(dep_svc) = by_name.get(dep.as_str()) else {
+                bail!("service {} depends on unknown service {}", svc.name, dep);
+            };
+            visit(dep_svc, by_name, marks, stack, ordered)?;
+        }
+        stack.pop();
+        marks.insert(&svc.name, Mark::Done);
+        ordered.push(svc);
+        Ok(())
+    }
+
+    let by_name = services
+        .iter()
+        .map(|s| (s.name.as_str(), s))
+        .collect::<HashMap<_, _>>();
+    let mut marks = HashMap::new();
+    let mut ordered = Vec::with_capacity(services.len());
+    for svc in services {
+        visit(svc, &by_name, &mut marks, &mut Vec::new(), &mut ordered)?;
+    }
+    Ok(ordered)
+}
+
+/// Waits for `svc`'s readiness check, if it has one, printing progress.
+pub async fn await_ready(svc: &ServiceDef) -> bool {
+    let Some(check) = &svc.ready_when else {
+        return true;
+    };
+    let secs = svc.ready_timeout_secs.unwrap_or(DEFAULT_READY_TIMEOUT_SECS);
+    println!("[{}] waiting for {} (up to {}s)", svc.name, check, secs);
+    let ready = wait_ready(check, Duration::from_secs(secs)).await;
+    if ready {
+        println!("[{}] ready", svc.name);
+    }
+    ready
+}
+
+/// Polls `check` until it succeeds or `limit` elapses.
+pub async fn wait_ready(check: &ReadyCheck, limit: Duration) -> bool {
+    let deadline = Instant::now() + limit;
+    loop {
+        if probe(check).await {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(Duration::from_millis(250)).await;
+    }
+}
+
+async fn probe(check: &ReadyCheck) -> bool {
+    let attempt = async {
+        match check {
+            ReadyCheck::Tcp(port) => TcpStream::connect(("127.0.0.1", *port)).await.is_ok(),
+            ReadyCheck::Http(url) => http_status(url).await.is_ok_and(|code| code == 200),
+        }
+    };
+    timeout(Duration::from_secs(2), attempt)
+        .await
+        .unwrap_or(false)
+}
+
+/// Issues a bare HTTP/1.0 GET and returns the response status code.
+async fn http_status(url: &str) -> Result<u16> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        bail!("only http:// readiness URLs are supported: {url}");
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+
+    let mut stream = TcpStream::connect(addr).await?;
+    let request = format!("GET {path} HTTP/1.0\r\nHost: {authority}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Drain the whole response so the server doesn't see a reset mid-write.
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await?;
+    String::from_utf8_lossy(&response)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .context("malformed HTTP response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn svc(name: &str, deps: &[&str]) -> ServiceDef {
+        ServiceDef {
+            name: name.into(),
+            command: "true".into(),
+            depends_on: deps.iter().map(|d| d.to_string()).collect(),
+            ready_when: None,
+            ready_timeout_secs: None,
+        }
+    }
+
+    #[test]
+    fn orders_dependencies_first() {
+        let services = vec![
+            svc("app", &["db", "cache"]),
+            svc("db", &[]),
+            svc("cache", &["db"]),
+        ];
+        let order = order_services(&services)
+            .expect("order")
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, vec!["db", "cache", "app"]);
+    }
+
+    #[test]
+    fn reports_dependency_cycle() {
+        let services = vec![svc("a", &["b"]), svc("b", &["c"]), svc("c", &["a"])];
+        let err = order_services(&services).expect_err("cycle").to_string();
+        assert!(err.contains("a -> b -> c -> a"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stop_group_kills_a_child_that_ignores_sigterm() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let mut child =
+            spawn_streaming_group("t", "trap '' TERM; sleep 30", td.path()).expect("spawn");
+        sleep(Duration::from_millis(200)).await;
+        timeout(
+            Duration::from_secs(5),
+            stop_group(&mut child, Duration::from_millis(200)),
+        )
+        .await
+        .expect("stopped in time")
+        .expect("stop");
+        assert!(child.try_wait().expect("status").is_some());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn wait_or_stop_stops_what_the_shell_started() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let mut child =
+            spawn_streaming_group("t", "sleep 30 & echo $! > pid; wait", td.path()).expect("spawn");
+        let pid_file = td.path().join("pid");
+        while !fs::read_to_string(&pid_file).is_ok_and(|p| p.ends_with('\n')) {
+            sleep(Duration::from_millis(20)).await;
+        }
+        let pid = fs::read_to_string(&pid_file).expect("pid");
+        timeout(Duration::from_secs(5), wait_or_stop(&mut child, async {}))
+            .await
+            .expect("stopped in time")
+            .expect("stop");
+
+        // Gone, or a zombie waiting for a reaper: either way not running.
+        let stat = Path::new("/proc").join(pid.trim()).join("stat");
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let running = loop {
+            let running = fs::read_to_string(&stat).is_ok_and(|s| {
+                s.rsplit(')')
+                    .next()
+                    .is_some_and(|rest| !rest.starts_with(" Z"))
+            });
+            if !running || Instant::now() > deadline {
+                break running;
+            }
+            sleep(Duration::from_millis(20)).await;
+        };
+        assert!(!running, "sleep {} outlived its shell", pid.trim());
+    }
+
+    #[tokio::test]
+    async fn tcp_readiness_succeeds_for_listener() {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        assert!(wait_ready(&ReadyCheck::Tcp(port), Duration::from_millis(500)).await);
+    }
+}
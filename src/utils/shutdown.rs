@@ -0,0 +1,23 @@
+use tokio_util::sync::CancellationToken;
+
+/// Exit status after a second Ctrl+C, as for a process killed by SIGINT.
+const FORCED_EXIT_CODE: i32 = 130;
+
+/// Cancels `token` on the first Ctrl+C, so a command that runs until
+/// interrupted can stop its children and print a summary. A second Ctrl+C
+/// exits at once, in case that cleanup hangs.
+///
+/// Listening replaces the default of exiting on Ctrl+C for the rest of the
+/// process, so only commands that stop on `token` should ask for it.
+pub fn cancel_on_ctrl_c(token: CancellationToken) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        token.cancel();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("interrupted again; exiting without cleanup");
+            std::process::exit(FORCED_EXIT_CODE);
+        }
+    });
+}
@@ -1,71 +1,817 @@
-use anyhow::Result;
-use chrono::{DateTime, Utc};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
-use sysinfo::{ProcessesToUpdate, System};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use sysinfo::{Process, ProcessesToUpdate, System};
+
+use crate::utils::{
+    config::{config_exists, load_config, ServiceDef},
+    language::{detect_project_language, Language},
+    ports::{PortOwner, PortScanner, Proto, DEFAULT_DEV_PORTS},
+    sanitize::{is_secret_key, mask_partial, Redactor, MAX_PARTIAL_KEEP},
+};
+
+/// Directory of named snapshots, relative to the project root.
+pub const SNAPSHOT_DIR: &str = ".devflow/snapshots";
+/// Where the single snapshot lived before snapshots had names.
+pub const LEGACY_SNAPSHOT_PATH: &str = ".devflow/snapshot.json";
+/// Version written to new snapshots. Version 1 had no ports or git state.
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 2;
+/// Stored in place of a secret env value.
+const REDACTED: &str = "<redacted>";
+/// Name `snap list` shows for the legacy snapshot file.
+const LEGACY_NAME: &str = "snapshot";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcSnapshot {
     pub pid: u32,
     pub name: String,
     pub cmd: String,
+    /// The command line as separate arguments. Older snapshots only have
+    /// `cmd`, which is split with shell-words rules when restoring.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub argv: Vec<String>,
+    /// Working directory of the process, if it could be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+}
+
+impl ProcSnapshot {
+    /// The arguments to relaunch this process with.
+    pub fn argv(&self) -> Result<Vec<String>> {
+        if !self.argv.is_empty() {
+            return Ok(self.argv.clone());
+        }
+        Ok(shell_words::split(&self.cmd)?)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Snapshot {
+    /// Missing in snapshots written before versioning, which are version 1.
+    #[serde(default = "unversioned")]
+    pub schema_version: u32,
     pub saved_at: DateTime<Utc>,
     pub cwd: String,
     pub processes: Vec<ProcSnapshot>,
     pub env: Vec<(String, String)>,
+    /// Keys in `env` whose values were replaced with `<redacted>`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redacted: Vec<String>,
+    /// Listening TCP ports among `desired_ports` and the common dev ports.
+    #[serde(default)]
+    pub ports: Vec<PortOwner>,
+    /// Repository state, if `cwd` was a git checkout.
+    #[serde(default)]
+    pub git: Option<GitState>,
+}
+
+fn unversioned() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitState {
+    /// `None` on a detached HEAD.
+    pub branch: Option<String>,
+    pub head: String,
+    /// Whether there were uncommitted changes.
+    pub dirty: bool,
+}
+
+/// Reads the branch, HEAD and dirty flag of the checkout at `root` by
+/// shelling out to `git`. `None` if `root` isn't a repository or git is
+/// unavailable.
+pub fn git_state(root: &Path) -> Option<GitState> {
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(args)
+            .output()
+            .ok()
+    };
+    let head = git(&["rev-parse", "HEAD"]).filter(|o| o.status.success())?;
+    let branch = git(&["symbolic-ref", "--short", "-q", "HEAD"])
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    let dirty = git(&["status", "--porcelain"]).is_some_and(|o| !o.stdout.is_empty());
+    Some(GitState {
+        branch,
+        head: String::from_utf8_lossy(&head.stdout).trim().to_string(),
+        dirty,
+    })
+}
+
+/// A saved snapshot as shown by `snap list`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    pub name: String,
+    pub path: String,
+    pub saved_at: DateTime<Utc>,
+    pub processes: usize,
+    /// Set for `.devflow/snapshot.json`, written by older devflow versions.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub legacy: bool,
+}
+
+/// Name used by `snap save` when none is given, e.g. `20240131-142502`.
+pub fn default_snapshot_name() -> String {
+    Local::now().format("%Y%m%d-%H%M%S").to_string()
 }
 
-pub fn save_snapshot(root: &Path) -> Result<()> {
+/// Path of the named snapshot, relative to the project root.
+pub fn snapshot_path(name: &str) -> Result<String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        bail!("invalid snapshot name {name:?}: use letters, digits, '-', '_' and '.'");
+    }
+    Ok(format!("{SNAPSHOT_DIR}/{name}.json"))
+}
+
+/// What [`capture_snapshot`] records.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaptureOptions {
+    /// Record every running process rather than only the project's.
+    pub all: bool,
+    /// Keep secret-looking env values instead of redacting them.
+    pub include_secrets: bool,
+    /// Redact secrets down to their last few characters (see
+    /// [`Redactor::redact_partial`]) rather than replacing them outright.
+    pub partial_redact: bool,
+}
+
+/// Captures the project's processes and env as snapshot `name`, returning
+/// the path it was written to, relative to `root`. Env values are redacted
+/// with `redactor`. The file is written next to its final path and renamed
+/// into place, so an interrupted save never leaves a truncated snapshot.
+pub fn save_snapshot(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    name: &str,
+    opts: CaptureOptions,
+    redactor: &Redactor,
+) -> Result<String> {
+    let path = snapshot_path(name)?;
+    let content =
+        serde_json::to_string_pretty(&capture_snapshot(root, config, profile, opts, redactor)?)?;
+    fs::create_dir_all(root.join(SNAPSHOT_DIR))?;
+    let tmp = root.join(format!("{path}.tmp"));
+    fs::write(&tmp, content).with_context(|| format!("could not write {}", tmp.display()))?;
+    fs::rename(&tmp, root.join(&path))
+        .with_context(|| format!("could not move {} into place", tmp.display()))?;
+    Ok(path)
+}
+
+/// Decides which running processes belong to the project.
+///
+/// Matching the project path against the command line alone misses most dev
+/// servers: `npm run dev`, `python manage.py runserver` or `go run .` are
+/// usually started with relative paths from inside the project, so their
+/// command lines never mention it. The working directory and process names
+/// typical of the project's language catch those.
+#[derive(Debug, Clone)]
+pub struct ProcessFilter {
+    root: PathBuf,
+    names: Vec<String>,
+    service_commands: Vec<String>,
+}
+
+impl ProcessFilter {
+    pub fn new(root: &Path, language: Language, services: &[ServiceDef]) -> Self {
+        let mut names = language_process_names(language)
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        names.extend(services.iter().map(|s| s.name.clone()));
+        Self {
+            root: root.to_path_buf(),
+            names,
+            service_commands: services.iter().map(|s| s.command.clone()).collect(),
+        }
+    }
+
+    /// Whether a process with this name, command line and working directory
+    /// belongs to the project. Paths are compared component by component, so
+    /// `/repo` does not claim `/repo-old`.
+    pub fn matches(&self, name: &str, cmd: &str, cwd: Option<&Path>) -> bool {
+        cmd.split_whitespace()
+            .filter_map(|arg| arg.rsplit('=').next())
+            .any(|arg| Path::new(arg).starts_with(&self.root))
+            || cwd.is_some_and(|dir| dir.starts_with(&self.root))
+            || self.names.iter().any(|hint| name_matches(name, hint))
+            || self
+                .service_commands
+                .iter()
+                .any(|svc| !svc.is_empty() && cmd.contains(svc.as_str()))
+    }
+}
+
+/// Process names that usually run a project in `language`.
+fn language_process_names(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::Rust => &["cargo"],
+        Language::Node => &["node", "npm", "npx", "yarn", "pnpm", "bun", "deno"],
+        Language::Python => &["python", "uvicorn", "gunicorn", "flask", "celery"],
+        Language::Go => &["go", "air"],
+        Language::Java => &["java", "mvn", "gradle"],
+        Language::Ruby => &["ruby", "bundle", "rails", "puma", "rspec"],
+        Language::Php => &["php", "php-fpm", "composer"],
+        Language::Unknown => &[],
+    }
+}
+
+/// `hint` itself or a versioned variant such as `python3.12`.
+fn name_matches(name: &str, hint: &str) -> bool {
+    name.strip_prefix(hint)
+        .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit() || c == '.'))
+}
+
+/// Captures the project's processes and env without saving them.
+pub fn capture_snapshot(
+    root: &Path,
+    config: Option<&Path>,
+    profile: Option<&str>,
+    opts: CaptureOptions,
+    redactor: &Redactor,
+) -> Result<Snapshot> {
+    // An unreadable config or unknown profile would silently drop the
+    // service-name hints and save the wrong processes.
+    let cfg = if config_exists(root, config) {
+        load_config(root, config, profile)?
+    } else {
+        Default::default()
+    };
+    let filter = ProcessFilter::new(root, detect_project_language(root), &cfg.services);
+
     let mut sys = System::new_all();
     sys.refresh_processes(ProcessesToUpdate::All, true);
     let cwd = root.display().to_string();
 
-    let processes = sys
+    let own_pid = std::process::id();
+    let mut processes = sys
         .processes()
         .iter()
+        .filter(|(pid, _)| pid.as_u32() != own_pid)
         .filter_map(|(pid, process)| {
-            let cmd = process
-                .cmd()
-                .iter()
-                .map(|s| s.to_string_lossy())
-                .collect::<Vec<_>>()
-                .join(" ");
-            if cmd.contains(&cwd) || process.name().to_string_lossy().contains("cargo") {
-                Some(ProcSnapshot {
-                    pid: pid.as_u32(),
-                    name: process.name().to_string_lossy().to_string(),
-                    cmd,
-                })
-            } else {
-                None
+            let name = process.name().to_string_lossy().to_string();
+            let cmd = command_line(process);
+            if !opts.all && !filter.matches(&name, &cmd, process.cwd()) {
+                return None;
             }
+            Some(ProcSnapshot {
+                pid: pid.as_u32(),
+                name,
+                cmd,
+                argv: process
+                    .cmd()
+                    .iter()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .collect(),
+                cwd: process.cwd().map(|p| p.display().to_string()),
+            })
         })
         .collect::<Vec<_>>();
+    // Lower pids usually started first; restore in that order.
+    processes.sort_by_key(|p| p.pid);
 
-    let env = std::env::vars()
-        .filter(|(k, _)| {
-            !k.to_lowercase().contains("token") && !k.to_lowercase().contains("secret")
-        })
-        .collect::<Vec<_>>();
+    let (env, redacted) = redact_env(std::env::vars(), opts, redactor);
+
+    let mut watched = DEFAULT_DEV_PORTS.to_vec();
+    watched.extend(&cfg.desired_ports);
+    watched.sort_unstable();
+    watched.dedup();
+    let scanner = PortScanner::new();
+    let ports = watched
+        .into_iter()
+        .filter_map(|port| scanner.owner(port, Proto::Tcp))
+        .collect();
 
-    let snap = Snapshot {
+    Ok(Snapshot {
+        schema_version: SNAPSHOT_SCHEMA_VERSION,
         saved_at: Utc::now(),
         cwd,
         processes,
         env,
+        redacted,
+        ports,
+        git: git_state(root),
+    })
+}
+
+/// Replaces the values of secret-looking env vars (by key, or credentials
+/// embedded in the value such as a password in a URL) with `<redacted>`
+/// unless `opts.include_secrets` is set, or with a partial mask under
+/// `opts.partial_redact`. Returns the env and the redacted keys.
+fn redact_env(
+    vars: impl IntoIterator<Item = (String, String)>,
+    opts: CaptureOptions,
+    redactor: &Redactor,
+) -> (Vec<(String, String)>, Vec<String>) {
+    let mut redacted_keys = Vec::new();
+    let env = vars
+        .into_iter()
+        .map(|(key, value)| {
+            if opts.include_secrets {
+                return (key, value);
+            }
+            let cleaned = match (is_secret_key(&key), opts.partial_redact) {
+                (true, false) => REDACTED.to_string(),
+                (true, true) => mask_partial(&value, MAX_PARTIAL_KEEP),
+                (false, false) => redactor.redact(&value),
+                (false, true) => redactor.redact_partial(&value, MAX_PARTIAL_KEEP),
+            };
+            if cleaned != value {
+                redacted_keys.push(key.clone());
+            }
+            (key, cleaned)
+        })
+        .collect();
+    redacted_keys.sort();
+    (env, redacted_keys)
+}
+
+/// Reads snapshot `name`, or when `name` is `None` the most recently saved
+/// named snapshot, falling back to the legacy `.devflow/snapshot.json`.
+/// Returns the snapshot's name along with it.
+pub fn read_snapshot(root: &Path, name: Option<&str>) -> Result<(String, Snapshot)> {
+    if let Some(name) = name {
+        let path = root.join(snapshot_path(name)?);
+        if !path.exists() {
+            bail!("no snapshot named {name} (see `devflow snap list`)");
+        }
+        return Ok((name.to_string(), load(&path)?));
+    }
+
+    let latest = list_snapshots(root)?
+        .into_iter()
+        .filter(|info| !info.legacy)
+        .max_by_key(|info| info.saved_at);
+    match latest {
+        Some(info) => Ok((info.name, load(&root.join(&info.path))?)),
+        None if root.join(LEGACY_SNAPSHOT_PATH).exists() => Ok((
+            LEGACY_NAME.to_string(),
+            load(&root.join(LEGACY_SNAPSHOT_PATH))?,
+        )),
+        None => bail!("no snapshots saved yet; run `devflow snap save` first"),
+    }
+}
+
+/// Every saved snapshot, oldest first, including the legacy single file.
+/// Files that can't be read are skipped with a warning.
+pub fn list_snapshots(root: &Path) -> Result<Vec<SnapshotInfo>> {
+    let mut found = Vec::new();
+    if root.join(LEGACY_SNAPSHOT_PATH).exists() {
+        found.push((
+            LEGACY_NAME.to_string(),
+            LEGACY_SNAPSHOT_PATH.to_string(),
+            true,
+        ));
+    }
+    if let Ok(entries) = fs::read_dir(root.join(SNAPSHOT_DIR)) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    found.push((
+                        name.to_string(),
+                        format!("{SNAPSHOT_DIR}/{name}.json"),
+                        false,
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut infos = Vec::new();
+    for (name, path, legacy) in found {
+        match load(&root.join(&path)) {
+            Ok(snap) => infos.push(SnapshotInfo {
+                name,
+                path,
+                saved_at: snap.saved_at,
+                processes: snap.processes.len(),
+                legacy,
+            }),
+            Err(err) => eprintln!("warning: skipping {}: {:#}", path, err),
+        }
+    }
+    infos.sort_by_key(|info| info.saved_at);
+    Ok(infos)
+}
+
+/// What changed between two snapshots. Processes are matched by command
+/// line with whitespace normalized; env vars by key.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct SnapshotDiff {
+    pub processes_added: Vec<String>,
+    pub processes_removed: Vec<String>,
+    pub env_added: Vec<String>,
+    pub env_removed: Vec<String>,
+    pub env_changed: Vec<String>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub fn diff_snapshots(old: &Snapshot, new: &Snapshot) -> SnapshotDiff {
+    let commands = |snap: &Snapshot| {
+        snap.processes
+            .iter()
+            .map(|p| p.cmd.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<BTreeSet<_>>()
     };
+    let (old_cmds, new_cmds) = (commands(old), commands(new));
+    let old_env = old.env.iter().cloned().collect::<BTreeMap<_, _>>();
+    let new_env = new.env.iter().cloned().collect::<BTreeMap<_, _>>();
+
+    SnapshotDiff {
+        processes_added: new_cmds.difference(&old_cmds).cloned().collect(),
+        processes_removed: old_cmds.difference(&new_cmds).cloned().collect(),
+        env_added: new_env
+            .keys()
+            .filter(|k| !old_env.contains_key(*k))
+            .cloned()
+            .collect(),
+        env_removed: old_env
+            .keys()
+            .filter(|k| !new_env.contains_key(*k))
+            .cloned()
+            .collect(),
+        env_changed: new_env
+            .iter()
+            .filter(|(k, v)| old_env.get(*k).is_some_and(|old| old != *v))
+            .map(|(k, _)| k.clone())
+            .collect(),
+    }
+}
+
+fn load(path: &Path) -> Result<Snapshot> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+    parse_snapshot(&content).with_context(|| format!("invalid snapshot {}", path.display()))
+}
+
+/// Parses a snapshot of any schema version up to [`SNAPSHOT_SCHEMA_VERSION`],
+/// migrating older ones. Newer snapshots are refused rather than read with
+/// fields silently dropped.
+fn parse_snapshot(content: &str) -> Result<Snapshot> {
+    let mut value = serde_json::from_str::<Value>(content).map_err(corrupt)?;
+    let version = match value.get("schema_version") {
+        None => 1,
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|&v| v >= 1)
+            .ok_or_else(|| anyhow!("unknown schema_version {v}"))?,
+    };
+    if version > SNAPSHOT_SCHEMA_VERSION {
+        bail!(
+            "snapshot was created by a newer devflow (schema version {version}, this one \
+             reads up to {SNAPSHOT_SCHEMA_VERSION}); please upgrade devflow"
+        );
+    }
+    if version == SNAPSHOT_SCHEMA_VERSION {
+        // Straight from the text, so type errors point at a line too.
+        return serde_json::from_str(content).map_err(corrupt);
+    }
+    migrate(&mut value, version)?;
+    serde_json::from_value(value)
+        .map_err(|err| anyhow!("not a valid version {version} snapshot: {err}"))
+}
 
-    let content = serde_json::to_string_pretty(&snap)?;
-    fs::create_dir_all(root.join(".devflow"))?;
-    fs::write(root.join(".devflow/snapshot.json"), content)?;
+/// Upgrades the JSON of a version `from` snapshot to the current schema one
+/// version at a time.
+fn migrate(value: &mut Value, from: u32) -> Result<()> {
+    let Some(fields) = value.as_object_mut() else {
+        bail!("not a valid version {from} snapshot: expected a JSON object");
+    };
+    for version in from..SNAPSHOT_SCHEMA_VERSION {
+        match version {
+            // Version 1 (unversioned) predates port and git recording.
+            1 => {
+                fields
+                    .entry("ports")
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                fields.entry("git").or_insert(Value::Null);
+            }
+            _ => unreachable!("no migration from snapshot version {version}"),
+        }
+        fields.insert("schema_version".into(), (version + 1).into());
+    }
     Ok(())
 }
 
-pub fn read_snapshot(root: &Path) -> Result<Snapshot> {
-    let content = fs::read_to_string(root.join(".devflow/snapshot.json"))?;
-    Ok(serde_json::from_str(&content)?)
+/// `snapshot file is corrupt at line 3, column 7: expected value`, or a
+/// hint that it was cut short.
+fn corrupt(err: serde_json::Error) -> anyhow::Error {
+    if err.is_eof() {
+        return anyhow!(
+            "snapshot file ends early at line {}; it was probably cut short while saving",
+            err.line()
+        );
+    }
+    let message = err.to_string();
+    let message = message.split(" at line ").next().unwrap_or(&message);
+    anyhow!(
+        "snapshot file is corrupt at line {}, column {}: {message}",
+        err.line(),
+        err.column()
+    )
+}
+
+/// Command lines of every running process, formatted like
+/// [`ProcSnapshot::cmd`].
+pub fn running_commands() -> HashSet<String> {
+    let mut sys = System::new_all();
+    sys.refresh_processes(ProcessesToUpdate::All, true);
+    sys.processes().values().map(command_line).collect()
+}
+
+fn command_line(process: &Process) -> String {
+    process
+        .cmd()
+        .iter()
+        .map(|s| s.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, saved_at: &str) {
+        let snap = Snapshot {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            saved_at: saved_at.parse().expect("timestamp"),
+            cwd: root.display().to_string(),
+            processes: Vec::new(),
+            env: Vec::new(),
+            redacted: Vec::new(),
+            ports: Vec::new(),
+            git: None,
+        };
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+        fs::write(path, serde_json::to_string(&snap).expect("json")).expect("write");
+    }
+
+    #[test]
+    fn reads_latest_named_snapshot_before_legacy_file() {
+        let td = tempfile::tempdir().expect("tempdir");
+        write(td.path(), LEGACY_SNAPSHOT_PATH, "2024-03-01T00:00:00Z");
+        assert_eq!(
+            read_snapshot(td.path(), None).expect("legacy").0,
+            "snapshot"
+        );
+
+        write(
+            td.path(),
+            &snapshot_path("b").expect("name"),
+            "2024-02-01T00:00:00Z",
+        );
+        write(
+            td.path(),
+            &snapshot_path("a").expect("name"),
+            "2024-01-01T00:00:00Z",
+        );
+        assert_eq!(read_snapshot(td.path(), None).expect("latest").0, "b");
+        assert_eq!(read_snapshot(td.path(), Some("a")).expect("named").0, "a");
+        assert!(read_snapshot(td.path(), Some("c")).is_err());
+
+        let names = list_snapshots(td.path())
+            .expect("list")
+            .into_iter()
+            .map(|info| info.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b", "snapshot"]);
+    }
+
+    #[test]
+    fn diff_reports_processes_and_env_changes() {
+        let proc = |cmd: &str| ProcSnapshot {
+            pid: 1,
+            name: "x".into(),
+            cmd: cmd.into(),
+            argv: Vec::new(),
+            cwd: None,
+        };
+        let snap = |processes, env: &[(&str, &str)]| Snapshot {
+            schema_version: SNAPSHOT_SCHEMA_VERSION,
+            saved_at: Utc::now(),
+            cwd: "/repo".into(),
+            processes,
+            env: env
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            redacted: Vec::new(),
+            ports: Vec::new(),
+            git: None,
+        };
+        let old = snap(
+            vec![proc("node  server.js"), proc("postgres -D data")],
+            &[("PORT", "3000"), ("DEBUG", "1")],
+        );
+        let new = snap(
+            vec![proc("node server.js"), proc("redis-server")],
+            &[("PORT", "4000"), ("CI", "1")],
+        );
+
+        assert_eq!(
+            diff_snapshots(&old, &new),
+            SnapshotDiff {
+                processes_added: vec!["redis-server".into()],
+                processes_removed: vec!["postgres -D data".into()],
+                env_added: vec!["CI".into()],
+                env_removed: vec!["DEBUG".into()],
+                env_changed: vec!["PORT".into()],
+            }
+        );
+        assert!(diff_snapshots(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn filter_matches_language_processes_services_and_project_paths() {
+        let services = [ServiceDef {
+            name: "worker".into(),
+            command: "celery -A tasks worker".into(),
+            depends_on: Vec::new(),
+            ready_when: None,
+            ready_timeout_secs: None,
+        }];
+        let filter = ProcessFilter::new(Path::new("/repo"), Language::Python, &services);
+
+        assert!(filter.matches("python3.12", "python3.12 manage.py runserver", None));
+        assert!(filter.matches("sh", "sh -c celery -A tasks worker", None));
+        assert!(filter.matches("vim", "vim notes.md", Some(Path::new("/repo/docs"))));
+        assert!(filter.matches("cat", "cat /repo/README.md", None));
+        assert!(filter.matches("cargo", "cargo run --manifest-path=/repo/Cargo.toml", None));
+        assert!(!filter.matches("cat", "cat /repo-old/README.md", None));
+        assert!(!filter.matches("vim", "vim x", Some(Path::new("/repository"))));
+        assert!(!filter.matches("pythonista", "pythonista", None));
+        assert!(!filter.matches("node", "node server.js", Some(Path::new("/elsewhere"))));
+    }
+
+    #[test]
+    fn secret_env_values_are_redacted_unless_included() {
+        let vars = || {
+            [
+                ("AWS_SECRET_ACCESS_KEY", "abc"),
+                ("DATABASE_URL", "postgres://app:pw@db/app"),
+                ("PORT", "3000"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        };
+
+        let redactor = Redactor::default();
+        let (env, redacted) = redact_env(vars(), CaptureOptions::default(), &redactor);
+        assert_eq!(redacted, ["AWS_SECRET_ACCESS_KEY", "DATABASE_URL"]);
+        assert_eq!(env[0].1, "<redacted>");
+        assert_eq!(env[1].1, "postgres://app:<redacted>@db/app");
+        assert_eq!(env[2].1, "3000");
+
+        let partial = CaptureOptions {
+            partial_redact: true,
+            ..CaptureOptions::default()
+        };
+        let (env, redacted) = redact_env(vars(), partial, &redactor);
+        assert_eq!(redacted, ["AWS_SECRET_ACCESS_KEY", "DATABASE_URL"]);
+        assert_eq!(env[0].1, "****");
+        assert_eq!(env[1].1, "postgres://app:****@db/app");
+
+        let include = CaptureOptions {
+            include_secrets: true,
+            ..CaptureOptions::default()
+        };
+        let (env, redacted) = redact_env(vars(), include, &redactor);
+        assert!(redacted.is_empty());
+        assert_eq!(env[0].1, "abc");
+    }
+
+    #[test]
+    fn unversioned_snapshots_still_parse() {
+        let snap: Snapshot = serde_json::from_str(
+            r#"{"saved_at":"2024-01-01T00:00:00Z","cwd":"/repo","processes":[],"env":[]}"#,
+        )
+        .expect("v1 snapshot");
+        assert_eq!(snap.schema_version, 1);
+        assert!(snap.ports.is_empty());
+        assert!(snap.git.is_none());
+    }
+
+    #[test]
+    fn parse_migrates_old_and_refuses_newer_snapshots() {
+        let v1 = r#"{"saved_at":"2024-01-01T00:00:00Z","cwd":"/repo","processes":[],"env":[]}"#;
+        let snap = parse_snapshot(v1).expect("v1 snapshot");
+        assert_eq!(snap.schema_version, SNAPSHOT_SCHEMA_VERSION);
+        assert!(snap.ports.is_empty());
+
+        let newer = format!(
+            r#"{{"schema_version":{},"saved_at":"2024-01-01T00:00:00Z"}}"#,
+            SNAPSHOT_SCHEMA_VERSION + 1
+        );
+        let err = parse_snapshot(&newer).unwrap_err().to_string();
+        assert!(err.contains("created by a newer devflow"), "{err}");
+
+        let err = parse_snapshot(r#"{"schema_version":0}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("unknown schema_version 0"), "{err}");
+    }
+
+    #[test]
+    fn parse_points_at_corrupt_and_truncated_files() {
+        let err = parse_snapshot("{\n  \"saved_at\": oops\n}")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "snapshot file is corrupt at line 2, column 15: expected value"
+        );
+
+        let err = parse_snapshot("{\n  \"saved_at\": \"2024")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("ends early at line 2"), "{err}");
+
+        let current = format!(
+            "{{\"schema_version\":{SNAPSHOT_SCHEMA_VERSION},\n\"saved_at\":\"2024-01-01T00:00:00Z\",\n\"cwd\":1}}"
+        );
+        let err = parse_snapshot(&current).unwrap_err().to_string();
+        assert!(err.contains("corrupt at line 3"), "{err}");
+    }
+
+    #[test]
+    fn git_state_reads_branch_and_dirty_flag() {
+        let td = tempfile::tempdir().expect("tempdir");
+        let git = |args: &[&str]| {
+            let ok = std::process::Command::new("git")
+                .arg("-C")
+                .arg(td.path())
+                .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+                .args(args)
+                .output()
+                .is_ok_and(|o| o.status.success());
+            assert!(ok, "git {args:?}");
+        };
+        if std::process::Command::new("git")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+        assert!(git_state(td.path()).is_none());
+
+        git(&["init", "-q", "-b", "main"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "init"]);
+        let state = git_state(td.path()).expect("git state");
+        assert_eq!(state.branch.as_deref(), Some("main"));
+        assert_eq!(state.head.len(), 40);
+        assert!(!state.dirty);
+
+        fs::write(td.path().join("new.txt"), "x").expect("write");
+        assert!(git_state(td.path()).expect("git state").dirty);
+    }
+
+    #[test]
+    fn capture_fails_on_an_invalid_config() {
+        let td = tempfile::tempdir().expect("tempdir");
+        fs::write(td.path().join(".devflow.yaml"), "services: [oops").expect("write");
+        let err = capture_snapshot(
+            td.path(),
+            None,
+            None,
+            CaptureOptions::default(),
+            &Redactor::default(),
+        )
+        .expect_err("invalid config");
+        assert!(err.to_string().contains("invalid .devflow.yaml"), "{err}");
+    }
+
+    #[test]
+    fn rejects_names_that_escape_the_snapshot_dir() {
+        assert!(snapshot_path("../env").is_err());
+        assert!(snapshot_path(".hidden").is_err());
+        assert!(snapshot_path("").is_err());
+        assert_eq!(
+            snapshot_path("before-upgrade").expect("valid"),
+            ".devflow/snapshots/before-upgrade.json"
+        );
+    }
 }
@@ -1,6 +1,7 @@
 use devflow::utils::{
-    envcheck::validate_env_schema,
+    envcheck::{validate_env_schema, SchemaSpec},
     language::{detect_project_language, Language},
+    ports::PortRange,
     sanitize::redact,
 };
 use std::collections::HashMap;
@@ -14,7 +15,7 @@ fn redact_masks_secret_values() {
 
 #[test]
 fn env_schema_detects_missing_key() {
-    let schema = HashMap::from([("PORT".to_string(), "int".to_string())]);
+    let schema = HashMap::from([("PORT".to_string(), SchemaSpec::parse("int"))]);
     let actual = HashMap::new();
     let issues = validate_env_schema(&schema, &actual);
     assert_eq!(issues.len(), 1);
@@ -27,3 +28,14 @@ fn language_detects_node_repo() {
     std::fs::write(td.path().join("package.json"), "{}").expect("write");
     assert_eq!(detect_project_language(td.path()), Language::Node);
 }
+
+#[test]
+fn port_range_parses_and_validates_bounds() {
+    let range: PortRange = "3000-3005".parse().expect("range");
+    assert_eq!(range.ports().count(), 6);
+    assert!("3005-3000".parse::<PortRange>().is_err());
+    let err = "0-65535".parse::<PortRange>().expect_err("too wide");
+    assert!(err.contains("wider than 65535 ports"), "{err}");
+    assert!("1-65535".parse::<PortRange>().is_ok());
+    assert!("3000".parse::<PortRange>().is_err());
+}